@@ -0,0 +1,142 @@
+use crate::config;
+use crate::git;
+use crate::jj;
+use crate::sign;
+use anyhow::Result;
+use chrono::Utc;
+use git2::Oid;
+use std::collections::HashSet;
+
+pub fn run(teammate: &str, saves: &[String]) -> Result<()> {
+    let repo = git::discover()?;
+    let user = config::get_user(&repo)?;
+    let weft = config::get_active_weft(&repo, &user)?;
+
+    let (owner, their_weft) = match teammate.split_once('/') {
+        Some((owner, name)) => (owner, name),
+        None => (teammate, config::DEFAULT_WEFT),
+    };
+
+    let source = match repo.find_reference(&git::team_ref(owner, their_weft)) {
+        Ok(ref_) => ref_.peel_to_commit()?.id(),
+        Err(_) => {
+            return Err(anyhow::anyhow!(
+                "No shared weft '{}/{}'. Run 'weft fetch' first.",
+                owner,
+                their_weft
+            ));
+        }
+    };
+
+    let old_head = match git::get_weft_head(&repo, &user, &weft)? {
+        Some(oid) => oid,
+        None => {
+            return Err(anyhow::anyhow!(
+                "Weft not initialized. Run 'weft init' first."
+            ));
+        }
+    };
+
+    let trunk = git::get_trunk(&repo)?;
+    let mut shared = git::commits_between(&repo, trunk, source)?;
+    shared.reverse();
+
+    let selected = if saves.is_empty() {
+        shared
+    } else {
+        let mut wanted = HashSet::new();
+        for save in saves {
+            let oid = repo.revparse_single(save)?.peel_to_commit()?.id();
+            if !shared.contains(&oid) {
+                return Err(anyhow::anyhow!(
+                    "'{}' is not one of {}'s shared saves on top of trunk",
+                    save,
+                    owner
+                ));
+            }
+            wanted.insert(oid);
+        }
+        shared
+            .into_iter()
+            .filter(|oid| wanted.contains(oid))
+            .collect()
+    };
+
+    let previous = jj::current_commit(&repo)?;
+    let already_adopted: HashSet<Oid> = git::commits_between(&repo, trunk, previous)?
+        .into_iter()
+        .filter_map(|oid| {
+            let commit = repo.find_commit(oid).ok()?;
+            git::adopted_from(commit.message().unwrap_or("")).map(|(_, from)| from)
+        })
+        .collect();
+
+    let mut tip = previous;
+    let mut adopted = 0;
+    for oid in selected {
+        if already_adopted.contains(&oid) {
+            continue;
+        }
+
+        let save = repo.find_commit(oid)?;
+        let onto = repo.find_commit(tip)?;
+        let mut index = repo.cherrypick_commit(&save, &onto, 0, None)?;
+        if index.has_conflicts() {
+            return Err(anyhow::anyhow!(
+                "Cannot adopt {} ({}): it conflicts with your weft. Run 'weft sync' first or pick other saves.",
+                &oid.to_string()[..8],
+                save.summary().unwrap_or("")
+            ));
+        }
+
+        let tree = repo.find_tree(index.write_tree_to(&repo)?)?;
+        let message = format!(
+            "{}\n\n{} {} {}\n",
+            save.message().unwrap_or("").trim_end(),
+            git::ADOPTED_FROM,
+            owner,
+            oid
+        );
+        tip = sign::commit(
+            &repo,
+            &save.author(),
+            &repo.signature()?,
+            &message,
+            &tree,
+            &[&onto],
+        )?;
+        adopted += 1;
+    }
+
+    if adopted == 0 {
+        println!("Nothing to adopt from {}/{}", owner, their_weft);
+        return Ok(());
+    }
+
+    jj::new_on_imported(&repo, tip)?;
+    let head = jj::current_commit(&repo)?;
+    git::update_weft_head(&repo, &user, &weft, head, "weft adopt")?;
+
+    let op_entry = serde_json::json!({
+        "timestamp": Utc::now().timestamp(),
+        "command": "adopt",
+        "args": {"from": format!("{}/{}", owner, their_weft), "saves": saves},
+        "inverse": {
+            "op": "switch-weft",
+            "weft": weft,
+            "commit": previous.to_string(),
+            "ref": git::weft_ref(&user, &weft),
+            "old": old_head.to_string()
+        }
+    });
+
+    git::update_op_log(&repo, &op_entry.to_string())?;
+
+    println!(
+        "Adopted {} saves from {}/{} onto weft '{}'",
+        adopted, owner, their_weft, weft
+    );
+    println!("Duplicates are dropped automatically once they land in trunk.");
+
+    Ok(())
+}
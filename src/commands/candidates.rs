@@ -0,0 +1,83 @@
+use crate::config;
+use crate::format;
+use crate::git;
+use crate::loom;
+use anyhow::Result;
+
+pub fn run(mine: bool, json: bool, stale: bool) -> Result<()> {
+    let repo = git::discover()?;
+    let user = config::get_user(&repo)?;
+
+    if let Err(e) = loom::fetch(&repo) {
+        eprintln!("Warning: {}", e.to_string().trim_end());
+        eprintln!("Showing candidates known locally.");
+    }
+
+    let trunk = git::get_trunk(&repo)?;
+
+    let mut infos = Vec::new();
+    for candidate in loom::list(&repo)? {
+        if mine && loom::author(&repo, &candidate.id)? != user {
+            continue;
+        }
+        let info = loom::inspect(&repo, &candidate, trunk)?;
+        if stale && info.behind_trunk == 0 {
+            continue;
+        }
+        infos.push(info);
+    }
+
+    if json {
+        println!("{}", serde_json::to_string_pretty(&infos)?);
+        return Ok(());
+    }
+
+    if infos.is_empty() {
+        println!("No open candidates.");
+        return Ok(());
+    }
+
+    for info in &infos {
+        println!(
+            "{} ({}, {}, {})",
+            info.id,
+            info.author,
+            format::relative_time(info.created),
+            match info.revisions {
+                1 => "1 revision".to_string(),
+                n => format!("{} revisions", n),
+            }
+        );
+        if let Some(title) = &info.title {
+            println!("  {}", title);
+        }
+        println!(
+            "  {} commits, {} files changed, +{} -{}",
+            info.commits, info.files_changed, info.insertions, info.deletions
+        );
+
+        let state = if info.tangled { "tangled" } else { "clean" };
+        let applies = if info.unrelated {
+            "unrelated history".to_string()
+        } else if !info.applies {
+            "conflicts with trunk".to_string()
+        } else if info.behind_trunk > 0 {
+            format!("applies on trunk ({} behind)", info.behind_trunk)
+        } else {
+            "up to date with trunk".to_string()
+        };
+        println!("  {}, {}", state, applies);
+
+        if let Some(check) = &info.check {
+            let verdict = if check.passed { "passed" } else { "failed" };
+            println!(
+                "  check '{}' {} ({})",
+                check.command,
+                verdict,
+                format::relative_time(check.ran_at)
+            );
+        }
+    }
+
+    Ok(())
+}
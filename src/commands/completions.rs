@@ -0,0 +1,112 @@
+//! `weft completions`: shell completion scripts.
+//!
+//! The scripts don't list values themselves. On every tab they run
+//! `COMPLETE=<shell> weft -- <words>`, which `main` hands to clap_complete
+//! before anything else, so ids come from the repository as it is then. The
+//! functions below produce those values; they run with nothing on the
+//! terminal, so they swallow errors and return nothing instead.
+
+use crate::config;
+use crate::git;
+use crate::loom;
+use anyhow::Result;
+use clap::ValueEnum;
+use clap_complete::engine::CompletionCandidate;
+use clap_complete::env::{Bash, EnvCompleter, Fish, Zsh};
+use git2::Repository;
+
+/// The environment variable the scripts set when asking for completions.
+pub const COMPLETE_VAR: &str = "COMPLETE";
+
+#[derive(Clone, Copy, ValueEnum)]
+pub enum Shell {
+    Bash,
+    Zsh,
+    Fish,
+}
+
+pub fn run(shell: Shell, mut cmd: clap::Command) -> Result<()> {
+    let completer: &dyn EnvCompleter = match shell {
+        Shell::Bash => &Bash,
+        Shell::Zsh => &Zsh,
+        Shell::Fish => &Fish,
+    };
+
+    cmd.build();
+    let name = cmd.get_name().to_string();
+    completer.write_registration(COMPLETE_VAR, &name, &name, &name, &mut std::io::stdout())?;
+    Ok(())
+}
+
+/// Open candidate ids, with their titles.
+pub fn candidate_ids() -> Vec<CompletionCandidate> {
+    with_repo(|repo| {
+        let mut values = Vec::new();
+        for candidate in loom::list(repo)? {
+            let title = loom::read_meta(repo, &candidate.id)?.map(|meta| meta.title);
+            values.push(CompletionCandidate::new(&candidate.id).help(title.map(Into::into)));
+        }
+        Ok(values)
+    })
+}
+
+/// Teammates' shared wefts as `adopt` takes them: the teammate's name for
+/// their default weft, `<teammate>/<weft>` for the others.
+pub fn team_wefts() -> Vec<CompletionCandidate> {
+    with_repo(|repo| {
+        let mut values = Vec::new();
+        for weft in git::list_team_wefts(repo)? {
+            let value = if weft.weft == config::DEFAULT_WEFT {
+                weft.user.clone()
+            } else {
+                format!("{}/{}", weft.user, weft.weft)
+            };
+            let summary = repo.find_commit(weft.head)?.summary().map(String::from);
+            values.push(CompletionCandidate::new(value).help(summary.map(Into::into)));
+        }
+        Ok(values)
+    })
+}
+
+/// Short ids of the saves teammates have shared on top of trunk.
+pub fn team_saves() -> Vec<CompletionCandidate> {
+    with_repo(|repo| {
+        let trunk = git::get_trunk(repo)?;
+        let mut values = Vec::new();
+        for weft in git::list_team_wefts(repo)? {
+            for oid in git::commits_between(repo, trunk, weft.head)? {
+                let commit = repo.find_commit(oid)?;
+                let help = format!(
+                    "{}/{}: {}",
+                    weft.user,
+                    weft.weft,
+                    commit.summary().unwrap_or("")
+                );
+                values
+                    .push(CompletionCandidate::new(&oid.to_string()[..8]).help(Some(help.into())));
+            }
+        }
+        Ok(values)
+    })
+}
+
+/// The current user's named wefts.
+pub fn own_wefts() -> Vec<CompletionCandidate> {
+    with_repo(|repo| {
+        let user = config::get_user(repo)?;
+        let mut values = Vec::new();
+        for (name, oid) in git::list_wefts(repo, &user)? {
+            let summary = repo.find_commit(oid)?.summary().map(String::from);
+            values.push(CompletionCandidate::new(name).help(summary.map(Into::into)));
+        }
+        Ok(values)
+    })
+}
+
+fn with_repo(
+    values: impl FnOnce(&Repository) -> Result<Vec<CompletionCandidate>>,
+) -> Vec<CompletionCandidate> {
+    git::discover()
+        .and_then(|repo| values(&repo))
+        .unwrap_or_default()
+}
@@ -0,0 +1,164 @@
+use crate::git;
+use crate::jj;
+use crate::loom;
+use anyhow::Result;
+use git2::{Diff, DiffFormat, Oid, Repository};
+use std::io::Write;
+
+pub fn run(candidate_id: &str, revs: Option<&str>) -> Result<()> {
+    let repo = git::discover()?;
+
+    if let Err(e) = loom::fetch(&repo) {
+        eprintln!("Warning: {}", e.to_string().trim_end());
+        eprintln!("Showing revisions known locally.");
+    }
+
+    let range = revision_range(&repo, candidate_id, revs)?;
+    let (from, from_oid, to, to_oid) = (range.from, range.from_oid, range.to, range.to_oid);
+
+    println!(
+        "Changes in '{}' from revision {} ({}) to revision {} ({}):",
+        candidate_id,
+        from,
+        &from_oid.to_string()[..8],
+        to,
+        &to_oid.to_string()[..8]
+    );
+
+    if let Ok(trunk) = git::get_trunk(&repo) {
+        let from_base = repo.merge_base(from_oid, trunk).ok();
+        let to_base = repo.merge_base(to_oid, trunk).ok();
+        if from_base != to_base {
+            println!(
+                "Note: the revisions are based on different trunk commits, \
+                 so the diff includes changes from trunk."
+            );
+        }
+    }
+    println!();
+
+    let diff = repo.diff_tree_to_tree(
+        Some(&repo.find_commit(from_oid)?.tree()?),
+        Some(&repo.find_commit(to_oid)?.tree()?),
+        None,
+    )?;
+
+    if diff.deltas().len() == 0 {
+        println!("No changes between these revisions.");
+        return Ok(());
+    }
+
+    print_patch(&diff)
+}
+
+/// Two revisions of a candidate to compare.
+pub struct RevisionRange {
+    pub from: u32,
+    pub from_oid: Oid,
+    pub to: u32,
+    pub to_oid: Oid,
+}
+
+/// Resolves `revs` (`A..B`, default the last two revisions) on a candidate.
+pub fn revision_range(
+    repo: &Repository,
+    candidate_id: &str,
+    revs: Option<&str>,
+) -> Result<RevisionRange> {
+    let candidate = match loom::find(repo, candidate_id)? {
+        Some(candidate) => candidate,
+        None => {
+            return Err(anyhow::anyhow!(
+                "Candidate '{}' not found. Run 'weft candidates' to see open candidates.",
+                candidate_id
+            ));
+        }
+    };
+
+    let latest = candidate.latest_revision();
+    let (from, to) = match revs {
+        Some(revs) => parse_revs(revs)?,
+        None if latest > 1 => (latest - 1, latest),
+        None => {
+            return Err(anyhow::anyhow!(
+                "Candidate '{}' has only one revision, so there is nothing to compare.",
+                candidate_id
+            ));
+        }
+    };
+
+    Ok(RevisionRange {
+        from,
+        from_oid: revision(&candidate, from)?,
+        to,
+        to_oid: revision(&candidate, to)?,
+    })
+}
+
+/// The weft's changes against trunk as a patch, including unsaved work.
+pub fn weft_patch(repo: &Repository) -> Result<String> {
+    let snapshot = jj::current_commit(repo)?;
+    let trunk = git::get_trunk(repo)?;
+    let base = repo.merge_base(snapshot, trunk)?;
+
+    let diff = repo.diff_tree_to_tree(
+        Some(&repo.find_commit(base)?.tree()?),
+        Some(&repo.find_commit(snapshot)?.tree()?),
+        None,
+    )?;
+    patch_text(&diff)
+}
+
+/// Writes `diff` to stdout as a unified patch.
+pub fn print_patch(diff: &Diff) -> Result<()> {
+    write_patch(diff, &mut std::io::stdout().lock())
+}
+
+/// `diff` as a unified patch.
+pub fn patch_text(diff: &Diff) -> Result<String> {
+    let mut patch = Vec::new();
+    write_patch(diff, &mut patch)?;
+    Ok(String::from_utf8_lossy(&patch).into_owned())
+}
+
+fn write_patch(diff: &Diff, out: &mut impl Write) -> Result<()> {
+    diff.print(DiffFormat::Patch, |_, _, line| {
+        let prefix = match line.origin() {
+            '+' | '-' | ' ' => vec![line.origin() as u8],
+            _ => Vec::new(),
+        };
+        out.write_all(&prefix).is_ok() && out.write_all(line.content()).is_ok()
+    })?;
+    Ok(())
+}
+
+/// Parses `A..B` into a pair of revision numbers.
+fn parse_revs(revs: &str) -> Result<(u32, u32)> {
+    let parsed = revs
+        .split_once("..")
+        .and_then(|(from, to)| Some((from.parse().ok()?, to.parse().ok()?)));
+
+    match parsed {
+        Some((from, to)) if from > 0 && to > 0 => Ok((from, to)),
+        _ => Err(anyhow::anyhow!(
+            "Invalid revision range '{}'. Use the form 1..3.",
+            revs
+        )),
+    }
+}
+
+fn revision(candidate: &loom::Candidate, rev: u32) -> Result<Oid> {
+    candidate
+        .revisions
+        .iter()
+        .find(|(r, _)| *r == rev)
+        .map(|(_, oid)| *oid)
+        .ok_or_else(|| {
+            anyhow::anyhow!(
+                "Candidate '{}' has no revision {} (it has {}).",
+                candidate.id,
+                rev,
+                candidate.revisions.len()
+            )
+        })
+}
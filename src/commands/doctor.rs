@@ -0,0 +1,583 @@
+//! `weft doctor`: checks the pieces weft depends on, one at a time, and with
+//! `--fix` repairs those it can without losing anything. Without `--fix` or
+//! `--probe` it changes nothing, locally or on origin.
+
+use crate::commands::queue;
+use crate::config;
+use crate::daemon;
+use crate::git;
+use crate::jj;
+use anyhow::{Context, Result};
+use chrono::Utc;
+use git2::{Oid, Repository};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::time::{Duration, SystemTime};
+
+/// Git leaves `index.lock` behind when it is killed; one this old is not
+/// held by a running git.
+const INDEX_LOCK_AGE: Duration = Duration::from_secs(10 * 60);
+
+#[derive(PartialEq)]
+enum Health {
+    Ok,
+    Warn,
+    Fail,
+}
+
+/// What `--fix` does about a finding.
+enum Repair {
+    RemoveFile(PathBuf),
+    Colocate,
+    /// Make this commit jj's working copy again.
+    Edit(Oid),
+    /// Rewrite the op-log with only the lines that parse.
+    RewriteOpLog(String),
+    CreateOpLog,
+    /// Create the main branch at this commit.
+    CreateMain(Oid),
+}
+
+struct Finding {
+    check: &'static str,
+    health: Health,
+    detail: String,
+    repair: Option<Repair>,
+}
+
+impl Finding {
+    fn new(check: &'static str, health: Health, detail: impl Into<String>) -> Self {
+        Finding {
+            check,
+            health,
+            detail: detail.into(),
+            repair: None,
+        }
+    }
+
+    fn repair(mut self, repair: Repair) -> Self {
+        self.repair = Some(repair);
+        self
+    }
+}
+
+pub fn run(fix: bool, probe: bool) -> Result<()> {
+    let repo = git::discover()?;
+
+    let mut problems = 0;
+    let mut fixable = 0;
+    for finding in diagnose(&repo, probe) {
+        let label = match finding.health {
+            Health::Ok => "ok  ",
+            Health::Warn => "warn",
+            Health::Fail => "FAIL",
+        };
+        println!("[{}] {}: {}", label, finding.check, finding.detail);
+
+        let mut resolved = finding.health == Health::Ok;
+        if let Some(repair) = &finding.repair {
+            if fix {
+                match apply(&repo, repair) {
+                    Ok(done) => {
+                        println!("       fixed: {}", done);
+                        resolved = true;
+                    }
+                    Err(e) => println!("       fix failed: {:#}", e),
+                }
+            } else {
+                fixable += 1;
+            }
+        }
+        if !resolved && finding.health == Health::Fail {
+            problems += 1;
+        }
+    }
+
+    if fixable > 0 {
+        println!("\nRun 'weft doctor --fix' to repair {} of these.", fixable);
+    }
+    if problems > 0 {
+        return Err(anyhow::anyhow!(
+            "weft doctor found {} problem{}",
+            problems,
+            if problems == 1 { "" } else { "s" }
+        ));
+    }
+    Ok(())
+}
+
+fn diagnose(repo: &Repository, probe: bool) -> Vec<Finding> {
+    let mut findings = Vec::new();
+
+    let jj_ok = match crate::check_jj() {
+        Ok(()) => {
+            let version = jj::version().map(|v| v.to_string()).unwrap_or_default();
+            findings.push(Finding::new("jj", Health::Ok, format!("jj {}", version)));
+            true
+        }
+        Err(e) => {
+            let first = e.to_string().lines().next().unwrap_or("").to_string();
+            findings.push(Finding::new("jj", Health::Fail, first));
+            false
+        }
+    };
+
+    findings.push(colocation(repo, jj_ok));
+    findings.push(weft_head(repo, jj_ok));
+    findings.push(op_log(repo));
+    findings.push(trunk(repo));
+    findings.push(remote(repo, probe));
+    findings.extend(locks(repo));
+
+    findings
+}
+
+fn colocation(repo: &Repository, jj_ok: bool) -> Finding {
+    let workdir = match repo.workdir() {
+        Some(workdir) => workdir,
+        None => return Finding::new("colocation", Health::Fail, "repository is bare"),
+    };
+
+    let jj_dir = workdir.join(".jj");
+    if !jj_dir.is_dir() {
+        let finding = Finding::new("colocation", Health::Fail, "no jj repository here");
+        return if jj_ok {
+            finding.repair(Repair::Colocate)
+        } else {
+            finding
+        };
+    }
+
+    // A colocated jj repo points its store at the `.git` next to it; one
+    // that keeps its own git store can't see weft's refs.
+    let target = fs::read_to_string(jj_dir.join("repo/store/git_target")).unwrap_or_default();
+    let target = jj_dir.join("repo/store").join(target.trim());
+    match (target.canonicalize(), repo.path().canonicalize()) {
+        (Ok(target), Ok(git_dir)) if target == git_dir => {
+            Finding::new("colocation", Health::Ok, "jj and git share .git")
+        }
+        _ => Finding::new(
+            "colocation",
+            Health::Fail,
+            "jj keeps its own git store; re-create it with 'jj git init --colocate'",
+        ),
+    }
+}
+
+fn weft_head(repo: &Repository, jj_ok: bool) -> Finding {
+    let user = match config::get_user(repo) {
+        Ok(user) => user,
+        Err(e) => return Finding::new("weft head", Health::Fail, format!("{:#}", e)),
+    };
+    let weft = config::get_active_weft(repo, &user).unwrap_or_else(|_| config::DEFAULT_WEFT.into());
+    let name = git::weft_ref(&user, &weft);
+
+    let head = match git::get_weft_head(repo, &user, &weft) {
+        Ok(Some(head)) => head,
+        Ok(None) => {
+            let hint = match git::list_wefts(repo, &user) {
+                Ok(wefts) if !wefts.is_empty() => "switch to another with 'weft switch'",
+                _ => "run 'weft init'",
+            };
+            return Finding::new(
+                "weft head",
+                Health::Fail,
+                format!("{} is missing; {}", name, hint),
+            );
+        }
+        Err(e) => return Finding::new("weft head", Health::Fail, format!("{}: {:#}", name, e)),
+    };
+    if !jj_ok {
+        return Finding::new(
+            "weft head",
+            Health::Warn,
+            format!("{} exists; jj not checked", name),
+        );
+    }
+
+    let current = match jj::current_commit(repo) {
+        Ok(current) => current,
+        Err(e) => return Finding::new("weft head", Health::Fail, format!("{:#}", e)),
+    };
+    if current == head {
+        return Finding::new(
+            "weft head",
+            Health::Ok,
+            format!("{} is jj's working copy", name),
+        );
+    }
+
+    // jj rewrites the working-copy commit as files change, so the same
+    // change id just means there are unsaved changes.
+    match (jj::change_id(repo, current), jj::change_id(repo, head)) {
+        (Ok(a), Ok(b)) if a == b => Finding::new(
+            "weft head",
+            Health::Ok,
+            format!("{} is jj's working copy, with unsaved changes", name),
+        ),
+        _ => Finding::new(
+            "weft head",
+            Health::Fail,
+            format!(
+                "jj's working copy is {}, not {} ({})",
+                &current.to_string()[..8],
+                name,
+                &head.to_string()[..8]
+            ),
+        )
+        .repair(Repair::Edit(head)),
+    }
+}
+
+fn op_log(repo: &Repository) -> Finding {
+    let content = match git::get_op_log(repo) {
+        Ok(Some(content)) => content,
+        Ok(None) => {
+            return Finding::new("op-log", Health::Fail, "refs/weft/op-log is missing")
+                .repair(Repair::CreateOpLog)
+        }
+        Err(e) => {
+            return Finding::new("op-log", Health::Fail, format!("{:#}", e))
+                .repair(Repair::CreateOpLog)
+        }
+    };
+
+    let mut kept = String::new();
+    let mut bad = Vec::new();
+    let mut entries = 0;
+    for (number, line) in content.lines().enumerate() {
+        if line.trim().is_empty() {
+            continue;
+        }
+        match serde_json::from_str::<serde_json::Value>(line) {
+            Ok(entry) if entry["command"].is_string() => {
+                entries += 1;
+                kept.push_str(line);
+                kept.push('\n');
+            }
+            _ => bad.push((number + 1).to_string()),
+        }
+    }
+
+    if bad.is_empty() {
+        Finding::new(
+            "op-log",
+            Health::Ok,
+            format!("{} entr{}", entries, if entries == 1 { "y" } else { "ies" }),
+        )
+    } else {
+        Finding::new(
+            "op-log",
+            Health::Fail,
+            match bad.as_slice() {
+                [line] => format!("line {} does not parse", line),
+                lines => format!("lines {} do not parse", lines.join(", ")),
+            },
+        )
+        .repair(Repair::RewriteOpLog(kept))
+    }
+}
+
+/// Looks trunk up without [`git::get_main`], which creates a missing main.
+fn trunk(repo: &Repository) -> Finding {
+    let main = git::main_branch(repo);
+    if let Ok(oid) = git::get_origin_main(repo) {
+        return Finding::new(
+            "trunk",
+            Health::Ok,
+            format!("origin/{} at {}", main, &oid.to_string()[..8]),
+        );
+    }
+
+    let local = repo
+        .find_reference(&format!("refs/heads/{}", main))
+        .and_then(|reference| reference.peel_to_commit());
+    if let Ok(commit) = local {
+        return Finding::new(
+            "trunk",
+            Health::Warn,
+            format!(
+                "{} at {}; no origin/{}, run 'git fetch origin'",
+                main,
+                &commit.id().to_string()[..8],
+                main
+            ),
+        );
+    }
+
+    match repo.head().and_then(|head| head.peel_to_commit()) {
+        Ok(head) => Finding::new(
+            "trunk",
+            Health::Warn,
+            "no main or master branch; weft would create main at HEAD",
+        )
+        .repair(Repair::CreateMain(head.id())),
+        Err(e) => Finding::new("trunk", Health::Fail, e.message().to_string()),
+    }
+}
+
+/// Runs `git push --porcelain` against origin.
+fn push(repo: &Repository, args: &[String]) -> std::io::Result<std::process::Output> {
+    Command::new("git")
+        .args(["push", "--porcelain", "origin"])
+        .args(args)
+        .current_dir(repo.path())
+        .output()
+}
+
+/// Asks origin whether it takes weft's and loom's refs. A dry run only shows
+/// that origin is reachable, since server hooks and permissions never run for
+/// it; with `probe`, probe refs are really pushed and then deleted.
+fn remote(repo: &Repository, probe: bool) -> Finding {
+    if repo.find_remote("origin").is_err() {
+        return Finding::new(
+            "remote",
+            Health::Warn,
+            "no origin; share, propose and weave need one",
+        );
+    }
+    let head = match repo.head().and_then(|head| head.peel_to_commit()) {
+        Ok(commit) => commit.id(),
+        Err(e) => return Finding::new("remote", Health::Fail, e.message().to_string()),
+    };
+
+    let probes = [
+        "refs/weft/weft-doctor/probe",
+        "refs/loom/weft-doctor-probe/1",
+    ];
+    let mut args: Vec<String> = probes
+        .iter()
+        .map(|probe| format!("{}:{}", head, probe))
+        .collect();
+    // Atomic, so a rejection leaves no probe behind to clean up.
+    args.insert(0, if probe { "--atomic" } else { "--dry-run" }.to_string());
+    let output = match push(repo, &args) {
+        Ok(output) => output,
+        Err(e) => return Finding::new("remote", Health::Fail, format!("cannot run git: {}", e)),
+    };
+
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let rejected: Vec<&str> = stdout
+        .lines()
+        .filter(|line| line.starts_with('!'))
+        .filter_map(|line| line.split('\t').nth(1))
+        .collect();
+    if output.status.success() && !probe {
+        Finding::new(
+            "remote",
+            Health::Ok,
+            "origin is reachable; 'weft doctor --probe' checks it accepts refs/weft/* and refs/loom/*",
+        )
+    } else if output.status.success() {
+        let deletes: Vec<String> = probes.iter().map(|probe| format!(":{}", probe)).collect();
+        match push(repo, &deletes) {
+            Ok(deleted) if deleted.status.success() => Finding::new(
+                "remote",
+                Health::Ok,
+                "origin accepts refs/weft/* and refs/loom/*",
+            ),
+            _ => Finding::new(
+                "remote",
+                Health::Warn,
+                format!(
+                    "origin accepts refs/weft/* and refs/loom/*, but deleting {} failed",
+                    probes.join(" and ")
+                ),
+            ),
+        }
+    } else if !rejected.is_empty() {
+        Finding::new(
+            "remote",
+            Health::Fail,
+            format!("origin rejects {}", rejected.join(", ")),
+        )
+    } else {
+        Finding::new(
+            "remote",
+            Health::Fail,
+            String::from_utf8_lossy(&output.stderr)
+                .lines()
+                .last()
+                .unwrap_or("git push failed")
+                .to_string(),
+        )
+    }
+}
+
+fn locks(repo: &Repository) -> Vec<Finding> {
+    let mut findings = Vec::new();
+
+    let queue_lock = repo.path().join("weft").join(queue::LOCK_FILE);
+    if queue_lock.exists() {
+        let pid = fs::read_to_string(&queue_lock).unwrap_or_default();
+        let pid = pid.trim();
+        if !pid.is_empty() && process_alive(pid) {
+            findings.push(Finding::new(
+                "queue lock",
+                Health::Ok,
+                format!("held by 'weft queue run' (pid {})", pid),
+            ));
+        } else {
+            findings.push(
+                Finding::new(
+                    "queue lock",
+                    Health::Fail,
+                    "left behind by a 'weft queue run' that is not running",
+                )
+                .repair(Repair::RemoveFile(queue_lock)),
+            );
+        }
+    }
+
+    let socket = daemon::socket_path(repo);
+    if socket.exists() {
+        if serve_answers(&socket) {
+            findings.push(Finding::new("daemon", Health::Ok, "weft serve is running"));
+        } else {
+            findings.push(
+                Finding::new(
+                    "daemon",
+                    Health::Fail,
+                    "socket left behind by a stopped weft serve",
+                )
+                .repair(Repair::RemoveFile(socket)),
+            );
+        }
+    }
+
+    let index_lock = repo.path().join("index.lock");
+    if let Ok(meta) = fs::metadata(&index_lock) {
+        let age = meta
+            .modified()
+            .ok()
+            .and_then(|modified| SystemTime::now().duration_since(modified).ok())
+            .unwrap_or_default();
+        if age >= INDEX_LOCK_AGE {
+            findings.push(
+                Finding::new(
+                    "index lock",
+                    Health::Fail,
+                    format!(
+                        "{} minutes old; git was killed mid-operation",
+                        age.as_secs() / 60
+                    ),
+                )
+                .repair(Repair::RemoveFile(index_lock)),
+            );
+        } else {
+            findings.push(Finding::new(
+                "index lock",
+                Health::Warn,
+                "a git command is running; check again when it finishes",
+            ));
+        }
+    }
+
+    findings
+}
+
+fn apply(repo: &Repository, repair: &Repair) -> Result<String> {
+    match repair {
+        Repair::RemoveFile(path) => {
+            fs::remove_file(path).with_context(|| format!("Cannot remove {}", path.display()))?;
+            Ok(format!("removed {}", path.display()))
+        }
+        Repair::Colocate => {
+            jj::colocate(repo)?;
+            Ok("ran 'jj git init --colocate'".to_string())
+        }
+        Repair::Edit(head) => {
+            let previous = jj::current_commit(repo)?;
+            jj::edit(repo, *head)?;
+            Ok(format!(
+                "jj's working copy is back on your weft; it was on {}, still in 'jj log'",
+                &previous.to_string()[..8]
+            ))
+        }
+        Repair::RewriteOpLog(kept) => {
+            let backup = backup_op_log(repo)?;
+            let blob = repo.blob(kept.as_bytes())?;
+            repo.reference(
+                "refs/weft/op-log",
+                blob,
+                true,
+                "weft doctor: drop unparseable entries",
+            )?;
+            Ok(format!(
+                "dropped the bad lines; the old op-log is in {}",
+                backup.display()
+            ))
+        }
+        Repair::CreateOpLog => {
+            let backup = backup_op_log(repo)?;
+            let blob = repo.blob(b"")?;
+            repo.reference(
+                "refs/weft/op-log",
+                blob,
+                true,
+                "weft doctor: recreate op-log",
+            )?;
+            if backup.exists() {
+                Ok(format!(
+                    "started an empty op-log; the old one is in {}",
+                    backup.display()
+                ))
+            } else {
+                Ok("started an empty op-log".to_string())
+            }
+        }
+        Repair::CreateMain(head) => {
+            repo.reference("refs/heads/main", *head, false, "weft doctor: create main")?;
+            Ok(format!("created main at {}", &head.to_string()[..8]))
+        }
+    }
+}
+
+/// Copies whatever refs/weft/op-log points at to `.git/weft/`.
+fn backup_op_log(repo: &Repository) -> Result<PathBuf> {
+    let dir = repo.path().join("weft");
+    let path = dir.join(format!("op-log-{}.bak", Utc::now().timestamp()));
+    if let Ok(reference) = repo.find_reference("refs/weft/op-log") {
+        if let Ok(object) = reference.peel(git2::ObjectType::Any) {
+            fs::create_dir_all(&dir)?;
+            let content = match object.as_blob() {
+                Some(blob) => blob.content().to_vec(),
+                None => format!(
+                    "{} {}\n",
+                    object.kind().map_or("object", |k| k.str()),
+                    object.id()
+                )
+                .into_bytes(),
+            };
+            fs::write(&path, content)?;
+        }
+    }
+    Ok(path)
+}
+
+#[cfg(unix)]
+fn process_alive(pid: &str) -> bool {
+    Command::new("kill")
+        .args(["-0", pid])
+        .stderr(std::process::Stdio::null())
+        .status()
+        .map(|status| status.success())
+        .unwrap_or(false)
+}
+
+#[cfg(not(unix))]
+fn process_alive(_pid: &str) -> bool {
+    // Can't tell; assume the lock is held rather than remove it.
+    true
+}
+
+#[cfg(unix)]
+fn serve_answers(socket: &Path) -> bool {
+    std::os::unix::net::UnixStream::connect(socket).is_ok()
+}
+
+#[cfg(not(unix))]
+fn serve_answers(_socket: &Path) -> bool {
+    false
+}
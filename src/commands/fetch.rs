@@ -0,0 +1,43 @@
+use crate::git;
+use anyhow::{Context, Result};
+use git2::Repository;
+use std::process::Command;
+
+pub fn run() -> Result<()> {
+    let repo = git::discover()?;
+    let wefts = fetch_team(&repo)?;
+
+    println!("Fetched {} shared wefts from origin", wefts);
+    println!("\nNext steps:");
+    println!("  weft team  # See what your teammates are working on");
+
+    Ok(())
+}
+
+/// Fetches teammates' shared wefts and trunk, returning how many wefts there are.
+pub fn fetch_team(repo: &Repository) -> Result<usize> {
+    if repo.find_remote("origin").is_err() {
+        return Err(anyhow::anyhow!(
+            "No remote 'origin' configured. Add a remote with: git remote add origin <url>"
+        ));
+    }
+
+    let output = Command::new("git")
+        .args([
+            "fetch",
+            "--prune",
+            "origin",
+            "+refs/weft/*:refs/weft-team/*",
+            "+refs/heads/*:refs/remotes/origin/*",
+        ])
+        .current_dir(repo.path())
+        .output()
+        .context("Failed to run git fetch")?;
+
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(anyhow::anyhow!("Failed to fetch shared wefts: {}", stderr));
+    }
+
+    Ok(git::list_team_wefts(repo)?.len())
+}
@@ -0,0 +1,25 @@
+use crate::config;
+use crate::git;
+use anyhow::Result;
+
+pub fn run() -> Result<()> {
+    let repo = git::discover()?;
+    let user = config::get_user(&repo)?;
+    let active = config::get_active_weft(&repo, &user)?;
+
+    let wefts = git::list_wefts(&repo, &user)?;
+    if wefts.is_empty() {
+        println!("Weft not initialized. Run 'weft init' first.");
+        return Ok(());
+    }
+
+    for (name, oid) in &wefts {
+        let marker = if *name == active { "*" } else { " " };
+        let commit = repo.find_commit(*oid)?;
+        let summary = commit.summary().unwrap_or("");
+        let short = oid.to_string();
+        println!("{} {} ({}) {}", marker, name, &short[..8], summary);
+    }
+
+    Ok(())
+}
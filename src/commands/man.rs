@@ -0,0 +1,18 @@
+use anyhow::{Context, Result};
+use std::path::Path;
+
+/// Prints the `weft(1)` man page, or with `out` writes one page per command
+/// (`weft.1`, `weft-save.1`, ...) into that directory.
+pub fn run(cmd: clap::Command, out: Option<&Path>) -> Result<()> {
+    match out {
+        Some(dir) => {
+            std::fs::create_dir_all(dir)
+                .with_context(|| format!("Cannot create {}", dir.display()))?;
+            clap_mangen::generate_to(cmd, dir)
+                .with_context(|| format!("Cannot write man pages to {}", dir.display()))?;
+            println!("Wrote man pages to {}", dir.display());
+        }
+        None => clap_mangen::Man::new(cmd).render(&mut std::io::stdout())?,
+    }
+    Ok(())
+}
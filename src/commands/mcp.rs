@@ -0,0 +1,276 @@
+//! `weft mcp`: a Model Context Protocol server on stdin/stdout, so agents can
+//! drive weft through typed tools instead of parsing command output.
+//!
+//! Messages are newline-delimited JSON-RPC 2.0. Every tool call is recorded
+//! in the op-log with the name of the agent that made it.
+
+use crate::git;
+use crate::rpc::{
+    self, error_response, RpcError, INTERNAL_ERROR, INVALID_PARAMS, INVALID_REQUEST,
+    METHOD_NOT_FOUND, PARSE_ERROR,
+};
+use crate::Weft;
+use anyhow::Result;
+use chrono::Utc;
+use serde_json::{json, Value};
+use std::io::{self, BufRead, Write};
+
+/// The protocol revision offered when the client does not name one.
+const PROTOCOL_VERSION: &str = "2025-06-18";
+
+/// How many saves the `weft://log` resource lists.
+const LOG_LIMIT: usize = 50;
+
+struct Server {
+    weft: Weft,
+    /// Set by `--agent`, else taken from the client's `initialize` request.
+    agent: Option<String>,
+}
+
+pub fn run(agent: Option<&str>) -> Result<()> {
+    let mut server = Server {
+        weft: Weft::discover()?,
+        agent: agent.map(str::to_string),
+    };
+
+    let mut stdout = io::stdout().lock();
+    for line in io::stdin().lock().lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        let response = match serde_json::from_str::<Value>(&line) {
+            Ok(message) => server.handle(&message),
+            Err(e) => Some(error_response(
+                Value::Null,
+                RpcError::new(PARSE_ERROR, e.to_string()),
+            )),
+        };
+
+        if let Some(response) = response {
+            writeln!(stdout, "{}", response)?;
+            stdout.flush()?;
+        }
+    }
+
+    Ok(())
+}
+
+impl Server {
+    /// Answers one message; notifications get no response.
+    fn handle(&mut self, message: &Value) -> Option<Value> {
+        let method = message["method"].as_str();
+        let params = &message["params"];
+
+        let id = match message.get("id") {
+            Some(id) => id.clone(),
+            None => return None,
+        };
+
+        let result = match method {
+            Some("initialize") => Ok(self.initialize(params)),
+            Some("ping") => Ok(json!({})),
+            Some("tools/list") => Ok(json!({ "tools": tools() })),
+            Some("tools/call") => self.call_tool(params),
+            Some("resources/list") => Ok(json!({ "resources": resources() })),
+            Some("resources/read") => self.read_resource(params),
+            Some(method) => Err(RpcError::new(
+                METHOD_NOT_FOUND,
+                format!("Unknown method '{}'", method),
+            )),
+            None => Err(RpcError::new(INVALID_REQUEST, "Missing method")),
+        };
+
+        Some(match result {
+            Ok(result) => json!({ "jsonrpc": "2.0", "id": id, "result": result }),
+            Err(e) => error_response(id, e),
+        })
+    }
+
+    fn initialize(&mut self, params: &Value) -> Value {
+        if self.agent.is_none() {
+            self.agent = params["clientInfo"]["name"].as_str().map(str::to_string);
+        }
+
+        json!({
+            "protocolVersion": params["protocolVersion"].as_str().unwrap_or(PROTOCOL_VERSION),
+            "capabilities": {
+                "tools": {},
+                "resources": {}
+            },
+            "serverInfo": {
+                "name": "weft",
+                "version": env!("CARGO_PKG_VERSION")
+            },
+            "instructions": "weft keeps your work safe: save often, sync to pick up trunk, \
+                             and propose when the work is ready for review. \
+                             Tangled commits are conflicts left for later, not failures."
+        })
+    }
+
+    fn call_tool(&self, params: &Value) -> Result<Value, RpcError> {
+        let name = params["name"]
+            .as_str()
+            .ok_or_else(|| RpcError::new(INVALID_PARAMS, "Missing tool name"))?;
+        let args = match &params["arguments"] {
+            Value::Null => json!({}),
+            args => args.clone(),
+        };
+
+        if !tools().iter().any(|tool| tool["name"] == name) {
+            return Err(RpcError::new(
+                INVALID_PARAMS,
+                format!("Unknown tool '{}'", name),
+            ));
+        }
+
+        let outcome = rpc::call(&self.weft, name, &args)
+            .map(|value| value.expect("tool names are checked against tools()"));
+        self.record(name, &args, outcome.as_ref().err())
+            .map_err(|e| RpcError::new(INTERNAL_ERROR, e.to_string()))?;
+
+        // Tool failures are results the agent should read, not protocol errors.
+        Ok(match outcome {
+            Ok(value) => json!({
+                "content": [{
+                    "type": "text",
+                    "text": serde_json::to_string_pretty(&value).unwrap_or_default()
+                }],
+                "isError": false
+            }),
+            Err(e) => json!({
+                "content": [{ "type": "text", "text": format!("{:#}", e) }],
+                "isError": true
+            }),
+        })
+    }
+
+    fn read_resource(&self, params: &Value) -> Result<Value, RpcError> {
+        let uri = params["uri"]
+            .as_str()
+            .ok_or_else(|| RpcError::new(INVALID_PARAMS, "Missing resource uri"))?;
+
+        let contents = match uri {
+            "weft://log" => self.weft.log(LOG_LIMIT).map(|log| json!(log)),
+            "weft://tangled" => self.weft.status().map(|status| json!(status.tangled)),
+            _ => {
+                return Err(RpcError::new(
+                    INVALID_PARAMS,
+                    format!("Unknown resource '{}'", uri),
+                ));
+            }
+        }
+        .map_err(|e| RpcError::new(INTERNAL_ERROR, format!("{:#}", e)))?;
+
+        Ok(json!({
+            "contents": [{
+                "uri": uri,
+                "mimeType": "application/json",
+                "text": serde_json::to_string_pretty(&contents).unwrap_or_default()
+            }]
+        }))
+    }
+
+    /// Logs a tool call, with the agent that made it, for the record.
+    /// Undo skips these entries, like hook runs.
+    fn record(&self, tool: &str, args: &Value, error: Option<&anyhow::Error>) -> Result<()> {
+        let entry = json!({
+            "timestamp": Utc::now().timestamp(),
+            "command": "mcp",
+            "args": {
+                "agent": self.agent.as_deref().unwrap_or("unknown"),
+                "tool": tool,
+                "arguments": args,
+                "ok": error.is_none(),
+                "error": error.map(|e| format!("{:#}", e)),
+            },
+            "inverse": {
+                "op": "none"
+            }
+        });
+        git::update_op_log(self.weft.repo(), &entry.to_string())
+    }
+}
+
+fn tools() -> Vec<Value> {
+    let no_args = json!({ "type": "object", "properties": {} });
+    vec![
+        json!({
+            "name": "save",
+            "description": "Save the working copy to the active weft. Never blocks on conflicts.",
+            "inputSchema": {
+                "type": "object",
+                "properties": {
+                    "message": { "type": "string", "description": "What this save contains" }
+                },
+                "required": ["message"]
+            }
+        }),
+        json!({
+            "name": "sync",
+            "description": "Rebase the weft onto trunk. Conflicts become tangled commits instead of stopping.",
+            "inputSchema": no_args
+        }),
+        json!({
+            "name": "status",
+            "description": "The active weft, how far it is from trunk, unsaved work and tangled commits.",
+            "inputSchema": no_args
+        }),
+        json!({
+            "name": "diff",
+            "description": "The weft's changes against trunk including unsaved work, \
+                            or the changes between two revisions of a candidate.",
+            "inputSchema": {
+                "type": "object",
+                "properties": {
+                    "candidate": { "type": "string", "description": "Candidate to compare revisions of" },
+                    "revs": { "type": "string", "description": "Revisions as FROM..TO (default: the last two)" }
+                }
+            }
+        }),
+        json!({
+            "name": "undo",
+            "description": "Undo the last weft operation.",
+            "inputSchema": no_args
+        }),
+        json!({
+            "name": "oplog",
+            "description": "The most recent op-log entries, oldest first.",
+            "inputSchema": {
+                "type": "object",
+                "properties": {
+                    "limit": { "type": "integer", "minimum": 1, "description": "How many entries (default 20)" }
+                }
+            }
+        }),
+        json!({
+            "name": "propose",
+            "description": "Push the weft as a merge candidate, or as a new revision of its candidate.",
+            "inputSchema": {
+                "type": "object",
+                "properties": {
+                    "title": { "type": "string", "description": "Short title shown to reviewers" },
+                    "description": { "type": "string", "description": "Longer description of the candidate" }
+                }
+            }
+        }),
+    ]
+}
+
+fn resources() -> Vec<Value> {
+    vec![
+        json!({
+            "uri": "weft://log",
+            "name": "Weft log",
+            "description": "The latest saves on the active weft, newest first",
+            "mimeType": "application/json"
+        }),
+        json!({
+            "uri": "weft://tangled",
+            "name": "Tangled commits",
+            "description": "Commits on the active weft with unresolved conflicts",
+            "mimeType": "application/json"
+        }),
+    ]
+}
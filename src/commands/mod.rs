@@ -1,8 +1,26 @@
+pub mod adopt;
+pub mod candidates;
+pub mod completions;
+pub mod diff;
+pub mod doctor;
+pub mod fetch;
 pub mod init;
+pub mod list;
+pub mod man;
+pub mod mcp;
+pub mod new;
 pub mod propose;
+pub mod queue;
+pub mod review;
 pub mod save;
+pub mod serve;
 pub mod share;
 pub mod status;
+pub mod switch;
 pub mod sync;
+pub mod team;
+pub mod tui;
 pub mod undo;
+pub mod untangle;
 pub mod weave;
+pub mod web;
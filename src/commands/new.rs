@@ -0,0 +1,58 @@
+use crate::config;
+use crate::git;
+use crate::jj;
+use anyhow::Result;
+use chrono::Utc;
+
+pub fn run(name: &str) -> Result<()> {
+    let repo = git::discover()?;
+    let user = config::get_user(&repo)?;
+    let name = config::validate_weft_name(name)?;
+    let current = config::get_active_weft(&repo, &user)?;
+
+    if git::get_weft_head(&repo, &user, &name)?.is_some() {
+        return Err(anyhow::anyhow!(
+            "Weft '{}' already exists. Switch to it with: weft switch {}",
+            name,
+            name
+        ));
+    }
+
+    if git::get_weft_head(&repo, &user, &current)?.is_none() {
+        return Err(anyhow::anyhow!(
+            "Weft not initialized. Run 'weft init' first."
+        ));
+    }
+
+    let previous = jj::current_commit(&repo)?;
+    git::update_weft_head(&repo, &user, &current, previous, "weft new: snapshot")?;
+
+    let trunk = git::get_trunk(&repo)?;
+    jj::new_on(&repo, trunk)?;
+    let head = jj::current_commit(&repo)?;
+
+    git::update_weft_head(&repo, &user, &name, head, "weft new")?;
+    config::set_active_weft(&repo, &user, &name)?;
+
+    let op_entry = serde_json::json!({
+        "timestamp": Utc::now().timestamp(),
+        "command": "new",
+        "args": {"name": name},
+        "inverse": {
+            "op": "switch-weft",
+            "weft": current,
+            "commit": previous.to_string(),
+            "created": git::weft_ref(&user, &name)
+        }
+    });
+
+    git::update_op_log(&repo, &op_entry.to_string())?;
+
+    println!("Created weft '{}' from trunk", name);
+    println!(
+        "Your work on '{}' is saved; return with: weft switch {}",
+        current, current
+    );
+
+    Ok(())
+}
@@ -1,44 +1,303 @@
+use crate::commands::status;
 use crate::config;
 use crate::git;
+use crate::hooks::{self, HookEnv};
+use crate::limits;
+use crate::loom::{self, CandidateMeta};
+use crate::outcome::{FileChange, Proposal, ProposeOutcome};
+use crate::secrets;
+use crate::Weft;
 use anyhow::{Context, Result};
+use chrono::Utc;
+use git2::{Delta, Repository};
+use std::env;
+use std::fs;
+use std::io::IsTerminal;
 use std::process::Command;
 
-pub fn run() -> Result<()> {
-    let repo = git::discover()?;
-    let user = config::get_user(&repo)?;
-
-    let weft_head = Command::new("jj")
-        .args(&[
-            "--no-pager",
-            "log",
-            "-r",
-            "@",
-            "-T",
-            "commit_id",
-            "--no-graph",
-        ])
-        .current_dir(repo.path())
-        .output()?
-        .stdout;
-    let weft_head = String::from_utf8_lossy(&weft_head).trim().to_string();
+/// Asks for a title and description when a new candidate has none.
+pub type AskDetails<'a> = &'a dyn Fn() -> Result<Option<(String, String)>>;
+
+#[derive(Default)]
+pub struct ProposeOptions<'a> {
+    pub title: Option<String>,
+    pub description: Option<String>,
+    /// Called for a candidate's first revision when no title is given.
+    pub ask_details: Option<AskDetails<'a>>,
+}
 
-    let commit_hash = weft_head.clone();
-    let short_hash = if commit_hash.len() >= 8 {
-        &commit_hash[..8]
+pub fn run(title: Option<&str>, description: Option<&str>, sync_first: bool) -> Result<()> {
+    let weft = Weft::discover()?;
+
+    if sync_first {
+        let synced = weft.sync()?;
+        for report in &synced.hooks {
+            eprintln!("{}", report);
+        }
+        println!("Synced weft '{}' onto trunk.", synced.weft);
+        if !synced.tangled.is_empty() {
+            println!("{} tangled commits.", synced.tangled.len());
+        }
+    }
+
+    let options = ProposeOptions {
+        title: title.map(str::to_string),
+        description: description.map(str::to_string),
+        ask_details: if std::io::stdin().is_terminal() {
+            Some(&prompt_for_details)
+        } else {
+            None
+        },
+    };
+
+    let proposal = match weft.propose(options)? {
+        ProposeOutcome::NothingToPropose { weft } => {
+            println!(
+                "Warning: weft '{}' has no changes relative to trunk. Nothing to propose.",
+                weft
+            );
+            println!("Save some work with 'weft save' first.");
+            return Ok(());
+        }
+        ProposeOutcome::AlreadyProposed {
+            weft,
+            candidate_id,
+            revision,
+        } => {
+            println!(
+                "Weft '{}' is already proposed as '{}' (revision {}).",
+                weft, candidate_id, revision
+            );
+            return Ok(());
+        }
+        ProposeOutcome::Proposed(proposal) => proposal,
+    };
+
+    for report in &proposal.hooks {
+        eprintln!("{}", report);
+    }
+    if proposal.behind_trunk > 0 {
+        println!(
+            "Warning: weft '{}' is {} commits behind trunk. Use 'weft propose --sync' to sync first.",
+            proposal.weft, proposal.behind_trunk
+        );
+    }
+
+    println!("Proposed {} commits:", proposal.commits.len());
+    for commit in &proposal.commits {
+        println!("  {} {}", &commit.id.to_string()[..8], commit.summary);
+    }
+    println!("Files changed:");
+    for file in &proposal.files {
+        println!("  {} {}", file.status, file.path);
+    }
+    println!();
+
+    let id = &proposal.candidate_id;
+    let rev = proposal.revision;
+    if rev == 1 {
+        println!("Candidate created: {}", proposal.candidate_ref);
     } else {
-        &commit_hash
+        println!(
+            "Candidate '{}' updated to revision {}: {}",
+            id, rev, proposal.candidate_ref
+        );
+        println!(
+            "See what changed with 'weft diff --candidate {} --revs {}..{}'",
+            id,
+            rev - 1,
+            rev
+        );
+    }
+    if let Some(title) = &proposal.title {
+        println!("Title: {}", title);
+    }
+    println!("\nNext steps:");
+    println!("  weft status  # Check candidate status");
+    println!("  weft weave {}  # Merge when ready", id);
+
+    Ok(())
+}
+
+/// Pushes the active weft's head as a new candidate, or as a new revision of
+/// the candidate it was last proposed as.
+pub fn propose_weft(
+    repo: &Repository,
+    user: &str,
+    options: ProposeOptions,
+) -> Result<ProposeOutcome> {
+    let weft = config::get_active_weft(repo, user)?;
+
+    let head = match git::get_weft_head(repo, user, &weft)? {
+        Some(oid) => oid,
+        None => {
+            return Err(anyhow::anyhow!(
+                "Weft not initialized. Run 'weft init' first."
+            ));
+        }
+    };
+    let trunk = git::get_trunk(repo)?;
+
+    let tangled = git::tangled_between(repo, trunk, head)?;
+    if !tangled.is_empty() {
+        let mut message = format!(
+            "Cannot propose weft '{}': it has {} tangled commits:",
+            weft,
+            tangled.len()
+        );
+        for oid in &tangled {
+            let commit = repo.find_commit(*oid)?;
+            message.push_str(&format!(
+                "\n  - {} {}",
+                &oid.to_string()[..8],
+                commit.summary().unwrap_or("")
+            ));
+        }
+        message.push_str("\nResolve them with 'weft untangle' first.");
+        return Err(anyhow::anyhow!(message));
+    }
+
+    let base = repo.merge_base(head, trunk)?;
+    let diff = repo.diff_tree_to_tree(
+        Some(&repo.find_commit(base)?.tree()?),
+        Some(&repo.find_commit(head)?.tree()?),
+        None,
+    )?;
+    if diff.deltas().len() == 0 {
+        return Ok(ProposeOutcome::NothingToPropose { weft });
+    }
+
+    let config = config::load(repo)?;
+    let hits = secrets::scan(repo, trunk, head, &config.secrets)?;
+    if !hits.is_empty() {
+        return Err(secrets::blocked(repo, "propose", &hits));
+    }
+
+    let oversized = limits::check_range(repo, trunk, head, &config.limits)?;
+    if !oversized.is_empty() {
+        return Err(anyhow::anyhow!(
+            "Refusing to propose: these files in your saves are over the limits:\n{}\n\n\
+             Remove them from the saves listed, then propose again.\n{}",
+            limits::describe_saves(repo, &oversized, &config.limits)?,
+            limits::ignore_hint()
+        ));
+    }
+
+    let (_, behind_trunk) = repo.graph_ahead_behind(head, trunk)?;
+
+    let mut commits = git::commits_between(repo, trunk, head)?
+        .into_iter()
+        .map(|oid| status::summarize(repo, oid))
+        .collect::<Result<Vec<_>>>()?;
+    commits.reverse();
+
+    let files = diff
+        .deltas()
+        .map(|delta| FileChange {
+            status: status_letter(delta.status()),
+            path: delta
+                .new_file()
+                .path()
+                .or_else(|| delta.old_file().path())
+                .map(|p| p.display().to_string())
+                .unwrap_or_default(),
+        })
+        .collect();
+
+    if let Err(e) = loom::fetch(repo) {
+        return Err(anyhow::anyhow!(
+            "Cannot propose: {}",
+            e.to_string().trim_end()
+        ));
+    }
+
+    let weft_head = head.to_string();
+
+    // Keep the id of the candidate this weft was proposed as, as long as it is
+    // still open; once woven or dropped, the next propose starts a new one.
+    let existing = match loom::weft_candidate(repo, user, &weft)? {
+        Some(id) => loom::find(repo, &id)?,
+        None => None,
     };
 
-    let candidate_id = format!("{}-{}", user, short_hash);
+    if let Some(candidate) = &existing {
+        if candidate.head == head {
+            return Ok(ProposeOutcome::AlreadyProposed {
+                weft,
+                candidate_id: candidate.id.clone(),
+                revision: candidate.latest_revision(),
+            });
+        }
+    }
+
+    let (candidate_id, rev) = match &existing {
+        Some(candidate) => (candidate.id.clone(), candidate.latest_revision() + 1),
+        None => (format!("{}-{}", user, &weft_head[..8]), 1),
+    };
+
+    // Candidates proposed before revisions existed sit at `refs/loom/<id>`,
+    // which rules out `refs/loom/<id>/<rev>` next to it.
+    let legacy_ref = format!("refs/loom/{}", candidate_id);
+    if repo.find_reference(&legacy_ref).is_ok() {
+        return Err(anyhow::anyhow!(
+            "Candidate '{}' was proposed by an older weft at {} and cannot take \
+             revisions. Weave or drop it, then propose again.",
+            candidate_id,
+            legacy_ref
+        ));
+    }
+
+    let candidate_ref = loom::revision_ref(&candidate_id, rev);
+    let existing_meta = loom::read_meta(repo, &candidate_id)?;
+
+    let details = match (options.title, options.ask_details) {
+        (Some(title), _) => Some((title, options.description.unwrap_or_default())),
+        (None, _) if existing_meta.is_some() => None,
+        (None, Some(ask)) => ask()?,
+        (None, None) => None,
+    };
+
+    // Refs to create on origin, forced ones marked; local copies are only
+    // written once the push went through.
+    let mut updates = vec![(head, candidate_ref.clone(), false)];
+    if existing.is_none() {
+        let link = repo.blob(candidate_id.as_bytes())?;
+        updates.push((link, loom::weft_candidate_ref(user, &weft), true));
+    }
+    if let Some((title, description)) = &details {
+        let meta = CandidateMeta {
+            title: title.clone(),
+            description: description.clone(),
+            author: user.to_string(),
+            created: existing_meta
+                .as_ref()
+                .map(|m| m.created)
+                .unwrap_or_else(|| Utc::now().timestamp()),
+        };
+        let blob = loom::write_meta(repo, &meta)?;
+        updates.push((blob, loom::meta_ref(&candidate_id), true));
+    }
+    let refspecs: Vec<String> = updates
+        .iter()
+        .map(|(oid, name, force)| format!("{}{}:{}", if *force { "+" } else { "" }, oid, name))
+        .collect();
 
-    let candidate_ref = format!("refs/loom/{}", candidate_id);
+    let hook_reports = hooks::pre(
+        repo,
+        "pre-propose",
+        &HookEnv {
+            command: "propose",
+            user,
+            weft: &weft,
+            head: Some(head),
+            trunk: Some(trunk),
+            candidate: Some(&candidate_id),
+        },
+    )?;
 
     let output = Command::new("git")
-        .args(&[
-            "push",
-            "origin",
-            &format!("{}:refs/{}", weft_head, candidate_ref),
-        ])
+        .args(["push", "origin"])
+        .args(&refspecs)
         .current_dir(repo.path())
         .output()
         .context("Failed to create candidate")?;
@@ -48,10 +307,70 @@ pub fn run() -> Result<()> {
         return Err(anyhow::anyhow!("Failed to create candidate: {}", stderr));
     }
 
-    println!("Candidate created: {}", candidate_ref);
-    println!("\nNext steps:");
-    println!("  weft status  # Check candidate status");
-    println!("  weft weave {}  # Merge when ready", candidate_id);
+    for (oid, name, _) in &updates {
+        repo.reference(name, *oid, true, "weft propose")?;
+    }
 
-    Ok(())
+    Ok(ProposeOutcome::Proposed(Proposal {
+        weft,
+        candidate_id,
+        revision: rev,
+        candidate_ref,
+        title: details.map(|(title, _)| title),
+        commits,
+        files,
+        behind_trunk,
+        hooks: hook_reports,
+    }))
+}
+
+fn status_letter(status: Delta) -> char {
+    match status {
+        Delta::Added => 'A',
+        Delta::Deleted => 'D',
+        Delta::Renamed => 'R',
+        Delta::Copied => 'C',
+        _ => 'M',
+    }
+}
+
+/// Opens the user's editor for a title (first line) and description (the rest).
+fn prompt_for_details() -> Result<Option<(String, String)>> {
+    let editor = env::var("VISUAL")
+        .or_else(|_| env::var("EDITOR"))
+        .unwrap_or_else(|_| "vi".to_string());
+
+    let file = tempfile::Builder::new()
+        .prefix("weft-propose-")
+        .suffix(".md")
+        .tempfile()?;
+    fs::write(
+        file.path(),
+        "\n\n# Write a title on the first line and a description below it.\n\
+         # Lines starting with '#' are ignored. Leave empty to propose without a title.\n",
+    )?;
+
+    let status = Command::new("sh")
+        .args(["-c", &format!("{} \"$1\"", editor), "sh"])
+        .arg(file.path())
+        .status()
+        .with_context(|| format!("Failed to run editor '{}'", editor))?;
+
+    if !status.success() {
+        return Err(anyhow::anyhow!("Editor '{}' exited with an error", editor));
+    }
+
+    let content = fs::read_to_string(file.path())?;
+    let text: Vec<&str> = content.lines().filter(|l| !l.starts_with('#')).collect();
+    let text = text.join("\n");
+    let text = text.trim();
+
+    match text.split_once('\n') {
+        _ if text.is_empty() => Ok(None),
+        Some((title, description)) => Ok(Some((
+            title.trim().to_string(),
+            description.trim().to_string(),
+        ))),
+        None => Ok(Some((text.to_string(), String::new()))),
+    }
 }
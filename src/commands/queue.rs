@@ -0,0 +1,398 @@
+use crate::config::{self, WeftConfig};
+use crate::format;
+use crate::git;
+use crate::hooks::{self, HookEnv};
+use crate::loom::queue::{self, Ejected, QueueEntry, QueueState};
+use crate::loom::woven::{self, WovenRecord};
+use crate::loom::{self, review, Strategy, Woven};
+use crate::verify;
+use anyhow::{Context, Result};
+use chrono::Utc;
+use git2::{Oid, Repository};
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::PathBuf;
+use std::process::Command;
+
+pub fn add(candidate_id: &str) -> Result<()> {
+    let repo = git::discover()?;
+    let user = config::get_user(&repo)?;
+
+    loom::fetch(&repo)?;
+
+    if loom::find(&repo, candidate_id)?.is_none() {
+        return Err(anyhow::anyhow!(
+            "Candidate '{}' not found. Run 'weft candidates' to see open candidates.",
+            candidate_id
+        ));
+    }
+
+    let (mut state, lease) = queue::read(&repo)?;
+    if let Some(pos) = state
+        .entries
+        .iter()
+        .position(|e| e.candidate == candidate_id)
+    {
+        println!(
+            "'{}' is already queued at position {}",
+            candidate_id,
+            pos + 1
+        );
+        return Ok(());
+    }
+
+    state.entries.push(QueueEntry {
+        candidate: candidate_id.to_string(),
+        added_by: user,
+        added_at: Utc::now().timestamp(),
+    });
+    queue::publish(&repo, &state, lease, &[])?;
+
+    println!(
+        "Queued '{}' at position {}",
+        candidate_id,
+        state.entries.len()
+    );
+    println!("\nNext steps:");
+    println!("  weft queue run  # Weave everything in the queue");
+
+    Ok(())
+}
+
+pub fn list() -> Result<()> {
+    let repo = git::discover()?;
+
+    if let Err(e) = loom::fetch(&repo) {
+        eprintln!("Warning: {}", e.to_string().trim_end());
+        eprintln!("Showing the queue as known locally.");
+    }
+
+    let (state, _) = queue::read(&repo)?;
+
+    if state.entries.is_empty() {
+        println!("Merge queue is empty.");
+    } else {
+        println!("Merge queue:");
+        for (i, entry) in state.entries.iter().enumerate() {
+            println!(
+                "  {}. {} (queued by {}, {})",
+                i + 1,
+                entry.candidate,
+                entry.added_by,
+                format::relative_time(entry.added_at)
+            );
+        }
+    }
+
+    if !state.ejected.is_empty() {
+        println!("\nRecently ejected:");
+        for ejected in state.ejected.iter().rev().take(5) {
+            println!("  - {}: {}", ejected.candidate, ejected.reason);
+        }
+    }
+
+    Ok(())
+}
+
+enum Outcome {
+    Woven(Oid),
+    Ejected(String),
+    Gone,
+    Raced,
+}
+
+/// How many times in a row the front of the queue may lose the race for main
+/// before `weft queue run` gives up.
+const MAX_RACES: u32 = 5;
+
+pub fn run(verify: bool) -> Result<()> {
+    let repo = git::discover()?;
+    let config = config::load(&repo)?;
+
+    let check = match (verify, &config.weave.check) {
+        (false, _) => None,
+        (true, Some(check)) => Some(check.clone()),
+        (true, None) => {
+            return Err(anyhow::anyhow!(
+                "--verify needs a check command. Set [weave] check in .weft.toml"
+            ));
+        }
+    };
+
+    // The queue always replays onto the previous result, so ff means rebase here.
+    let strategy = match config.weave.strategy {
+        None | Some(Strategy::Ff) => Strategy::Rebase,
+        Some(strategy) => strategy,
+    };
+
+    let _lock = QueueLock::acquire(&repo)?;
+
+    let mut woven = 0;
+    let mut ejected = 0;
+    // The candidate at the front of the queue and how often it lost a race.
+    let mut races = (String::new(), 0);
+
+    loop {
+        loom::fetch(&repo)?;
+
+        let (mut state, lease) = queue::read(&repo)?;
+        let entry = match state.entries.first() {
+            Some(entry) => entry.clone(),
+            None => break,
+        };
+
+        let trunk = git::get_origin_main(&repo)
+            .context("origin/main not found. Push main to origin before running the queue.")?;
+
+        let mut remaining = state.clone();
+        remaining.entries.remove(0);
+
+        if races.0 != entry.candidate {
+            races = (entry.candidate.clone(), 0);
+        }
+
+        match weave_entry(
+            &repo,
+            &entry.candidate,
+            trunk,
+            strategy,
+            check.as_deref(),
+            &config,
+            (&remaining, lease),
+        )? {
+            Outcome::Woven(oid) => {
+                println!(
+                    "Woven '{}' into main ({})",
+                    entry.candidate,
+                    &oid.to_string()[..8]
+                );
+                woven += 1;
+            }
+            Outcome::Ejected(reason) => {
+                state.entries.remove(0);
+                state.ejected.push(Ejected {
+                    candidate: entry.candidate.clone(),
+                    reason: reason.clone(),
+                    ejected_at: Utc::now().timestamp(),
+                });
+                // A check run for this entry is published with its ejection.
+                queue::publish(
+                    &repo,
+                    &state,
+                    lease,
+                    &check_refspec(&repo, &entry.candidate),
+                )?;
+                println!("Ejected '{}': {}", entry.candidate, reason);
+                ejected += 1;
+            }
+            Outcome::Gone => {
+                state.entries.remove(0);
+                queue::publish(&repo, &state, lease, &[])?;
+                println!("Skipped '{}': candidate no longer exists", entry.candidate);
+            }
+            Outcome::Raced => {
+                races.1 += 1;
+                if races.1 >= MAX_RACES {
+                    return Err(anyhow::anyhow!(
+                        "Main or the queue moved {} times in a row while weaving '{}'. \
+                         Run 'weft queue run' again once things are quieter.",
+                        MAX_RACES,
+                        entry.candidate
+                    ));
+                }
+                println!(
+                    "Main or the queue moved while weaving '{}'; retrying",
+                    entry.candidate
+                );
+            }
+        }
+    }
+
+    println!("Queue empty. {} woven, {} ejected.", woven, ejected);
+    Ok(())
+}
+
+fn weave_entry(
+    repo: &Repository,
+    candidate_id: &str,
+    trunk: Oid,
+    strategy: Strategy,
+    check: Option<&str>,
+    config: &WeftConfig,
+    (remaining, lease): (&QueueState, Option<Oid>),
+) -> Result<Outcome> {
+    let candidate = match loom::find(repo, candidate_id)? {
+        Some(candidate) => candidate,
+        None => return Ok(Outcome::Gone),
+    };
+    let head = candidate.head;
+
+    if let Some(violation) = loom::validate(repo, head, trunk)? {
+        return Ok(Outcome::Ejected(violation));
+    }
+    if config.weave.require_signed {
+        if let Some(violation) = loom::validate_signatures(repo, head, trunk)? {
+            return Ok(Outcome::Ejected(violation));
+        }
+    }
+    if let Some(violation) = review::violation(repo, &candidate, &config.review)? {
+        return Ok(Outcome::Ejected(violation));
+    }
+
+    let woven = match loom::weave_commit(repo, strategy, candidate_id, head, trunk)? {
+        Woven::Ready(oid) => oid,
+        Woven::Refused(reason) => return Ok(Outcome::Ejected(reason)),
+    };
+
+    if let Some(check) = check {
+        let result = verify::run_check(repo, candidate_id, head, woven, check)?;
+        if !result.passed {
+            return Ok(Outcome::Ejected(format!(
+                "check '{}' failed; output saved to {}",
+                check,
+                verify::log_path(repo, candidate_id, woven).display()
+            )));
+        }
+    }
+
+    let user = config::get_user(repo)?;
+    let weft = config::get_active_weft(repo, &user)?;
+    let env = HookEnv {
+        command: "weave",
+        user: &user,
+        weft: &weft,
+        head: Some(woven),
+        trunk: Some(trunk),
+        candidate: Some(candidate_id),
+    };
+    match hooks::pre(repo, "pre-weave", &env) {
+        Ok(reports) => {
+            for report in &reports {
+                eprintln!("{}", report);
+            }
+        }
+        Err(e) => return Ok(Outcome::Ejected(e.to_string())),
+    }
+
+    let (woven_log, woven_refspec) = woven::append(
+        repo,
+        &WovenRecord {
+            candidate: candidate_id.to_string(),
+            title: loom::read_meta(repo, candidate_id)?.map(|m| m.title),
+            author: loom::author(repo, candidate_id)?,
+            revision: candidate.latest_revision(),
+            strategy,
+            commit: woven.to_string(),
+            by: user.clone(),
+            at: Utc::now().timestamp(),
+        },
+    )?;
+
+    // The entry leaves the queue in the same push that lands it on main.
+    let (queue_blob, [queue_lease, queue_refspec]) = queue::stage(repo, remaining, lease)?;
+
+    let main_ref = format!("refs/heads/{}", git::main_branch(repo));
+    let mut refspecs = vec![
+        format!("{}:{}", woven, main_ref),
+        woven_refspec,
+        queue_refspec,
+    ];
+    // The check this weave ran is published rather than deleted with the
+    // rest of the candidate, so other clones see what gated it.
+    let mut cleanup = loom::cleanup_refspecs(repo, candidate_id);
+    if check.is_some() {
+        let deletion = format!(":{}", loom::checks_ref(candidate_id));
+        cleanup.retain(|refspec| *refspec != deletion);
+        cleanup.extend(check_refspec(repo, candidate_id));
+    }
+    refspecs.extend(cleanup);
+
+    let output = Command::new("git")
+        .args([
+            "push",
+            "--atomic",
+            "--porcelain",
+            &format!("--force-with-lease={}:{}", main_ref, trunk),
+            &queue_lease,
+            "origin",
+        ])
+        .args(&refspecs)
+        .current_dir(repo.path())
+        .output()
+        .context("Failed to push main")?;
+
+    if !output.status.success() {
+        let porcelain = String::from_utf8_lossy(&output.stdout);
+        if git::lease_failed(&porcelain) {
+            return Ok(Outcome::Raced);
+        }
+        // Anything else (hooks, protected branches, permissions) would fail
+        // the same way on every retry.
+        return Ok(Outcome::Ejected(format!(
+            "origin refused the push: {}{}",
+            porcelain,
+            String::from_utf8_lossy(&output.stderr).trim_end()
+        )));
+    }
+
+    woven::recorded(repo, woven_log)?;
+    queue::recorded(repo, queue_blob)?;
+
+    for report in hooks::post(
+        repo,
+        "post-weave",
+        &HookEnv {
+            trunk: Some(woven),
+            ..env
+        },
+    ) {
+        eprintln!("{}", report);
+    }
+
+    Ok(Outcome::Woven(woven))
+}
+
+/// Under the git directory's `weft/`; holds the pid of the run that took it.
+pub const LOCK_FILE: &str = "queue.lock";
+
+/// Keeps two `weft queue run`s in the same clone from weaving concurrently.
+struct QueueLock {
+    path: PathBuf,
+}
+
+impl QueueLock {
+    fn acquire(repo: &Repository) -> Result<Self> {
+        let dir = repo.path().join("weft");
+        fs::create_dir_all(&dir)?;
+        let path = dir.join(LOCK_FILE);
+
+        let mut file = OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(&path)
+            .map_err(|_| {
+                anyhow::anyhow!(
+                    "Another 'weft queue run' is in progress. If it is not, run 'weft doctor --fix'"
+                )
+            })?;
+        // Lets `weft doctor` tell a held lock from one left by a crash.
+        writeln!(file, "{}", std::process::id())?;
+
+        Ok(QueueLock { path })
+    }
+}
+
+impl Drop for QueueLock {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.path);
+    }
+}
+
+/// Refspec publishing the candidate's check results as recorded locally.
+fn check_refspec(repo: &Repository, candidate_id: &str) -> Vec<String> {
+    let checks_ref = loom::checks_ref(candidate_id);
+    match repo.refname_to_id(&checks_ref) {
+        Ok(checks) => vec![format!("+{}:{}", checks, checks_ref)],
+        Err(_) => Vec::new(),
+    }
+}
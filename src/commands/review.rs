@@ -0,0 +1,155 @@
+use crate::commands::diff;
+use crate::config;
+use crate::format;
+use crate::git;
+use crate::loom::review::{self, Verdict, Vote};
+use crate::loom::{self, Candidate};
+use anyhow::Result;
+use chrono::Utc;
+use git2::Repository;
+
+pub fn review(candidate_id: &str) -> Result<()> {
+    let repo = git::discover()?;
+
+    if let Err(e) = loom::fetch(&repo) {
+        eprintln!("Warning: {}", e.to_string().trim_end());
+        eprintln!("Showing the candidate as known locally.");
+    }
+
+    let candidate = find(&repo, candidate_id)?;
+    let trunk = git::get_trunk(&repo)?;
+    let rules = config::load(&repo)?.review;
+
+    println!(
+        "Candidate '{}' by {}, revision {} ({})",
+        candidate.id,
+        loom::author(&repo, &candidate.id)?,
+        candidate.latest_revision(),
+        &candidate.head.to_string()[..8]
+    );
+    if let Some(meta) = loom::read_meta(&repo, &candidate.id)? {
+        println!("  {}", meta.title);
+        for line in meta.description.lines() {
+            println!("  {}", line);
+        }
+    }
+
+    let (votes, _) = review::read(&repo, &candidate.id)?;
+    let votes = review::current_votes(&candidate, &votes);
+    if votes.is_empty() {
+        println!("\nNo votes on this revision yet.");
+    } else {
+        println!("\nVotes on this revision:");
+        for vote in &votes {
+            let verdict = match vote.verdict {
+                Verdict::Approve => "approved".to_string(),
+                Verdict::Reject => format!(
+                    "rejected: {}",
+                    vote.reason.as_deref().unwrap_or("no reason given")
+                ),
+            };
+            println!(
+                "  {} {} ({})",
+                vote.voter,
+                verdict,
+                format::relative_time(vote.at)
+            );
+        }
+    }
+    match review::violation(&repo, &candidate, &rules)? {
+        Some(violation) => println!("Not ready to weave: {}", violation),
+        None => println!("Ready to weave."),
+    }
+
+    let base = repo.merge_base(candidate.head, trunk)?;
+    let diff = repo.diff_tree_to_tree(
+        Some(&repo.find_commit(base)?.tree()?),
+        Some(&repo.find_commit(candidate.head)?.tree()?),
+        None,
+    )?;
+    println!();
+    diff::print_patch(&diff)?;
+
+    println!("\nNext steps:");
+    println!("  weft approve {}", candidate.id);
+    println!("  weft reject {} --reason \"...\"", candidate.id);
+
+    Ok(())
+}
+
+pub fn approve(candidate_id: &str) -> Result<()> {
+    vote(candidate_id, Verdict::Approve, None)
+}
+
+pub fn reject(candidate_id: &str, reason: &str) -> Result<()> {
+    if reason.trim().is_empty() {
+        return Err(anyhow::anyhow!("A rejection needs a reason."));
+    }
+    vote(
+        candidate_id,
+        Verdict::Reject,
+        Some(reason.trim().to_string()),
+    )
+}
+
+fn vote(candidate_id: &str, verdict: Verdict, reason: Option<String>) -> Result<()> {
+    let repo = git::discover()?;
+    let user = config::get_user(&repo)?;
+
+    loom::fetch(&repo)?;
+    let candidate = find(&repo, candidate_id)?;
+    let rules = config::load(&repo)?.review;
+
+    if verdict == Verdict::Approve
+        && !rules.allow_author
+        && loom::author(&repo, &candidate.id)? == user
+    {
+        return Err(anyhow::anyhow!(
+            "You cannot approve your own candidate '{}'. Ask a teammate to review it.",
+            candidate.id
+        ));
+    }
+
+    let signature = repo.signature()?;
+    let signoff = format!(
+        "{} <{}>",
+        signature.name().unwrap_or(&user),
+        signature.email().unwrap_or("")
+    );
+
+    let (mut votes, lease) = review::read(&repo, &candidate.id)?;
+    votes.push(Vote {
+        voter: user,
+        signoff: signoff.clone(),
+        verdict,
+        reason,
+        revision: candidate.latest_revision(),
+        commit: candidate.head.to_string(),
+        at: Utc::now().timestamp(),
+    });
+    review::publish(&repo, &candidate.id, &votes, lease)?;
+
+    let action = match verdict {
+        Verdict::Approve => "Approved",
+        Verdict::Reject => "Rejected",
+    };
+    println!(
+        "{} '{}' revision {} as {}",
+        action,
+        candidate.id,
+        candidate.latest_revision(),
+        signoff
+    );
+
+    Ok(())
+}
+
+fn find(repo: &Repository, candidate_id: &str) -> Result<Candidate> {
+    match loom::find(repo, candidate_id)? {
+        Some(candidate) => Ok(candidate),
+        None => Err(anyhow::anyhow!(
+            "Candidate '{}' not found. Run 'weft candidates' to see open candidates.",
+            candidate_id
+        )),
+    }
+}
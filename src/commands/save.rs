@@ -1,15 +1,70 @@
-use crate::config;
+use crate::config::{self, OnSave};
+use crate::daemon;
+use crate::format;
 use crate::git;
+use crate::hooks::{self, HookEnv};
+use crate::jj;
+use crate::limits;
+use crate::outcome::{SaveOutcome, SkippedFile};
+use crate::Weft;
 use anyhow::Result;
 use chrono::Utc;
+use git2::Repository;
+use serde_json::json;
 use std::process::Command;
 
 pub fn run(message: &str) -> Result<()> {
-    let repo = git::discover()?;
-    let user = config::get_user(&repo)?;
+    let outcome: SaveOutcome = daemon::call_or("save", json!({ "message": message }), || {
+        Weft::discover()?.save(message)
+    })?;
+
+    // Hook output goes to stderr so it never mixes with weft's own output.
+    for report in &outcome.hooks {
+        eprintln!("{}", report);
+    }
+    if !outcome.skipped.is_empty() {
+        eprintln!("Warning: left these files out of the save:");
+        for file in &outcome.skipped {
+            let binary = if file.binary { ", binary" } else { "" };
+            eprintln!(
+                "  {} ({}{})",
+                file.path,
+                format::human_size(file.size),
+                binary
+            );
+        }
+        eprintln!(
+            "They are still on disk and were added to .git/info/exclude.\n{}",
+            limits::ignore_hint()
+        );
+    }
+
+    println!("Saved: {}", outcome.message);
+
+    Ok(())
+}
+
+/// Describes the working copy as a save on `weft` and records it in the op-log.
+pub fn save_weft(repo: &Repository, user: &str, weft: &str, message: &str) -> Result<SaveOutcome> {
+    let trunk = git::get_trunk(repo).ok();
+    let mut hook_reports = hooks::pre(
+        repo,
+        "pre-save",
+        &HookEnv {
+            command: "save",
+            user,
+            weft,
+            head: git::get_weft_head(repo, user, weft)?,
+            trunk,
+            candidate: None,
+        },
+    )?;
+
+    let skipped = enforce_limits(repo, user, weft)?;
 
     let output = Command::new("jj")
-        .args(&["describe", "-m", &format!("save: {}", message)])
+        .args(jj::signing_args(repo)?)
+        .args(["describe", "-m", &format!("save: {}", message)])
         .current_dir(repo.path())
         .output()?;
 
@@ -20,43 +75,84 @@ pub fn run(message: &str) -> Result<()> {
         ));
     }
 
-    let jj_log = Command::new("jj")
-        .args(&[
-            "--no-pager",
-            "log",
-            "-r",
-            "@",
-            "-T",
-            "commit_id",
-            "--no-graph",
-        ])
-        .current_dir(repo.path())
-        .output()?;
-
-    if !jj_log.status.success() {
-        return Err(anyhow::anyhow!("Failed to get commit id"));
-    }
-
-    let commit_id = String::from_utf8_lossy(&jj_log.stdout).trim().to_string();
+    let head = jj::current_commit(repo)?;
+    let commit_id = head.to_string();
 
-    let head = commit_id.parse::<git2::Oid>()?;
-
-    git::update_weft_head(&repo, &user, head, "weft save")?;
+    git::update_weft_head(repo, user, weft, head, "weft save")?;
 
     let now = Utc::now();
     let op_entry = serde_json::json!({
         "timestamp": now.timestamp(),
         "command": "save",
-        "args": {"message": message},
+        "args": {"message": message, "weft": weft},
         "inverse": {
             "op": "delete-commit",
             "commit": commit_id
         }
     });
 
-    git::update_op_log(&repo, &op_entry.to_string())?;
+    git::update_op_log(repo, &op_entry.to_string())?;
 
-    println!("Saved: {}", message);
+    hook_reports.extend(hooks::post(
+        repo,
+        "post-save",
+        &HookEnv {
+            command: "save",
+            user,
+            weft,
+            head: Some(head),
+            trunk,
+            candidate: None,
+        },
+    ));
 
-    Ok(())
+    Ok(SaveOutcome {
+        weft: weft.to_string(),
+        commit: head,
+        message: message.to_string(),
+        skipped,
+        hooks: hook_reports,
+    })
+}
+
+/// Checks the files changed on disk since the weft's last save against the
+/// `[limits]` in `.weft.toml`, leaving new offending files out of the save or
+/// refusing it. Runs before any jj command, since jj snapshots the working
+/// copy (and with it every offending file) as soon as it starts.
+fn enforce_limits(repo: &Repository, user: &str, weft: &str) -> Result<Vec<SkippedFile>> {
+    let limits = config::load(repo)?.limits;
+    let base = git::get_weft_head(repo, user, weft)?;
+    let violations = limits::check_workdir(repo, base, &limits)?;
+    if violations.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let listing = limits::describe(&violations, &limits);
+    if limits.on_save == OnSave::Refuse {
+        return Err(anyhow::anyhow!(
+            "Refusing to save: these files are over the limits:\n{}\n\n{}",
+            listing,
+            limits::ignore_hint()
+        ));
+    }
+    if violations.iter().any(|v| !v.added) {
+        return Err(anyhow::anyhow!(
+            "Refusing to save: these files are over the limits, and some are already in \
+             earlier saves so they cannot be skipped:\n{}\n\n{}",
+            listing,
+            limits::ignore_hint()
+        ));
+    }
+
+    let paths: Vec<String> = violations.iter().map(|v| v.path.clone()).collect();
+    jj::skip_files(repo, &paths)?;
+
+    Ok(violations
+        .into_iter()
+        .map(|v| SkippedFile {
+            path: v.path,
+            size: v.size,
+            binary: v.binary,
+        })
+        .collect())
 }
@@ -0,0 +1,324 @@
+//! `weft serve`: a daemon that keeps the repository open so that editors and
+//! agents polling weft don't pay for a fresh process, and a handful of jj
+//! processes, on every call.
+//!
+//! It listens on `.git/weft/serve.sock` for newline-delimited JSON-RPC 2.0.
+//! Methods are the [`Weft`] operations (`save`, `sync`, `status`, ...) plus
+//! `run`, which runs any other weft command and returns its output.
+
+use crate::daemon;
+use crate::rpc::{
+    self, error_response, RpcError, INTERNAL_ERROR, INVALID_PARAMS, INVALID_REQUEST,
+    METHOD_NOT_FOUND, PARSE_ERROR,
+};
+use crate::Weft;
+use anyhow::Result;
+use serde_json::{json, Value};
+
+#[cfg(unix)]
+pub use unix::{run, stop};
+
+#[cfg(not(unix))]
+pub fn run() -> Result<()> {
+    Err(anyhow::anyhow!("weft serve is only available on Unix"))
+}
+
+#[cfg(not(unix))]
+pub fn stop() -> Result<()> {
+    run()
+}
+
+struct Server {
+    weft: Weft,
+    /// The daemon's own [`daemon::CALLER_ENV`], which callers must share.
+    env: Value,
+    /// The last status report and the repository fingerprint it was made at.
+    status: Option<(u64, Value)>,
+}
+
+#[cfg(unix)]
+mod unix {
+    use super::*;
+    use std::fs;
+    use std::io::{BufRead, BufReader, Write};
+    use std::os::unix::net::{UnixListener, UnixStream};
+    use std::path::PathBuf;
+    use std::sync::{Arc, Mutex};
+    use std::thread;
+
+    /// Removes the socket when the daemon exits normally.
+    struct Socket(PathBuf);
+
+    impl Drop for Socket {
+        fn drop(&mut self) {
+            let _ = fs::remove_file(&self.0);
+        }
+    }
+
+    pub fn run() -> Result<()> {
+        let weft = Weft::discover()?;
+
+        let path = daemon::socket_path(weft.repo());
+        if UnixStream::connect(&path).is_ok() {
+            return Err(anyhow::anyhow!(
+                "weft serve is already running for this repository. Stop it with 'weft serve --stop'."
+            ));
+        }
+        // Left behind by a daemon that was killed.
+        let _ = fs::remove_file(&path);
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+
+        let listener = UnixListener::bind(&path)?;
+        let socket = Socket(path.clone());
+
+        println!("Serving weft for {} on {}", weft.user(), path.display());
+        println!("weft commands in this repository now go through it. Stop with Ctrl-C.");
+
+        let server = Arc::new(Mutex::new(Server {
+            weft,
+            env: daemon::caller_env(),
+            status: None,
+        }));
+        for stream in listener.incoming() {
+            let stream = match stream {
+                Ok(stream) => stream,
+                Err(e) => {
+                    eprintln!("Warning: {}", e);
+                    continue;
+                }
+            };
+
+            // Connections get a thread each so an idle client doesn't block
+            // others; requests still run one at a time.
+            let server = Arc::clone(&server);
+            let path = socket.0.clone();
+            thread::spawn(move || {
+                if let Err(e) = serve_connection(&server, stream, &path) {
+                    eprintln!("Warning: {}", e);
+                }
+            });
+        }
+
+        Ok(())
+    }
+
+    /// Asks the running daemon to exit.
+    pub fn stop() -> Result<()> {
+        let repo = crate::git::discover()?;
+        // A caller whose environment changed since must still be able to stop it.
+        let mut client = daemon::Client::open(&repo)
+            .ok_or_else(|| anyhow::anyhow!("weft serve is not running for this repository."))?;
+        client.call::<Value>("shutdown", json!({}))?;
+        println!("Stopped weft serve.");
+        Ok(())
+    }
+
+    fn serve_connection(
+        server: &Mutex<Server>,
+        stream: UnixStream,
+        socket: &PathBuf,
+    ) -> Result<()> {
+        let mut writer = stream.try_clone()?;
+        for line in BufReader::new(stream).lines() {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+
+            let (response, shutdown) = match serde_json::from_str::<Value>(&line) {
+                Ok(message) => {
+                    let mut server = server.lock().unwrap_or_else(|e| e.into_inner());
+                    (server.handle(&message), message["method"] == "shutdown")
+                }
+                Err(e) => (
+                    Some(error_response(
+                        Value::Null,
+                        RpcError::new(PARSE_ERROR, e.to_string()),
+                    )),
+                    false,
+                ),
+            };
+
+            if let Some(response) = response {
+                writeln!(writer, "{}", response)?;
+                writer.flush()?;
+            }
+            if shutdown {
+                let _ = fs::remove_file(socket);
+                std::process::exit(0);
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Server {
+    /// Answers one message; notifications get no response.
+    fn handle(&mut self, message: &Value) -> Option<Value> {
+        let id = message.get("id")?.clone();
+        let params = &message["params"];
+
+        if let Err(e) = self.check_caller(message["method"].as_str(), params) {
+            return Some(error_response(id, e));
+        }
+
+        let result = match message["method"].as_str() {
+            Some("hello") => self.hello(params),
+            Some("shutdown") => Ok(json!({})),
+            Some("status") => self.status(),
+            Some("run") => self.run_command(params),
+            Some(method) => match rpc::call(&self.weft, method, params) {
+                Ok(Some(value)) => Ok(value),
+                Ok(None) => Err(RpcError::new(
+                    METHOD_NOT_FOUND,
+                    format!("Unknown method '{}'", method),
+                )),
+                Err(e) => Err(RpcError::new(INTERNAL_ERROR, format!("{:#}", e))),
+            },
+            None => Err(RpcError::new(INVALID_REQUEST, "Missing method")),
+        };
+
+        Some(match result {
+            Ok(result) => json!({ "jsonrpc": "2.0", "id": id, "result": result }),
+            Err(e) => error_response(id, e),
+        })
+    }
+
+    /// Refuses a request whose `caller` is another user, or whose environment
+    /// differs from the one hooks and the editor would run in here. Anyone
+    /// this daemon serves may stop it, whatever their environment.
+    fn check_caller(&self, method: Option<&str>, params: &Value) -> Result<(), RpcError> {
+        let caller = &params["caller"];
+        if let Some(user) = caller["user"].as_str() {
+            self.check_user(user)?;
+        }
+        if !caller["env"].is_null() && caller["env"] != self.env && method != Some("shutdown") {
+            return Err(RpcError::new(
+                daemon::WRONG_ENV,
+                "This daemon runs in a different environment; restart 'weft serve' from this shell",
+            ));
+        }
+        Ok(())
+    }
+
+    fn check_user(&self, user: &str) -> Result<(), RpcError> {
+        if user != self.weft.user() {
+            return Err(RpcError::new(
+                daemon::WRONG_USER,
+                format!("This daemon serves '{}', not '{}'", self.weft.user(), user),
+            ));
+        }
+        Ok(())
+    }
+
+    /// Lets a client check it is talking to a daemon for the same user.
+    fn hello(&self, params: &Value) -> Result<Value, RpcError> {
+        if let Some(user) = params["user"].as_str() {
+            self.check_user(user)?;
+        }
+        Ok(json!({
+            "version": env!("CARGO_PKG_VERSION"),
+            "user": self.weft.user(),
+            "repository": self.weft.repo().path().display().to_string(),
+        }))
+    }
+
+    /// `weft status`, answered from the cache while nothing it reads changed.
+    fn status(&mut self) -> Result<Value, RpcError> {
+        let internal = |e: anyhow::Error| RpcError::new(INTERNAL_ERROR, format!("{:#}", e));
+
+        let current = fingerprint(&self.weft).map_err(internal)?;
+        if let Some((cached, report)) = &self.status {
+            if *cached == current {
+                return Ok(report.clone());
+            }
+        }
+
+        let report = serde_json::to_value(self.weft.status().map_err(internal)?)
+            .map_err(|e| internal(e.into()))?;
+        // Asking jj for the working copy snapshots it, which can itself change
+        // the fingerprint, so take it again before caching.
+        let current = fingerprint(&self.weft).map_err(internal)?;
+        self.status = Some((current, report.clone()));
+        Ok(report)
+    }
+
+    /// Runs any weft command in the repository and returns what it printed.
+    fn run_command(&mut self, params: &Value) -> Result<Value, RpcError> {
+        let args: Vec<&str> = params["args"]
+            .as_array()
+            .and_then(|args| args.iter().map(Value::as_str).collect())
+            .ok_or_else(|| RpcError::new(INVALID_PARAMS, "'args' must be a list of strings"))?;
+
+        let repo = self.weft.repo();
+        let output = std::env::current_exe()
+            .and_then(|exe| {
+                std::process::Command::new(exe)
+                    .args(&args)
+                    .current_dir(repo.workdir().unwrap_or(repo.path()))
+                    .env("WEFT_USER", self.weft.user())
+                    .env("WEFT_NO_DAEMON", "1")
+                    .output()
+            })
+            .map_err(|e| RpcError::new(INTERNAL_ERROR, e.to_string()))?;
+
+        Ok(json!({
+            "code": output.status.code(),
+            "stdout": String::from_utf8_lossy(&output.stdout),
+            "stderr": String::from_utf8_lossy(&output.stderr),
+        }))
+    }
+}
+
+/// Changes whenever anything `weft status` reads could have changed: refs,
+/// jj's operation heads and the files in the working copy.
+fn fingerprint(weft: &Weft) -> Result<u64> {
+    use std::collections::hash_map::DefaultHasher;
+    use std::hash::{Hash, Hasher};
+
+    let repo = weft.repo();
+    let mut hasher = DefaultHasher::new();
+
+    std::fs::read(repo.path().join("HEAD"))
+        .ok()
+        .hash(&mut hasher);
+    for reference in repo.references()? {
+        let reference = reference?;
+        reference.name_bytes().hash(&mut hasher);
+        reference
+            .target()
+            .map(|oid| oid.as_bytes().to_vec())
+            .hash(&mut hasher);
+    }
+    weft.active_weft()?.hash(&mut hasher);
+
+    let workdir = match repo.workdir() {
+        Some(workdir) => workdir,
+        None => return Ok(hasher.finish()),
+    };
+
+    if let Ok(heads) = std::fs::read_dir(workdir.join(".jj/repo/op_heads/heads")) {
+        let mut heads: Vec<_> = heads
+            .filter_map(|e| e.ok())
+            .map(|e| e.file_name())
+            .collect();
+        heads.sort();
+        heads.hash(&mut hasher);
+    }
+
+    let mut options = git2::StatusOptions::new();
+    options.include_untracked(true).recurse_untracked_dirs(true);
+    for entry in repo.statuses(Some(&mut options))?.iter() {
+        let path = entry.path_bytes();
+        path.hash(&mut hasher);
+        entry.status().bits().hash(&mut hasher);
+        if let Ok(meta) = std::fs::metadata(workdir.join(String::from_utf8_lossy(path).as_ref())) {
+            meta.len().hash(&mut hasher);
+            meta.modified().ok().hash(&mut hasher);
+        }
+    }
+
+    Ok(hasher.finish())
+}
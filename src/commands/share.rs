@@ -1,16 +1,31 @@
 use crate::config;
+use crate::daemon;
 use crate::git;
+use crate::limits;
+use crate::outcome::ShareOutcome;
+use crate::secrets;
+use crate::Weft;
 use anyhow::{Context, Result};
+use git2::Repository;
+use serde_json::json;
 use std::process::Command;
 
 pub fn run() -> Result<()> {
-    let repo = git::discover()?;
-    let user = config::get_user(&repo)?;
+    let outcome: ShareOutcome = daemon::call_or("share", json!({}), || Weft::discover()?.share())?;
 
-    let weft_head_ref = format!("refs/weft/{}/head", user);
-    let weft_head = match repo.find_reference(&weft_head_ref) {
-        Ok(ref_) => ref_.peel_to_commit()?.id().to_string(),
-        Err(_) => {
+    println!("Shared weft to: {}", outcome.remote_url);
+    println!("Remote ref: {}", outcome.remote_ref);
+
+    Ok(())
+}
+
+/// Pushes the active weft to `refs/weft/<user>/<weft>` on origin.
+pub fn share_weft(repo: &Repository, user: &str) -> Result<ShareOutcome> {
+    let weft = config::get_active_weft(repo, user)?;
+
+    let weft_head = match git::get_weft_head(repo, user, &weft)? {
+        Some(oid) => oid.to_string(),
+        None => {
             return Err(anyhow::anyhow!(
                 "Weft not initialized. Run 'weft init' first."
             ));
@@ -33,14 +48,27 @@ pub fn run() -> Result<()> {
         ));
     }
 
-    let remote_ref = format!("refs/weft/{}", user);
+    let trunk = git::get_trunk(repo)?;
+    let config = config::load(repo)?;
+    let hits = secrets::scan(repo, trunk, weft_head.parse()?, &config.secrets)?;
+    if !hits.is_empty() {
+        return Err(secrets::blocked(repo, "share", &hits));
+    }
+
+    let oversized = limits::check_range(repo, trunk, weft_head.parse()?, &config.limits)?;
+    if !oversized.is_empty() {
+        return Err(anyhow::anyhow!(
+            "Refusing to share: these files in your saves are over the limits:\n{}\n\n\
+             Remove them from the saves listed, then share again.\n{}",
+            limits::describe_saves(repo, &oversized, &config.limits)?,
+            limits::ignore_hint()
+        ));
+    }
+
+    let remote_ref = git::weft_ref(user, &weft);
 
     let mut cmd = Command::new("git");
-    cmd.args(&[
-        "push",
-        "origin",
-        &format!("{}:refs/{}", weft_head, remote_ref),
-    ]);
+    cmd.args(["push", "origin", &format!("{}:{}", weft_head, remote_ref)]);
 
     let output = cmd
         .current_dir(repo.path())
@@ -57,8 +85,10 @@ pub fn run() -> Result<()> {
         return Err(anyhow::anyhow!("Failed to push weft to remote: {}", stderr));
     }
 
-    println!("Shared weft to: {}", remote_url);
-    println!("Remote ref: refs/weft/{}", user);
-
-    Ok(())
+    Ok(ShareOutcome {
+        weft,
+        head: weft_head.parse()?,
+        remote_url,
+        remote_ref,
+    })
 }
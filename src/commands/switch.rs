@@ -0,0 +1,51 @@
+use crate::config;
+use crate::git;
+use crate::jj;
+use anyhow::Result;
+use chrono::Utc;
+
+pub fn run(name: &str) -> Result<()> {
+    let repo = git::discover()?;
+    let user = config::get_user(&repo)?;
+    let name = config::validate_weft_name(name)?;
+    let current = config::get_active_weft(&repo, &user)?;
+
+    if name == current {
+        println!("Already on weft '{}'", name);
+        return Ok(());
+    }
+
+    let target = match git::get_weft_head(&repo, &user, &name)? {
+        Some(oid) => oid,
+        None => {
+            return Err(anyhow::anyhow!(
+                "No weft named '{}'. Create it with: weft new {}",
+                name,
+                name
+            ));
+        }
+    };
+
+    let previous = jj::current_commit(&repo)?;
+    git::update_weft_head(&repo, &user, &current, previous, "weft switch: snapshot")?;
+
+    jj::edit(&repo, target)?;
+    config::set_active_weft(&repo, &user, &name)?;
+
+    let op_entry = serde_json::json!({
+        "timestamp": Utc::now().timestamp(),
+        "command": "switch",
+        "args": {"name": name},
+        "inverse": {
+            "op": "switch-weft",
+            "weft": current,
+            "commit": previous.to_string()
+        }
+    });
+
+    git::update_op_log(&repo, &op_entry.to_string())?;
+
+    println!("Switched to weft '{}'", name);
+
+    Ok(())
+}
@@ -1,87 +1,153 @@
-use crate::config;
+use crate::daemon;
 use crate::git;
+use crate::hooks::{self, HookEnv};
+use crate::jj;
+use crate::outcome::SyncOutcome;
+use crate::Weft;
 use anyhow::Result;
+use git2::{Commit, Oid, Repository};
+use serde_json::json;
 use std::process::Command;
 
 pub fn run() -> Result<()> {
-    let repo = git::discover()?;
-    let user = config::get_user(&repo)?;
+    let outcome: SyncOutcome = daemon::call_or("sync", json!({}), || Weft::discover()?.sync())?;
+    render(&outcome);
+    Ok(())
+}
+
+/// Prints what a sync did; shared with the commands that sync as a step.
+pub fn render(outcome: &SyncOutcome) {
+    for report in &outcome.hooks {
+        eprintln!("{}", report);
+    }
+    if let Some(issues) = &outcome.issues {
+        println!("Sync encountered issues: {}", issues);
+    }
+    if !outcome.dropped.is_empty() {
+        println!(
+            "Dropped {} adopted saves that already landed in trunk.",
+            outcome.dropped.len()
+        );
+    }
 
-    let weft_head_ref = format!("refs/weft/{}/head", user);
-    let weft_head = match repo.find_reference(&weft_head_ref) {
-        Ok(ref_) => ref_.peel_to_commit()?.id(),
-        Err(_) => {
+    if outcome.tangled.is_empty() {
+        println!("Synced. No conflicts.");
+    } else {
+        println!("Synced. {} tangled commits.", outcome.tangled.len());
+    }
+}
+
+/// Rebases `weft` onto trunk and reports which of its commits are tangled.
+pub fn sync_weft(repo: &Repository, user: &str, weft: &str) -> Result<SyncOutcome> {
+    let weft_head = match git::get_weft_head(repo, user, weft)? {
+        Some(oid) => oid,
+        None => {
             return Err(anyhow::anyhow!(
                 "Weft not initialized. Run 'weft init' first."
             ));
         }
     };
 
-    let main_oid = git::get_main(&repo)?;
-    let origin_main_oid = git::get_origin_main(&repo).ok();
-
-    let target_oid = origin_main_oid.unwrap_or(main_oid);
+    let target_oid = git::get_trunk(repo)?;
+
+    let mut hook_reports = hooks::pre(
+        repo,
+        "pre-sync",
+        &HookEnv {
+            command: "sync",
+            user,
+            weft,
+            head: Some(weft_head),
+            trunk: Some(target_oid),
+            candidate: None,
+        },
+    )?;
 
     let output = Command::new("jj")
-        .args(&[
+        .args(jj::signing_args(repo)?)
+        .args([
             "rebase",
+            "-b",
+            &weft_head.to_string(),
             "-d",
             &target_oid.to_string(),
-            "-r",
-            &format!("{}::", weft_head.to_string()),
         ])
         .current_dir(repo.path())
         .output()?;
 
-    if !output.status.success() {
-        println!(
-            "Sync encountered issues: {}",
+    let issues = if output.status.success() {
+        None
+    } else {
+        Some(
             String::from_utf8_lossy(&output.stderr)
-        );
-    }
-
-    let tangled_count = count_tangled_commits(&repo, &user)?;
-
-    let new_head = match repo.find_reference(&weft_head_ref) {
-        Ok(ref_) => ref_.peel_to_commit()?.id(),
-        Err(_) => weft_head,
+                .trim_end()
+                .to_string(),
+        )
     };
 
-    git::update_weft_head(&repo, &user, new_head, "weft sync")?;
+    let mut new_head = jj::current_commit(repo).unwrap_or(weft_head);
 
-    if tangled_count > 0 {
-        println!("Synced. {} tangled commits.", tangled_count);
-    } else {
-        println!("Synced. No conflicts.");
+    let landed = landed_adoptions(repo, target_oid, new_head)?;
+    if !landed.is_empty() {
+        jj::abandon(repo, &landed)?;
+        new_head = jj::current_commit(repo)?;
     }
 
-    Ok(())
+    git::update_weft_head(repo, user, weft, new_head, "weft sync")?;
+
+    hook_reports.extend(hooks::post(
+        repo,
+        "post-sync",
+        &HookEnv {
+            command: "sync",
+            user,
+            weft,
+            head: Some(new_head),
+            trunk: Some(target_oid),
+            candidate: None,
+        },
+    ));
+
+    Ok(SyncOutcome {
+        weft: weft.to_string(),
+        head: new_head,
+        trunk: target_oid,
+        tangled: git::tangled_between(repo, target_oid, new_head)?,
+        dropped: landed,
+        issues,
+        hooks: hook_reports,
+    })
 }
 
-fn count_tangled_commits(repo: &git2::Repository, user: &str) -> Result<usize> {
-    let weft_head_ref = format!("refs/weft/{}/head", user);
-    let weft_head = match repo.find_reference(&weft_head_ref) {
-        Ok(ref_) => ref_.peel_to_commit()?.id(),
-        Err(_) => return Ok(0),
-    };
-
-    let output = Command::new("jj")
-        .args(&[
-            "log",
-            "-r",
-            &format!("{}::", weft_head),
-            "-T",
-            r#"if(conflict, "tangled\n", "clean\n")"#,
-        ])
-        .current_dir(repo.path())
-        .output()?;
-
-    if !output.status.success() {
-        return Ok(0);
+/// Adopted saves whose original is now part of trunk, or that became empty
+/// after rebasing because trunk already contains the same change. Saves that
+/// were empty to begin with, such as message-only ones, are kept.
+fn landed_adoptions(repo: &Repository, trunk: Oid, weft_head: Oid) -> Result<Vec<Oid>> {
+    let mut landed = Vec::new();
+
+    for oid in git::commits_between(repo, trunk, weft_head)? {
+        let commit = repo.find_commit(oid)?;
+        let original = match git::adopted_from(commit.message().unwrap_or("")) {
+            Some((_, original)) => original,
+            None => continue,
+        };
+
+        let in_trunk = original == trunk || repo.graph_descendant_of(trunk, original)?;
+        let emptied = is_empty(&commit)?
+            && match repo.find_commit(original) {
+                Ok(original) => !is_empty(&original)?,
+                Err(_) => false,
+            };
+
+        if in_trunk || emptied {
+            landed.push(oid);
+        }
     }
 
-    let content = String::from_utf8_lossy(&output.stdout);
-    let count = content.lines().filter(|l| l.trim() == "tangled").count();
+    Ok(landed)
+}
 
-    Ok(count)
+/// Whether `commit` changes nothing relative to its only parent.
+fn is_empty(commit: &Commit) -> Result<bool> {
+    Ok(commit.parent_count() == 1 && commit.parent(0)?.tree_id() == commit.tree_id())
 }
@@ -0,0 +1,43 @@
+use crate::config;
+use crate::format;
+use crate::git;
+use anyhow::Result;
+
+pub fn run() -> Result<()> {
+    let repo = git::discover()?;
+    let user = config::get_user(&repo)?;
+    let trunk = git::get_trunk(&repo)?;
+
+    let wefts: Vec<git::TeamWeft> = git::list_team_wefts(&repo)?
+        .into_iter()
+        .filter(|w| w.user != user)
+        .collect();
+
+    if wefts.is_empty() {
+        println!("No shared wefts from teammates. Run 'weft fetch' first.");
+        return Ok(());
+    }
+
+    for weft in &wefts {
+        let commit = repo.find_commit(weft.head)?;
+        let (ahead, behind) = repo.graph_ahead_behind(weft.head, trunk)?;
+        let tangled = git::tangled_between(&repo, trunk, weft.head)?.len();
+
+        println!("{}/{}", weft.user, weft.weft);
+        println!(
+            "  Last save: {} ({})",
+            commit.summary().unwrap_or(""),
+            format::relative_time(commit.time().seconds())
+        );
+        if tangled > 0 {
+            println!(
+                "  {} ahead, {} behind trunk, {} tangled",
+                ahead, behind, tangled
+            );
+        } else {
+            println!("  {} ahead, {} behind trunk", ahead, behind);
+        }
+    }
+
+    Ok(())
+}
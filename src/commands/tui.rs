@@ -0,0 +1,649 @@
+//! `weft tui`: a full-screen view of the weft, built on the same
+//! [`StatusReport`] as `weft status`, with teammates' wefts, open candidates
+//! and a diff preview of whatever is selected.
+
+use crate::commands::{diff, fetch, untangle};
+use crate::git;
+use crate::loom::{self, CandidateInfo};
+use crate::outcome::{CommitSummary, ProposeOutcome, StatusReport};
+use crate::{ProposeOptions, Weft};
+use anyhow::Result;
+use crossterm::event::{self, Event, KeyCode, KeyEventKind};
+use git2::Oid;
+use ratatui::layout::{Constraint, Layout, Rect};
+use ratatui::style::{Color, Modifier, Style, Stylize};
+use ratatui::text::{Line, Text};
+use ratatui::widgets::{Block, Clear, List, ListItem, ListState, Paragraph, Wrap};
+use ratatui::{DefaultTerminal, Frame};
+use std::io::IsTerminal;
+
+const PANES: [&str; 4] = ["Saves", "Tangled", "Team", "Candidates"];
+const SAVES: usize = 0;
+const TANGLED: usize = 1;
+const TEAM: usize = 2;
+const CANDIDATES: usize = 3;
+
+/// One selectable line in a pane, and what the preview shows for it.
+struct Row {
+    label: String,
+    preview: Preview,
+}
+
+enum Preview {
+    /// A commit against its first parent.
+    Commit(Oid),
+    /// Everything on `tip` since it left trunk.
+    SinceTrunk(Oid),
+}
+
+enum Mode {
+    Normal,
+    Input { action: Input, text: String },
+    Confirm { prompt: String, action: Remote },
+}
+
+#[derive(Clone, Copy)]
+enum Input {
+    SaveMessage,
+    ProposeTitle,
+}
+
+/// Actions that talk to origin and so are confirmed first.
+enum Remote {
+    Propose { title: Option<String> },
+    Fetch,
+}
+
+/// What the event loop has to do outside of drawing.
+enum Step {
+    Continue,
+    Quit,
+    Untangle(Oid),
+}
+
+struct App {
+    weft: Weft,
+    report: Option<StatusReport>,
+    panes: [Vec<Row>; 4],
+    lists: [ListState; 4],
+    focus: usize,
+    preview: String,
+    scroll: u16,
+    mode: Mode,
+    /// The outcome of the last action, and whether it failed.
+    message: Option<(String, bool)>,
+    /// Set after actions, since hooks and jj may have written to the terminal.
+    repaint: bool,
+}
+
+pub fn run() -> Result<()> {
+    if !std::io::stdout().is_terminal() {
+        return Err(anyhow::anyhow!(
+            "weft tui needs a terminal. Use 'weft status' in scripts."
+        ));
+    }
+
+    let weft = Weft::discover()?;
+    if !weft.is_initialized()? {
+        println!("Weft not initialized. Run 'weft init' first.");
+        return Ok(());
+    }
+
+    let mut app = App {
+        weft,
+        report: None,
+        panes: Default::default(),
+        lists: Default::default(),
+        focus: SAVES,
+        preview: String::new(),
+        scroll: 0,
+        mode: Mode::Normal,
+        message: None,
+        repaint: false,
+    };
+    app.reload();
+
+    let mut terminal = ratatui::try_init()?;
+    let result = event_loop(&mut terminal, &mut app);
+    ratatui::restore();
+    result
+}
+
+fn event_loop(terminal: &mut DefaultTerminal, app: &mut App) -> Result<()> {
+    loop {
+        terminal.draw(|frame| draw(frame, app))?;
+
+        let key = match event::read()? {
+            Event::Key(key) if key.kind == KeyEventKind::Press => key.code,
+            _ => continue,
+        };
+
+        match app.on_key(key) {
+            Step::Continue => {}
+            Step::Quit => return Ok(()),
+            Step::Untangle(commit) => {
+                // jj's merge tool needs the terminal to itself.
+                ratatui::restore();
+                let outcome = untangle::untangle(&app.weft, commit);
+                *terminal = ratatui::try_init()?;
+                app.report_outcome(outcome);
+                app.reload();
+            }
+        }
+
+        if std::mem::take(&mut app.repaint) {
+            terminal.clear()?;
+        }
+    }
+}
+
+impl App {
+    /// Re-reads everything shown, keeping the selection where it can.
+    fn reload(&mut self) {
+        let report = match self.weft.status() {
+            Ok(report) => report,
+            Err(e) => {
+                self.message = Some((format!("{:#}", e), true));
+                return;
+            }
+        };
+
+        self.panes[SAVES] = report.recent.iter().map(commit_row).collect();
+        self.panes[TANGLED] = report.tangled.iter().map(commit_row).collect();
+        self.panes[TEAM] = self.team_rows().unwrap_or_default();
+        self.panes[CANDIDATES] = self.candidate_rows().unwrap_or_default();
+        self.report = Some(report);
+
+        for (pane, list) in self.panes.iter().zip(self.lists.iter_mut()) {
+            let selected = match list.selected() {
+                _ if pane.is_empty() => None,
+                Some(i) => Some(i.min(pane.len() - 1)),
+                None => Some(0),
+            };
+            list.select(selected);
+        }
+        self.update_preview();
+    }
+
+    fn team_rows(&self) -> Result<Vec<Row>> {
+        let repo = self.weft.repo();
+        let trunk = git::get_trunk(repo)?;
+
+        let mut rows = Vec::new();
+        for team in git::list_team_wefts(repo)? {
+            if team.user == self.weft.user() {
+                continue;
+            }
+            let (ahead, behind) = repo.graph_ahead_behind(team.head, trunk)?;
+            let tangled = git::tangled_between(repo, trunk, team.head)?.len();
+            let mut label = format!(
+                "{}/{}  {} ahead, {} behind",
+                team.user, team.weft, ahead, behind
+            );
+            if tangled > 0 {
+                label.push_str(&format!(", {} tangled", tangled));
+            }
+            rows.push(Row {
+                label,
+                preview: Preview::SinceTrunk(team.head),
+            });
+        }
+        Ok(rows)
+    }
+
+    fn candidate_rows(&self) -> Result<Vec<Row>> {
+        let repo = self.weft.repo();
+        let trunk = git::get_trunk(repo)?;
+
+        let mut rows = Vec::new();
+        for candidate in loom::list(repo)? {
+            let info = loom::inspect(repo, &candidate, trunk)?;
+            rows.push(Row {
+                label: candidate_label(&info),
+                preview: Preview::SinceTrunk(candidate.head),
+            });
+        }
+        Ok(rows)
+    }
+
+    fn update_preview(&mut self) {
+        self.scroll = 0;
+        let row = self.lists[self.focus]
+            .selected()
+            .and_then(|i| self.panes[self.focus].get(i));
+
+        self.preview = match row {
+            Some(row) => {
+                preview_text(&self.weft, &row.preview).unwrap_or_else(|e| format!("{:#}", e))
+            }
+            None => String::new(),
+        };
+    }
+
+    fn on_key(&mut self, key: KeyCode) -> Step {
+        match std::mem::replace(&mut self.mode, Mode::Normal) {
+            Mode::Normal => self.on_normal_key(key),
+            Mode::Input { action, mut text } => {
+                match key {
+                    KeyCode::Enter => self.submit(action, text.trim().to_string()),
+                    KeyCode::Esc => {}
+                    KeyCode::Backspace => {
+                        text.pop();
+                        self.mode = Mode::Input { action, text };
+                    }
+                    KeyCode::Char(c) => {
+                        text.push(c);
+                        self.mode = Mode::Input { action, text };
+                    }
+                    _ => self.mode = Mode::Input { action, text },
+                }
+                Step::Continue
+            }
+            Mode::Confirm { prompt, action } => {
+                match key {
+                    KeyCode::Char('y') | KeyCode::Char('Y') => self.run_remote(action),
+                    KeyCode::Char('n') | KeyCode::Char('N') | KeyCode::Esc => {
+                        self.message = Some(("Cancelled.".to_string(), false));
+                    }
+                    _ => self.mode = Mode::Confirm { prompt, action },
+                }
+                Step::Continue
+            }
+        }
+    }
+
+    fn on_normal_key(&mut self, key: KeyCode) -> Step {
+        match key {
+            KeyCode::Char('q') | KeyCode::Esc => return Step::Quit,
+            KeyCode::Tab => self.focus_pane((self.focus + 1) % PANES.len()),
+            KeyCode::BackTab => self.focus_pane((self.focus + PANES.len() - 1) % PANES.len()),
+            KeyCode::Down | KeyCode::Char('j') => self.move_selection(1),
+            KeyCode::Up | KeyCode::Char('k') => self.move_selection(-1),
+            KeyCode::PageDown | KeyCode::Char('J') => self.scroll = self.scroll.saturating_add(10),
+            KeyCode::PageUp | KeyCode::Char('K') => self.scroll = self.scroll.saturating_sub(10),
+            KeyCode::Char('r') => {
+                self.message = None;
+                self.reload();
+            }
+            KeyCode::Char('s') => {
+                self.mode = Mode::Input {
+                    action: Input::SaveMessage,
+                    text: String::new(),
+                }
+            }
+            KeyCode::Char('y') => {
+                let outcome = self.weft.sync().map(|synced| {
+                    let message = match synced.tangled.len() {
+                        0 => "Synced. No conflicts.".to_string(),
+                        n => format!("Synced. {} tangled commits.", n),
+                    };
+                    with_hooks(message, &synced.hooks)
+                });
+                self.report_outcome(outcome);
+                self.reload();
+            }
+            KeyCode::Char('u') => return self.untangle_selected(),
+            KeyCode::Char('p') => {
+                self.mode = Mode::Input {
+                    action: Input::ProposeTitle,
+                    text: String::new(),
+                }
+            }
+            KeyCode::Char('z') => {
+                let outcome = self
+                    .weft
+                    .undo()
+                    .map(|undone| format!("Undid: weft {}", undone.command));
+                self.report_outcome(outcome);
+                self.reload();
+            }
+            KeyCode::Char('f') => {
+                self.mode = Mode::Confirm {
+                    prompt: "Fetch teammates' wefts and candidates from origin?".to_string(),
+                    action: Remote::Fetch,
+                }
+            }
+            _ => {}
+        }
+        Step::Continue
+    }
+
+    fn focus_pane(&mut self, pane: usize) {
+        self.focus = pane;
+        self.update_preview();
+    }
+
+    fn move_selection(&mut self, delta: isize) {
+        let len = self.panes[self.focus].len();
+        if len == 0 {
+            return;
+        }
+        let list = &mut self.lists[self.focus];
+        let current = list.selected().unwrap_or(0) as isize;
+        list.select(Some((current + delta).clamp(0, len as isize - 1) as usize));
+        self.update_preview();
+    }
+
+    fn submit(&mut self, action: Input, text: String) {
+        match action {
+            Input::SaveMessage if text.is_empty() => {
+                self.message = Some(("A save needs a message.".to_string(), true));
+            }
+            Input::SaveMessage => {
+                let outcome = self.weft.save(&text).map(|saved| {
+                    let message = match saved.skipped.len() {
+                        0 => format!("Saved: {}", saved.message),
+                        n => format!(
+                            "Saved: {} ({} files over the limits left out)",
+                            saved.message, n
+                        ),
+                    };
+                    with_hooks(message, &saved.hooks)
+                });
+                self.report_outcome(outcome);
+                self.reload();
+            }
+            Input::ProposeTitle => {
+                let title = (!text.is_empty()).then_some(text);
+                self.mode = Mode::Confirm {
+                    prompt: "Push this weft to origin as a candidate?".to_string(),
+                    action: Remote::Propose { title },
+                };
+            }
+        }
+    }
+
+    fn run_remote(&mut self, action: Remote) {
+        let outcome = match action {
+            Remote::Propose { title } => self
+                .weft
+                .propose(ProposeOptions {
+                    title,
+                    ..Default::default()
+                })
+                .map(|outcome| match outcome {
+                    ProposeOutcome::NothingToPropose { weft } => {
+                        format!("Weft '{}' has no changes relative to trunk.", weft)
+                    }
+                    ProposeOutcome::AlreadyProposed {
+                        candidate_id,
+                        revision,
+                        ..
+                    } => format!(
+                        "Already proposed as '{}' (revision {}).",
+                        candidate_id, revision
+                    ),
+                    ProposeOutcome::Proposed(proposal) => with_hooks(
+                        format!(
+                            "Proposed '{}' (revision {}).",
+                            proposal.candidate_id, proposal.revision
+                        ),
+                        &proposal.hooks,
+                    ),
+                }),
+            Remote::Fetch => {
+                let repo = self.weft.repo();
+                fetch::fetch_team(repo).and_then(|wefts| {
+                    loom::fetch(repo)?;
+                    Ok(format!(
+                        "Fetched {} shared wefts and the candidates.",
+                        wefts
+                    ))
+                })
+            }
+        };
+        self.report_outcome(outcome);
+        self.reload();
+    }
+
+    /// Untangles the selected tangled commit, or the oldest one.
+    fn untangle_selected(&mut self) -> Step {
+        let tangled = match &self.report {
+            Some(report) if !report.tangled.is_empty() => &report.tangled,
+            _ => {
+                self.message = Some(("Nothing is tangled.".to_string(), false));
+                return Step::Continue;
+            }
+        };
+
+        let commit = match self.lists[TANGLED].selected() {
+            Some(i) if self.focus == TANGLED => tangled[i].id,
+            _ => tangled[tangled.len() - 1].id,
+        };
+        Step::Untangle(commit)
+    }
+
+    fn report_outcome(&mut self, outcome: Result<String>) {
+        self.repaint = true;
+        self.message = Some(match outcome {
+            Ok(message) => (message, false),
+            Err(e) => (format!("{:#}", e), true),
+        });
+    }
+}
+
+/// Appends what the hooks printed to an outcome's message; anything past
+/// the first line shows in a popup.
+fn with_hooks(message: String, hooks: &[String]) -> String {
+    let mut lines = vec![message];
+    lines.extend(hooks.iter().cloned());
+    lines.join("\n")
+}
+
+fn commit_row(commit: &CommitSummary) -> Row {
+    Row {
+        label: format!("{} {}", &commit.id.to_string()[..8], commit.summary),
+        preview: Preview::Commit(commit.id),
+    }
+}
+
+fn candidate_label(info: &CandidateInfo) -> String {
+    let mut label = format!("{} ({}", info.id, info.author);
+    if info.revisions > 1 {
+        label.push_str(&format!(", rev {}", info.revisions));
+    }
+    label.push(')');
+    if let Some(title) = &info.title {
+        label.push_str(&format!(" {}", title));
+    }
+    if info.unrelated {
+        label.push_str(" [unrelated history]");
+    } else if info.tangled {
+        label.push_str(" [tangled]");
+    } else if !info.applies {
+        label.push_str(" [conflicts with trunk]");
+    }
+    label
+}
+
+fn preview_text(weft: &Weft, preview: &Preview) -> Result<String> {
+    let repo = weft.repo();
+    let (header, base, tip) = match *preview {
+        Preview::Commit(oid) => {
+            let commit = repo.find_commit(oid)?;
+            let parent = commit.parents().next().map(|p| p.id());
+            (
+                commit.message().unwrap_or("").trim_end().to_string(),
+                parent,
+                oid,
+            )
+        }
+        Preview::SinceTrunk(tip) => {
+            let base = repo.merge_base(tip, git::get_trunk(repo)?).ok();
+            let header = match base {
+                Some(base) => format!("Changes since trunk {}", &base.to_string()[..8]),
+                None => "No common history with trunk".to_string(),
+            };
+            (header, base, tip)
+        }
+    };
+
+    let base_tree = match base {
+        Some(base) => Some(repo.find_commit(base)?.tree()?),
+        None => None,
+    };
+    let diff = repo.diff_tree_to_tree(
+        base_tree.as_ref(),
+        Some(&repo.find_commit(tip)?.tree()?),
+        None,
+    )?;
+
+    Ok(format!("{}\n\n{}", header, diff::patch_text(&diff)?))
+}
+
+fn draw(frame: &mut Frame, app: &mut App) {
+    let [header, body, footer] = Layout::vertical([
+        Constraint::Length(1),
+        Constraint::Min(0),
+        Constraint::Length(1),
+    ])
+    .areas(frame.area());
+    let [left, right] =
+        Layout::horizontal([Constraint::Percentage(40), Constraint::Percentage(60)]).areas(body);
+
+    frame.render_widget(Paragraph::new(header_line(app)), header);
+
+    let panes = Layout::vertical([Constraint::Ratio(1, 4); 4]).split(left);
+    for (i, area) in panes.iter().enumerate() {
+        draw_pane(frame, app, i, *area);
+    }
+
+    let preview = Paragraph::new(diff_text(&app.preview))
+        .block(Block::bordered().title("Preview"))
+        .scroll((app.scroll, 0));
+    frame.render_widget(preview, right);
+
+    let footer_line = match &app.message {
+        Some((message, true)) => Line::from(first_line(message)).fg(Color::Red),
+        Some((message, false)) => Line::from(first_line(message)).fg(Color::Green),
+        None => Line::from(
+            "s save  y sync  u untangle  p propose  z undo  f fetch  r refresh  tab pane  q quit",
+        )
+        .add_modifier(Modifier::DIM),
+    };
+    frame.render_widget(Paragraph::new(footer_line), footer);
+
+    match &app.mode {
+        Mode::Normal => {
+            // Errors and hook output are often longer than the footer.
+            if let Some((message, error)) = &app.message {
+                if message.lines().count() > 1 {
+                    let title = if *error { "Error" } else { "Output" };
+                    draw_popup(frame, title, message.clone(), "r to dismiss");
+                }
+            }
+        }
+        Mode::Input { action, text } => {
+            let title = match action {
+                Input::SaveMessage => "Save message",
+                Input::ProposeTitle => "Candidate title (optional)",
+            };
+            draw_popup(
+                frame,
+                title,
+                format!("{}_", text),
+                "enter to continue, esc to cancel",
+            );
+        }
+        Mode::Confirm { prompt, .. } => {
+            draw_popup(
+                frame,
+                "Confirm",
+                prompt.clone(),
+                "y to go ahead, n to cancel",
+            );
+        }
+    }
+}
+
+fn header_line(app: &App) -> Line<'static> {
+    let report = match &app.report {
+        Some(report) => report,
+        None => return Line::from("weft"),
+    };
+
+    let mut parts = vec![format!("weft '{}'", report.weft)];
+    parts.push(format!("{} ahead of trunk", report.ahead));
+    if report.behind > 0 {
+        parts.push(format!("{} behind", report.behind));
+    }
+    if !report.tangled.is_empty() {
+        parts.push(format!("{} tangled", report.tangled.len()));
+    }
+    if report.unsaved_changes {
+        parts.push("unsaved changes".to_string());
+    }
+    Line::from(parts.join(" · ")).add_modifier(Modifier::BOLD)
+}
+
+fn draw_pane(frame: &mut Frame, app: &mut App, pane: usize, area: Rect) {
+    let rows = &app.panes[pane];
+    let focused = pane == app.focus;
+
+    let mut block = Block::bordered().title(format!("{} ({})", PANES[pane], rows.len()));
+    if focused {
+        block = block.border_style(Style::new().fg(Color::Cyan));
+    }
+
+    let items: Vec<ListItem> = if rows.is_empty() {
+        vec![ListItem::new("nothing here").add_modifier(Modifier::DIM)]
+    } else {
+        rows.iter()
+            .map(|row| ListItem::new(row.label.clone()))
+            .collect()
+    };
+
+    let mut list = List::new(items).block(block);
+    if focused {
+        list = list.highlight_style(Style::new().add_modifier(Modifier::REVERSED));
+    }
+    frame.render_stateful_widget(list, area, &mut app.lists[pane]);
+}
+
+fn draw_popup(frame: &mut Frame, title: &str, body: String, hint: &str) {
+    let area = frame.area();
+    let width = area.width.saturating_sub(4).min(70);
+    let height = (body.lines().count() as u16 + 4).min(area.height);
+    let popup = Rect {
+        x: area.x + (area.width - width) / 2,
+        y: area.y + (area.height - height) / 2,
+        width,
+        height,
+    };
+
+    let mut text = Text::from(body);
+    text.push_line(Line::default());
+    text.push_line(Line::from(hint.to_string()).add_modifier(Modifier::DIM));
+
+    frame.render_widget(Clear, popup);
+    frame.render_widget(
+        Paragraph::new(text)
+            .block(Block::bordered().title(title.to_string()))
+            .wrap(Wrap { trim: false }),
+        popup,
+    );
+}
+
+fn diff_text(patch: &str) -> Text<'static> {
+    patch
+        .lines()
+        .map(|line| {
+            let styled = Line::from(line.to_string());
+            if line.starts_with("+++") || line.starts_with("---") || line.starts_with("diff ") {
+                styled.add_modifier(Modifier::BOLD)
+            } else if line.starts_with('+') {
+                styled.fg(Color::Green)
+            } else if line.starts_with('-') {
+                styled.fg(Color::Red)
+            } else if line.starts_with("@@") {
+                styled.fg(Color::Cyan)
+            } else {
+                styled
+            }
+        })
+        .collect()
+}
+
+fn first_line(message: &str) -> String {
+    message.lines().next().unwrap_or("").to_string()
+}
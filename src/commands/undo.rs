@@ -1,14 +1,23 @@
 use crate::config;
+use crate::daemon;
 use crate::git;
+use crate::jj;
+use crate::outcome::UndoOutcome;
+use crate::Weft;
 use anyhow::Result;
-use serde_json;
+use git2::Repository;
+use serde_json::json;
 use std::process::Command;
 
 pub fn run() -> Result<()> {
-    let repo = git::discover()?;
-    let _user = config::get_user(&repo)?;
+    let outcome: UndoOutcome = daemon::call_or("undo", json!({}), || Weft::discover()?.undo())?;
+    println!("Undid: weft {}", outcome.command);
+    Ok(())
+}
 
-    let log_content = match git::get_op_log(&repo) {
+/// Reverts the most recent undoable operation in the op-log.
+pub fn undo_last(repo: &Repository, user: &str) -> Result<UndoOutcome> {
+    let log_content = match git::get_op_log(repo) {
         Ok(Some(content)) => content,
         Ok(None) => {
             return Err(anyhow::anyhow!("No operations to undo"));
@@ -26,8 +35,20 @@ pub fn run() -> Result<()> {
         return Err(anyhow::anyhow!("No operations to undo"));
     }
 
-    let last_op: serde_json::Value = serde_json::from_str(lines.last().unwrap())
-        .map_err(|_| anyhow::anyhow!("Failed to parse op-log"))?;
+    // Hook runs are logged for the record but have nothing to undo.
+    let mut last_op = None;
+    for line in lines.iter().rev() {
+        let entry: serde_json::Value =
+            serde_json::from_str(line).map_err(|_| anyhow::anyhow!("Failed to parse op-log"))?;
+        if entry["inverse"]["op"].as_str() != Some("none") {
+            last_op = Some(entry);
+            break;
+        }
+    }
+    let last_op = match last_op {
+        Some(entry) => entry,
+        None => return Err(anyhow::anyhow!("No operations to undo")),
+    };
 
     let command = last_op["command"].as_str().unwrap_or("unknown");
     let inverse = &last_op["inverse"];
@@ -37,7 +58,7 @@ pub fn run() -> Result<()> {
     match op_type {
         "delete-commit" => {
             let output = Command::new("jj")
-                .args(&["op", "undo", "--no-pager"])
+                .args(["op", "undo", "--no-pager"])
                 .current_dir(repo.path())
                 .output()?;
 
@@ -47,8 +68,6 @@ pub fn run() -> Result<()> {
                     String::from_utf8_lossy(&output.stderr)
                 ));
             }
-
-            println!("Undid: weft {}", command);
         }
         "reset-ref" => {
             let ref_name = inverse["ref"].as_str().unwrap_or("");
@@ -58,8 +77,39 @@ pub fn run() -> Result<()> {
                 let old_oid_parsed = old_oid.parse::<git2::Oid>()?;
                 repo.reference(ref_name, old_oid_parsed, true, &format!("undo {}", command))?;
             }
+        }
+        "switch-weft" => {
+            let weft = inverse["weft"].as_str().unwrap_or("");
+            let commit = inverse["commit"].as_str().unwrap_or("");
+
+            if weft.is_empty() || commit.is_empty() {
+                return Err(anyhow::anyhow!(
+                    "Cannot undo weft {}: op-log entry is incomplete",
+                    command
+                ));
+            }
+
+            jj::edit(repo, commit.parse::<git2::Oid>()?)?;
+            config::set_active_weft(repo, user, weft)?;
+
+            // Operations that also moved the weft head record where it was.
+            if let (Some(ref_name), Some(old_oid)) =
+                (inverse["ref"].as_str(), inverse["old"].as_str())
+            {
+                repo.reference(
+                    ref_name,
+                    old_oid.parse()?,
+                    true,
+                    &format!("undo {}", command),
+                )?;
+            }
 
-            println!("Undid: weft {}", command);
+            // `weft new` records the weft it created, which goes away again.
+            if let Some(ref_name) = inverse["created"].as_str() {
+                if let Ok(mut created) = repo.find_reference(ref_name) {
+                    created.delete()?;
+                }
+            }
         }
         _ => {
             return Err(anyhow::anyhow!(
@@ -69,5 +119,7 @@ pub fn run() -> Result<()> {
         }
     }
 
-    Ok(())
+    Ok(UndoOutcome {
+        command: command.to_string(),
+    })
 }
@@ -0,0 +1,73 @@
+//! `weft untangle`: resolves a tangled commit with jj's merge tool.
+
+use crate::git;
+use crate::jj;
+use crate::Weft;
+use anyhow::Result;
+use chrono::Utc;
+use git2::Oid;
+
+/// Untangles `commit`, or the oldest tangled commit on the active weft.
+pub fn run(commit: Option<&str>) -> Result<()> {
+    let weft = Weft::discover()?;
+    let tangled: Vec<Oid> = weft.status()?.tangled.iter().map(|c| c.id).collect();
+
+    let commit = match commit {
+        Some(rev) => {
+            let oid = weft.repo().revparse_single(rev)?.peel_to_commit()?.id();
+            if !tangled.contains(&oid) {
+                return Err(anyhow::anyhow!(
+                    "Commit {} is not tangled on weft '{}'.",
+                    &oid.to_string()[..8],
+                    weft.active_weft()?
+                ));
+            }
+            oid
+        }
+        None => match tangled.last() {
+            Some(oid) => *oid,
+            None => {
+                println!("Nothing is tangled.");
+                return Ok(());
+            }
+        },
+    };
+
+    println!("{}", untangle(&weft, commit)?);
+    if tangled.len() > 1 {
+        println!(
+            "{} tangled commits left; run 'weft untangle' again.",
+            tangled.len() - 1
+        );
+    }
+    Ok(())
+}
+
+/// Resolves `commit`'s conflicts with jj, then moves the weft to the
+/// rewritten working copy, as `weft sync` does. `weft undo` puts the weft
+/// back where it was.
+pub fn untangle(weft: &Weft, commit: Oid) -> Result<String> {
+    let repo = weft.repo();
+    let active = weft.active_weft()?;
+    let previous = git::get_weft_head(repo, weft.user(), &active)?;
+    jj::resolve(repo, commit)?;
+
+    let head = jj::current_commit(repo)?;
+    git::update_weft_head(repo, weft.user(), &active, head, "weft untangle")?;
+
+    if let Some(previous) = previous {
+        let op_entry = serde_json::json!({
+            "timestamp": Utc::now().timestamp(),
+            "command": "untangle",
+            "args": {"commit": commit.to_string(), "weft": active},
+            "inverse": {
+                "op": "reset-ref",
+                "ref": git::weft_ref(weft.user(), &active),
+                "old": previous.to_string()
+            }
+        });
+        git::update_op_log(repo, &op_entry.to_string())?;
+    }
+
+    Ok(format!("Untangled {}.", &commit.to_string()[..8]))
+}
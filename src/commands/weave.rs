@@ -1,124 +1,370 @@
+use crate::commands::{save, sync};
+use crate::config;
+use crate::daemon;
 use crate::git;
+use crate::hooks::{self, HookEnv};
+use crate::jj;
+use crate::loom::woven::{self, WovenRecord};
+use crate::loom::{self, review, Strategy, Woven};
+use crate::outcome::WeaveOutcome;
+use crate::verify;
+use crate::Weft;
 use anyhow::{Context, Result};
+use chrono::Utc;
+use git2::{Oid, Repository};
+use serde_json::json;
 use std::process::Command;
 
-pub fn run(candidate_id: &str) -> Result<()> {
-    let repo = git::discover()?;
-
-    let candidate_ref = format!("refs/loom/{}", candidate_id);
-
-    let candidate_commit = match repo.find_reference(&candidate_ref) {
-        Ok(ref_) => ref_.peel_to_commit()?.id().to_string(),
-        Err(_) => {
-            let fetch_output = Command::new("git")
-                .args(&[
-                    "fetch",
-                    "origin",
-                    &format!("refs/loom/{}:refs/loom/{}", candidate_id, candidate_id),
-                ])
-                .current_dir(repo.path())
-                .output()
-                .context("Failed to fetch candidate")?;
-
-            if !fetch_output.status.success() {
-                let stderr = String::from_utf8_lossy(&fetch_output.stderr);
-                return Err(anyhow::anyhow!(
-                    "Candidate '{}' not found locally or on remote.\n{}",
-                    candidate_id,
-                    stderr
-                ));
-            }
-
-            match repo.find_reference(&candidate_ref) {
-                Ok(ref_) => ref_.peel_to_commit()?.id().to_string(),
-                Err(_) => {
-                    return Err(anyhow::anyhow!(
-                        "Candidate '{}' not found. Run 'weft propose' first.",
-                        candidate_id
-                    ));
-                }
-            }
+#[derive(Default)]
+pub struct WeaveOptions {
+    /// Overrides the `[weave] strategy` in `.weft.toml`.
+    pub strategy: Option<Strategy>,
+    /// Refuse candidates with commits not signed by a trusted key.
+    pub require_signed: bool,
+}
+
+pub fn run(candidate_id: &str, strategy: Option<Strategy>, require_signed: bool) -> Result<()> {
+    let params = json!({
+        "candidate_id": candidate_id,
+        "strategy": strategy,
+        "require_signed": require_signed,
+    });
+    let outcome: WeaveOutcome = daemon::call_or("weave", params, || {
+        Weft::discover()?.weave(
+            candidate_id,
+            WeaveOptions {
+                strategy,
+                require_signed,
+            },
+        )
+    })?;
+
+    // Hook output goes to stderr so it never mixes with weft's own output.
+    for report in &outcome.hooks {
+        eprintln!("{}", report);
+    }
+    if let Some(check) = &outcome.check {
+        println!("Check '{}' passed.", check.command);
+    }
+    match &outcome.title {
+        Some(title) => println!(
+            "Woven '{}' into main ({}): {}",
+            outcome.candidate_id, outcome.strategy, title
+        ),
+        None => println!(
+            "Woven '{}' into main ({}).",
+            outcome.candidate_id, outcome.strategy
+        ),
+    }
+
+    if outcome.saved.is_some() {
+        println!("Saved your unsaved changes before updating.");
+    }
+    if let Some(synced) = &outcome.synced {
+        if synced.tangled.is_empty() {
+            println!("Synced your weft onto the new main.");
+        } else {
+            println!(
+                "Synced your weft onto the new main. {} tangled commits.",
+                synced.tangled.len()
+            );
         }
-    };
+    }
+    for warning in &outcome.warnings {
+        eprintln!("Warning: {}", warning);
+    }
 
-    let output = Command::new("jj")
-        .args(&[
-            "log",
-            "-r",
-            &candidate_id,
-            "-T",
-            "if(conflict, \"tangled\", \"clean\")",
-        ])
-        .current_dir(repo.path())
-        .output()
-        .context("Failed to check candidate state")?;
+    Ok(())
+}
+
+/// Lands a candidate on main on origin with a single atomic, leased push, then
+/// brings the local trunk and the active weft up to date.
+pub fn weave_candidate(
+    repo: &Repository,
+    candidate_id: &str,
+    options: WeaveOptions,
+) -> Result<WeaveOutcome> {
+    let WeaveOptions {
+        strategy,
+        require_signed,
+    } = options;
 
-    if output.status.success() {
-        let state = String::from_utf8_lossy(&output.stdout).trim().to_string();
-        if state == "tangled" {
+    if let Err(e) = loom::fetch(repo) {
+        return Err(anyhow::anyhow!(
+            "Weave failed at step 'fetch': {}\n\
+             Nothing was changed. Check your connection to origin and try again.",
+            e.to_string().trim_end()
+        ));
+    }
+
+    let candidate = match loom::find(repo, candidate_id)? {
+        Some(candidate) => candidate,
+        None => {
             return Err(anyhow::anyhow!(
-                "Cannot weave: candidate '{}' has unresolved conflicts. Run 'weft untangle' first.",
+                "Candidate '{}' not found locally or on remote. Run 'weft propose' first.",
                 candidate_id
             ));
         }
+    };
+
+    let candidate_commit = candidate.head.to_string();
+
+    let branch = git::main_branch(repo);
+    let main_ref = format!("refs/heads/{}", branch);
+    if repo.find_reference(&main_ref).is_err() {
+        return Err(anyhow::anyhow!(
+            "Branch '{}' not found. Create it first or run 'weft sync'.",
+            branch
+        ));
+    }
+
+    let config = config::load(repo)?;
+    let strategy = strategy.or(config.weave.strategy).unwrap_or(Strategy::Ff);
+
+    // The main we build on is also the lease: the push only lands if origin
+    // still has exactly this main.
+    let expected_main = git::get_origin_main(repo).ok();
+    let trunk = match expected_main {
+        Some(oid) => oid,
+        None => git::get_main(repo)?,
+    };
+
+    if let Some(violation) = loom::validate(repo, candidate_commit.parse()?, trunk)? {
+        return Err(anyhow::anyhow!(
+            "Cannot weave '{}' ({}): {}",
+            candidate_id,
+            &candidate_commit[..8],
+            violation
+        ));
+    }
+
+    if require_signed || config.weave.require_signed {
+        if let Some(violation) = loom::validate_signatures(repo, candidate_commit.parse()?, trunk)?
+        {
+            return Err(anyhow::anyhow!(
+                "Cannot weave '{}' ({}): {}",
+                candidate_id,
+                &candidate_commit[..8],
+                violation
+            ));
+        }
+    }
+
+    if let Some(violation) = review::violation(repo, &candidate, &config.review)? {
+        return Err(anyhow::anyhow!(
+            "Cannot weave '{}' (revision {}): {}",
+            candidate_id,
+            candidate.latest_revision(),
+            violation
+        ));
     }
 
-    let main_ref = "refs/heads/main";
-    let _main_commit = match repo.find_reference(main_ref) {
-        Ok(ref_) => ref_.peel_to_commit()?.id().to_string(),
-        Err(_) => {
+    let meta = loom::read_meta(repo, candidate_id)?;
+    let woven_commit = match loom::weave_commit(
+        repo,
+        strategy,
+        candidate_id,
+        candidate_commit.parse()?,
+        trunk,
+    )? {
+        Woven::Ready(oid) => oid.to_string(),
+        Woven::Refused(reason) => {
             return Err(anyhow::anyhow!(
-                "Branch 'main' not found. Create it first or run 'weft sync'."
+                "Cannot weave '{}' with strategy {}: {}",
+                candidate_id,
+                strategy,
+                reason
             ));
         }
     };
 
+    let mut warnings = Vec::new();
+    let mut check_result = None;
+    if let Some(check) = &config.weave.check {
+        let result = verify::run_check(
+            repo,
+            candidate_id,
+            candidate.head,
+            woven_commit.parse()?,
+            check,
+        )?;
+
+        let checks = repo.refname_to_id(&loom::checks_ref(candidate_id))?;
+        let publish = Command::new("git")
+            .args([
+                "push",
+                "origin",
+                &format!("+{}:{}", checks, loom::checks_ref(candidate_id)),
+            ])
+            .current_dir(repo.path())
+            .output()
+            .context("Failed to publish check result")?;
+        if !publish.status.success() {
+            warnings.push(format!(
+                "Failed to publish check result: {}",
+                String::from_utf8_lossy(&publish.stderr).trim_end()
+            ));
+        }
+
+        if !result.passed {
+            return Err(anyhow::anyhow!(
+                "{}\nWeave refused: check '{}' failed on candidate '{}'.\n\
+                 Full output saved to {}",
+                verify::log_tail(&result, 20),
+                check,
+                candidate_id,
+                verify::log_path(repo, candidate_id, woven_commit.parse()?).display()
+            ));
+        }
+        check_result = Some(result);
+    }
+
+    let user = config::get_user(repo)?;
+    let weft = config::get_active_weft(repo, &user)?;
+    let mut hook_reports = hooks::pre(
+        repo,
+        "pre-weave",
+        &HookEnv {
+            command: "weave",
+            user: &user,
+            weft: &weft,
+            head: Some(woven_commit.parse()?),
+            trunk: Some(trunk),
+            candidate: Some(candidate_id),
+        },
+    )?;
+
+    let (woven_log, woven_refspec) = woven::append(
+        repo,
+        &WovenRecord {
+            candidate: candidate_id.to_string(),
+            title: meta.as_ref().map(|m| m.title.clone()),
+            author: loom::author(repo, candidate_id)?,
+            revision: candidate.latest_revision(),
+            strategy,
+            commit: woven_commit.clone(),
+            by: user.clone(),
+            at: Utc::now().timestamp(),
+        },
+    )?;
+
+    let lease = expected_main.map(|oid| oid.to_string()).unwrap_or_default();
+    let mut refspecs = vec![format!("{}:{}", woven_commit, main_ref), woven_refspec];
+    refspecs.extend(loom::cleanup_refspecs(repo, candidate_id));
+
     let output = Command::new("git")
-        .args(&[
+        .args([
             "push",
+            "--atomic",
+            "--porcelain",
+            &format!("--force-with-lease={}:{}", main_ref, lease),
             "origin",
-            &format!("{}:refs/heads/main", candidate_commit),
         ])
+        .args(&refspecs)
         .current_dir(repo.path())
         .output()
-        .context("Failed to update main")?;
+        .context("Failed to run git push")?;
 
     if !output.status.success() {
+        let porcelain = String::from_utf8_lossy(&output.stdout);
         let stderr = String::from_utf8_lossy(&output.stderr);
-        if stderr.contains("non-fast-forward") || stderr.contains("updates were rejected") {
+        if git::lease_failed(&porcelain) {
+            return Err(anyhow::anyhow!(
+                "Weave failed at step 'push': {} on origin moved since it was fetched \
+                 (expected {}).\n\
+                 Nothing was changed on origin. Run 'weft weave {}' again, or use \
+                 'weft queue add {}' to weave in turn.",
+                branch,
+                if lease.is_empty() {
+                    "no main"
+                } else {
+                    &lease[..8]
+                },
+                candidate_id,
+                candidate_id
+            ));
+        }
+        if stderr.contains("atomic") {
             return Err(anyhow::anyhow!(
-                "Weave failed: main branch has been updated since candidate was created.\n\
-                 Run 'weft sync' and try again, or re-propose your changes."
+                "Weave failed at step 'push': origin does not support atomic pushes.\n\
+                 Nothing was changed on origin. Upgrade the git server to weave safely."
             ));
         }
-        return Err(anyhow::anyhow!("Failed to update main: {}", stderr));
+        return Err(anyhow::anyhow!(
+            "Weave failed at step 'push': {}{}\n\
+             The push is atomic, so nothing was changed on origin.",
+            porcelain,
+            stderr.trim_end()
+        ));
     }
 
-    let output = Command::new("git")
-        .args(&["reset", "--hard", &format!("origin/main")])
-        .current_dir(repo.path())
-        .output()
-        .context("Failed to update local main")?;
+    woven::recorded(repo, woven_log)?;
 
-    if !output.status.success() {
-        let stderr = String::from_utf8_lossy(&output.stderr);
-        eprintln!("Warning: Failed to update local main: {}", stderr);
+    hook_reports.extend(hooks::post(
+        repo,
+        "post-weave",
+        &HookEnv {
+            command: "weave",
+            user: &user,
+            weft: &weft,
+            head: Some(woven_commit.parse()?),
+            trunk: Some(woven_commit.parse()?),
+            candidate: Some(candidate_id),
+        },
+    ));
+
+    let woven: Oid = woven_commit.parse()?;
+    let mut outcome = WeaveOutcome {
+        candidate_id: candidate_id.to_string(),
+        strategy,
+        woven,
+        title: meta.map(|m| m.title),
+        check: check_result,
+        saved: None,
+        synced: None,
+        warnings,
+        hooks: hook_reports,
+    };
+
+    if let Err(e) = update_local(repo, &main_ref, woven, &mut outcome) {
+        outcome.warnings.push(format!(
+            "{} on origin is updated, but step 'update local {}' failed: {}\n\
+             Run 'weft sync' to catch up.",
+            branch,
+            branch,
+            e.to_string().trim_end()
+        ));
     }
 
-    let output = Command::new("git")
-        .args(&["push", "origin", &format!(":{}", candidate_ref)])
-        .current_dir(repo.path())
-        .output()
-        .context("Failed to clean up candidate")?;
+    Ok(outcome)
+}
 
-    if !output.status.success() {
-        let stderr = String::from_utf8_lossy(&output.stderr);
-        eprintln!("Warning: Failed to clean up candidate ref: {}", stderr);
+/// Moves local main to the woven commit and carries the user's weft along.
+/// Unsaved work is saved first, so nothing in the working copy is discarded.
+fn update_local(
+    repo: &Repository,
+    main_ref: &str,
+    woven: Oid,
+    outcome: &mut WeaveOutcome,
+) -> Result<()> {
+    repo.reference(main_ref, woven, true, "weft weave")?;
+
+    let user = config::get_user(repo)?;
+    let weft = config::get_active_weft(repo, &user)?;
+    let weft_head = match git::get_weft_head(repo, &user, &weft)? {
+        Some(oid) => oid,
+        None => return Ok(()),
+    };
+
+    if jj::current_commit(repo)? != weft_head {
+        let saved = save::save_weft(repo, &user, &weft, "auto-save before weave")?;
+        outcome.saved = Some(saved.commit);
+        outcome.hooks.extend(saved.hooks);
     }
 
-    println!("Woven '{}' into main.", candidate_id);
-    println!("\nNext steps:");
-    println!("  weft sync  # Update your weft with the new main");
+    let mut synced = sync::sync_weft(repo, &user, &weft)?;
+    outcome.hooks.extend(std::mem::take(&mut synced.hooks));
+    outcome.synced = Some(synced);
 
     Ok(())
 }
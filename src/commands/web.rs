@@ -0,0 +1,401 @@
+//! `weft web`: a read-only HTML dashboard of the team's wefts, open
+//! candidates, this clone's op-log and what has been woven into trunk.
+//!
+//! Pages are rendered from the local clone on every request; nothing is
+//! fetched and nothing can be changed from the browser.
+
+use crate::commands::diff;
+use crate::format;
+use crate::git;
+use crate::loom::review;
+use crate::loom::woven::{self, WovenRecord};
+use crate::loom::{self, CandidateInfo};
+use anyhow::Result;
+use chrono::{Local, TimeZone};
+use git2::{Oid, Repository};
+use std::collections::HashMap;
+use tiny_http::{Header, Method, Request, Response, Server};
+
+/// How many trunk commits and op-log entries the overview shows.
+const HISTORY_LIMIT: usize = 50;
+
+pub fn run(port: u16) -> Result<()> {
+    let repo = git::discover()?;
+
+    let address = format!("127.0.0.1:{}", port);
+    let server = Server::http(&address)
+        .map_err(|e| anyhow::anyhow!("Cannot listen on {}: {}", address, e))?;
+
+    println!("Serving the weft dashboard at http://{}/", address);
+    println!("It shows this clone as of the last 'weft fetch'. Stop with Ctrl-C.");
+
+    for request in server.incoming_requests() {
+        let path = request.url().split('?').next().unwrap_or("/").to_string();
+
+        let (status, body) = if !local_host(&request, port) {
+            // Another name resolving to 127.0.0.1 (DNS rebinding) would let
+            // any web page read the dashboard.
+            (
+                421,
+                page("Wrong host", "<p>Open the dashboard through localhost.</p>"),
+            )
+        } else if *request.method() != Method::Get {
+            (
+                405,
+                page("Not allowed", "<p>The dashboard is read-only.</p>"),
+            )
+        } else {
+            match route(&repo, &path) {
+                Ok(Some(body)) => (200, body),
+                Ok(None) => (404, page("Not found", "<p>No such page.</p>")),
+                Err(e) => (
+                    500,
+                    page("Error", &format!("<pre>{}</pre>", esc(&format!("{:#}", e)))),
+                ),
+            }
+        };
+
+        let content_type =
+            Header::from_bytes(&b"Content-Type"[..], &b"text/html; charset=utf-8"[..])
+                .expect("static header is valid");
+        let response = Response::from_string(body)
+            .with_status_code(status)
+            .with_header(content_type);
+        if let Err(e) = request.respond(response) {
+            eprintln!("Warning: {}", e);
+        }
+    }
+
+    Ok(())
+}
+
+/// Whether the request names this server as `127.0.0.1:<port>` or
+/// `localhost:<port>` in its Host header.
+fn local_host(request: &Request, port: u16) -> bool {
+    let host = match request
+        .headers()
+        .iter()
+        .find(|header| header.field.equiv("Host"))
+    {
+        Some(header) => header.value.as_str().to_ascii_lowercase(),
+        None => return false,
+    };
+    host == format!("127.0.0.1:{}", port) || host == format!("localhost:{}", port)
+}
+
+fn route(repo: &Repository, path: &str) -> Result<Option<String>> {
+    let parts: Vec<&str> = path.trim_matches('/').split('/').collect();
+    match parts.as_slice() {
+        [""] => overview(repo).map(Some),
+        ["candidate", id] => candidate_page(repo, id),
+        ["team", user, weft] => team_page(repo, user, weft),
+        _ => Ok(None),
+    }
+}
+
+fn overview(repo: &Repository) -> Result<String> {
+    let trunk = git::get_trunk(repo)?;
+    let mut body = String::new();
+
+    body.push_str("<h2>Team wefts</h2>");
+    let team = git::list_team_wefts(repo)?;
+    if team.is_empty() {
+        body.push_str("<p class=\"dim\">No shared wefts. Run <code>weft fetch</code>.</p>");
+    } else {
+        body.push_str("<table><tr><th>Weft</th><th>Last save</th><th>Trunk</th></tr>");
+        for weft in &team {
+            let commit = repo.find_commit(weft.head)?;
+            let (ahead, behind) = repo.graph_ahead_behind(weft.head, trunk)?;
+            let tangled = git::tangled_between(repo, trunk, weft.head)?.len();
+            body.push_str(&format!(
+                "<tr><td><a href=\"/team/{user}/{weft}\">{user}/{weft}</a></td>\
+                 <td>{summary} <span class=\"dim\">{when}</span></td>\
+                 <td>{ahead} ahead, {behind} behind{tangled}</td></tr>",
+                user = esc(&weft.user),
+                weft = esc(&weft.weft),
+                summary = esc(commit.summary().unwrap_or("")),
+                when = format::relative_time(commit.time().seconds()),
+                ahead = ahead,
+                behind = behind,
+                tangled = match tangled {
+                    0 => String::new(),
+                    n => format!(", <span class=\"bad\">{} tangled</span>", n),
+                },
+            ));
+        }
+        body.push_str("</table>");
+    }
+
+    body.push_str("<h2>Open candidates</h2>");
+    let candidates = loom::list(repo)?;
+    if candidates.is_empty() {
+        body.push_str("<p class=\"dim\">No open candidates.</p>");
+    } else {
+        body.push_str("<table><tr><th>Candidate</th><th>Changes</th><th>State</th></tr>");
+        for candidate in &candidates {
+            let info = loom::inspect(repo, candidate, trunk)?;
+            body.push_str(&format!(
+                "<tr><td><a href=\"/candidate/{id}\">{id}</a> {title}<br>\
+                 <span class=\"dim\">{author}, {when}, revision {rev}</span></td>\
+                 <td>{commits} commits, {files} files, \
+                 <span class=\"add\">+{ins}</span> <span class=\"del\">-{del}</span></td>\
+                 <td>{state}</td></tr>",
+                id = esc(&info.id),
+                title = esc(info.title.as_deref().unwrap_or("")),
+                author = esc(&info.author),
+                when = format::relative_time(info.created),
+                rev = candidate.latest_revision(),
+                commits = info.commits,
+                files = info.files_changed,
+                ins = info.insertions,
+                del = info.deletions,
+                state = candidate_state(&info),
+            ));
+        }
+        body.push_str("</table>");
+    }
+
+    body.push_str("<h2>Trunk</h2>");
+    body.push_str(&trunk_history(repo, trunk)?);
+
+    body.push_str(
+        "<h2>Op-log</h2><p class=\"dim\">Operations run in this clone, newest first.</p>",
+    );
+    body.push_str(&oplog_timeline(repo)?);
+
+    Ok(page("weft dashboard", &body))
+}
+
+/// First-parent history of trunk, marking the commits candidates were woven as.
+fn trunk_history(repo: &Repository, trunk: Oid) -> Result<String> {
+    let records: HashMap<String, WovenRecord> = woven::read(repo)?
+        .into_iter()
+        .map(|record| (record.commit.clone(), record))
+        .collect();
+
+    let mut walk = repo.revwalk()?;
+    walk.push(trunk)?;
+    walk.simplify_first_parent()?;
+
+    let mut html = String::from("<table>");
+    for oid in walk.take(HISTORY_LIMIT) {
+        let commit = repo.find_commit(oid?)?;
+        let id = commit.id().to_string();
+        let woven = match records.get(&id) {
+            Some(record) => format!(
+                "<span class=\"tag\">woven {} rev {} ({}) by {}</span>",
+                esc(&record.candidate),
+                record.revision,
+                record.strategy,
+                esc(&record.by)
+            ),
+            None => String::new(),
+        };
+        html.push_str(&format!(
+            "<tr><td><code>{}</code></td><td>{} {}</td><td class=\"dim\">{}</td></tr>",
+            &id[..8],
+            esc(commit.summary().unwrap_or("")),
+            woven,
+            format::relative_time(commit.time().seconds())
+        ));
+    }
+    html.push_str("</table>");
+    Ok(html)
+}
+
+fn oplog_timeline(repo: &Repository) -> Result<String> {
+    let content = git::get_op_log(repo)?.unwrap_or_default();
+    let entries: Vec<serde_json::Value> = content
+        .lines()
+        .filter_map(|line| serde_json::from_str(line).ok())
+        .collect();
+    if entries.is_empty() {
+        return Ok("<p class=\"dim\">Nothing recorded yet.</p>".to_string());
+    }
+
+    let mut html = String::from("<table>");
+    for entry in entries.iter().rev().take(HISTORY_LIMIT) {
+        let timestamp = entry["timestamp"].as_i64().unwrap_or(0);
+        let when = Local
+            .timestamp_opt(timestamp, 0)
+            .single()
+            .map(|t| t.format("%Y-%m-%d %H:%M").to_string())
+            .unwrap_or_default();
+        html.push_str(&format!(
+            "<tr><td class=\"dim\">{}</td><td><b>{}</b></td><td><code>{}</code></td></tr>",
+            when,
+            esc(entry["command"].as_str().unwrap_or("?")),
+            esc(&entry["args"].to_string())
+        ));
+    }
+    html.push_str("</table>");
+    Ok(html)
+}
+
+fn candidate_page(repo: &Repository, candidate_id: &str) -> Result<Option<String>> {
+    let candidate = match loom::find(repo, candidate_id)? {
+        Some(candidate) => candidate,
+        None => return Ok(None),
+    };
+    let trunk = git::get_trunk(repo)?;
+    let info = loom::inspect(repo, &candidate, trunk)?;
+
+    let mut body = format!(
+        "<p><a href=\"/\">&larr; dashboard</a></p><h2>{} {}</h2>\
+         <p class=\"dim\">by {}, revision {} ({}), {}</p><p>{}</p>",
+        esc(&info.id),
+        esc(info.title.as_deref().unwrap_or("")),
+        esc(&info.author),
+        candidate.latest_revision(),
+        &info.head[..8],
+        candidate_state(&info),
+        esc(info.description.as_deref().unwrap_or(""))
+    );
+
+    if let Some(check) = &info.check {
+        body.push_str(&format!(
+            "<p>Check <code>{}</code> {} {}</p>",
+            esc(&check.command),
+            if check.passed {
+                "<span class=\"add\">passed</span>"
+            } else {
+                "<span class=\"bad\">failed</span>"
+            },
+            format::relative_time(check.ran_at)
+        ));
+    }
+
+    let (votes, _) = review::read(repo, &candidate.id)?;
+    let votes = review::current_votes(&candidate, &votes);
+    if !votes.is_empty() {
+        body.push_str("<h3>Votes on this revision</h3><ul>");
+        for vote in votes {
+            let verdict = match vote.verdict {
+                review::Verdict::Approve => "<span class=\"add\">approved</span>",
+                review::Verdict::Reject => "<span class=\"bad\">rejected</span>",
+            };
+            body.push_str(&format!(
+                "<li>{} {} {}</li>",
+                esc(&vote.voter),
+                verdict,
+                esc(vote.reason.as_deref().unwrap_or(""))
+            ));
+        }
+        body.push_str("</ul>");
+    }
+
+    body.push_str("<h3>Changes against trunk</h3>");
+    body.push_str(&patch_since_trunk(repo, candidate.head, trunk)?);
+    Ok(Some(page(&info.id, &body)))
+}
+
+fn team_page(repo: &Repository, user: &str, weft: &str) -> Result<Option<String>> {
+    let head = match repo.find_reference(&git::team_ref(user, weft)) {
+        Ok(ref_) => ref_.peel_to_commit()?.id(),
+        Err(_) => return Ok(None),
+    };
+    let trunk = git::get_trunk(repo)?;
+
+    let mut body = format!(
+        "<p><a href=\"/\">&larr; dashboard</a></p><h2>{}/{}</h2><h3>Saves since trunk</h3><table>",
+        esc(user),
+        esc(weft)
+    );
+    for oid in git::commits_between(repo, trunk, head)? {
+        let commit = repo.find_commit(oid)?;
+        let tangled = if git::is_tangled(repo, oid)? {
+            " <span class=\"bad\">tangled</span>"
+        } else {
+            ""
+        };
+        body.push_str(&format!(
+            "<tr><td><code>{}</code></td><td>{}{}</td><td class=\"dim\">{}</td></tr>",
+            &oid.to_string()[..8],
+            esc(commit.summary().unwrap_or("")),
+            tangled,
+            format::relative_time(commit.time().seconds())
+        ));
+    }
+    body.push_str("</table><h3>Changes against trunk</h3>");
+    body.push_str(&patch_since_trunk(repo, head, trunk)?);
+
+    Ok(Some(page(&format!("{}/{}", user, weft), &body)))
+}
+
+fn patch_since_trunk(repo: &Repository, tip: Oid, trunk: Oid) -> Result<String> {
+    // Unrelated history shows as the whole tree being added.
+    let base_tree = match repo.merge_base(tip, trunk) {
+        Ok(base) => Some(repo.find_commit(base)?.tree()?),
+        Err(_) => None,
+    };
+    let patch = repo.diff_tree_to_tree(
+        base_tree.as_ref(),
+        Some(&repo.find_commit(tip)?.tree()?),
+        None,
+    )?;
+    let patch = diff::patch_text(&patch)?;
+
+    let mut html = String::from("<pre class=\"diff\">");
+    for line in patch.lines() {
+        let class = if line.starts_with("+++") || line.starts_with("---") {
+            "file"
+        } else if line.starts_with('+') {
+            "add"
+        } else if line.starts_with('-') {
+            "del"
+        } else if line.starts_with("@@") {
+            "hunk"
+        } else if line.starts_with("diff ") {
+            "file"
+        } else {
+            ""
+        };
+        html.push_str(&format!("<span class=\"{}\">{}</span>\n", class, esc(line)));
+    }
+    html.push_str("</pre>");
+    Ok(html)
+}
+
+fn candidate_state(info: &CandidateInfo) -> String {
+    if info.unrelated {
+        "<span class=\"bad\">unrelated history</span>".to_string()
+    } else if info.tangled {
+        "<span class=\"bad\">tangled</span>".to_string()
+    } else if !info.applies {
+        "<span class=\"bad\">conflicts with trunk</span>".to_string()
+    } else if info.behind_trunk > 0 {
+        format!("applies on trunk ({} behind)", info.behind_trunk)
+    } else {
+        "up to date with trunk".to_string()
+    }
+}
+
+fn page(title: &str, body: &str) -> String {
+    format!(
+        "<!DOCTYPE html>\n<html><head><meta charset=\"utf-8\"><title>{}</title>\
+         <style>{}</style></head><body><h1>weft</h1>{}</body></html>\n",
+        esc(title),
+        STYLE,
+        body
+    )
+}
+
+const STYLE: &str = "\
+body{font-family:system-ui,sans-serif;max-width:70em;margin:2em auto;padding:0 1em;color:#222}\
+h1{font-size:1.2em;color:#555}\
+table{border-collapse:collapse;width:100%}\
+td,th{text-align:left;padding:.3em .6em;border-bottom:1px solid #eee;vertical-align:top}\
+code,pre{font-family:ui-monospace,monospace;font-size:.9em}\
+pre.diff{background:#f8f8f8;padding:1em;overflow-x:auto}\
+.dim{color:#888}.add{color:#1a7f37}.del{color:#cf222e}.bad{color:#cf222e;font-weight:bold}\
+.hunk{color:#0969da}.file{font-weight:bold}\
+.tag{background:#ddf4ff;border-radius:.3em;padding:0 .4em;font-size:.85em}";
+
+/// Escapes text for use in HTML.
+fn esc(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&#39;")
+}
@@ -1,6 +1,12 @@
-use anyhow::Result;
-use git2::Repository;
+use crate::loom::Strategy;
+use anyhow::{Context, Result};
+use git2::{ConfigLevel, Repository};
+use serde::Deserialize;
 use std::env;
+use std::fs;
+
+/// Name of the weft every user starts with (`refs/weft/<user>/head`).
+pub const DEFAULT_WEFT: &str = "head";
 
 pub fn get_user(repo: &Repository) -> Result<String> {
     if let Ok(user) = env::var("WEFT_USER") {
@@ -32,3 +38,146 @@ fn sanitize_username(name: &str) -> String {
     name.replace(|c: char| !c.is_alphanumeric() && c != '-' && c != '_', "-")
         .to_lowercase()
 }
+
+/// Returns the name of the weft that commands act on for `user`.
+pub fn get_active_weft(repo: &Repository, user: &str) -> Result<String> {
+    if let Ok(config) = repo.config() {
+        if let Ok(name) = config.get_string(&format!("weft.{}.active", user)) {
+            if !name.is_empty() {
+                return Ok(name);
+            }
+        }
+    }
+
+    Ok(DEFAULT_WEFT.to_string())
+}
+
+pub fn set_active_weft(repo: &Repository, user: &str, weft: &str) -> Result<()> {
+    let mut config = repo.config()?.open_level(ConfigLevel::Local)?;
+    config.set_str(&format!("weft.{}.active", user), weft)?;
+    Ok(())
+}
+
+pub fn validate_weft_name(name: &str) -> Result<String> {
+    let valid = !name.is_empty()
+        && !name.starts_with('-')
+        && name
+            .chars()
+            .all(|c| c.is_alphanumeric() || c == '-' || c == '_');
+
+    if !valid {
+        return Err(anyhow::anyhow!(
+            "Invalid weft name '{}'. Use letters, digits, '-' and '_' only.",
+            name
+        ));
+    }
+
+    Ok(name.to_string())
+}
+
+/// Per-repo settings read from `.weft.toml` at the root of the working copy.
+#[derive(Deserialize, Default)]
+#[serde(default, deny_unknown_fields)]
+pub struct WeftConfig {
+    pub weave: WeaveConfig,
+    pub review: ReviewConfig,
+    pub secrets: SecretsConfig,
+    pub limits: LimitsConfig,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default, deny_unknown_fields)]
+pub struct WeaveConfig {
+    /// Shell command that must succeed on the exact tree being woven.
+    pub check: Option<String>,
+    /// Strategy used when `weft weave` is run without `--strategy`.
+    pub strategy: Option<Strategy>,
+    /// Refuse candidates with commits not signed by a trusted key.
+    pub require_signed: bool,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default, deny_unknown_fields)]
+pub struct ReviewConfig {
+    /// Approvals the latest revision needs before it can be woven.
+    pub approvals: usize,
+    /// Whether the candidate's author may approve their own work.
+    pub allow_author: bool,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default, deny_unknown_fields)]
+pub struct SecretsConfig {
+    /// Patterns checked on top of the built-in credential patterns.
+    pub patterns: Vec<SecretPattern>,
+    /// Hits that are known not to be real secrets.
+    pub allow: Vec<SecretAllow>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SecretPattern {
+    pub name: String,
+    pub regex: String,
+}
+
+/// An allowlist entry; a hit is allowed when every field given matches it.
+#[derive(Deserialize, Default)]
+#[serde(default, deny_unknown_fields)]
+pub struct SecretAllow {
+    /// A file path, or a directory when it ends in `/`.
+    pub path: Option<String>,
+    /// The exact text that matched.
+    pub text: Option<String>,
+    /// Name of the pattern that matched.
+    pub pattern: Option<String>,
+}
+
+/// What `weft save` does with files over the limits. Share and propose always refuse.
+#[derive(Deserialize, Clone, Copy, PartialEq, Eq, Debug, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum OnSave {
+    /// Leave new offending files out of the save and warn.
+    #[default]
+    Skip,
+    /// Fail the save.
+    Refuse,
+}
+
+#[derive(Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct LimitsConfig {
+    /// Largest file, in bytes, a save may contain.
+    pub max_file_size: u64,
+    /// Whether saves may contain binary files.
+    pub allow_binary: bool,
+    pub on_save: OnSave,
+    /// Paths exempt from the limits; a directory when it ends in `/`.
+    pub allow: Vec<String>,
+}
+
+impl Default for LimitsConfig {
+    fn default() -> Self {
+        LimitsConfig {
+            max_file_size: 50_000_000,
+            allow_binary: true,
+            on_save: OnSave::Skip,
+            allow: Vec::new(),
+        }
+    }
+}
+
+pub fn load(repo: &Repository) -> Result<WeftConfig> {
+    let path = match repo.workdir() {
+        Some(workdir) => workdir.join(".weft.toml"),
+        None => return Ok(WeftConfig::default()),
+    };
+
+    if !path.exists() {
+        return Ok(WeftConfig::default());
+    }
+
+    let content =
+        fs::read_to_string(&path).with_context(|| format!("Failed to read {}", path.display()))?;
+    toml::from_str(&content).with_context(|| format!("Invalid config in {}", path.display()))
+}
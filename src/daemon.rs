@@ -0,0 +1,168 @@
+//! Client for `weft serve`, the per-repo daemon listening on
+//! `.git/weft/serve.sock`.
+//!
+//! Commands ask [`connect`] for a daemon and fall back to doing the work
+//! themselves when none is running. Set `WEFT_NO_DAEMON=1` to never use one.
+//!
+//! Hooks, the editor and jj run in the daemon's environment, so every request
+//! carries the caller's user and [`CALLER_ENV`], and the daemon refuses
+//! requests from a caller whose differ from its own.
+
+use crate::git;
+use anyhow::Result;
+use git2::Repository;
+use serde::de::DeserializeOwned;
+use serde_json::{json, Value};
+use std::path::PathBuf;
+
+/// Error code the daemon answers with when it serves someone else.
+pub const WRONG_USER: i64 = -32001;
+
+/// Error code the daemon answers with when the caller's environment differs
+/// from the one its hooks and editor would run in.
+pub const WRONG_ENV: i64 = -32002;
+
+/// The environment variables weft, its hooks and jj depend on; a daemon only
+/// serves callers that agree with it on all of them.
+pub const CALLER_ENV: &[&str] = &["PATH", "HOME", "EDITOR", "VISUAL", "JJ_CONFIG"];
+
+/// [`CALLER_ENV`] as this process sees it.
+pub fn caller_env() -> Value {
+    CALLER_ENV
+        .iter()
+        .map(|name| {
+            let value = std::env::var_os(name).map(|v| v.to_string_lossy().into_owned());
+            (name.to_string(), json!(value))
+        })
+        .collect::<serde_json::Map<_, _>>()
+        .into()
+}
+
+/// Where the daemon for `repo` listens.
+pub fn socket_path(repo: &Repository) -> PathBuf {
+    repo.path().join("weft").join("serve.sock")
+}
+
+/// Connects to the daemon for the repository containing the current directory.
+pub fn discover() -> Option<Client> {
+    connect(&git::discover().ok()?)
+}
+
+/// Runs `method` on the daemon when one serves this caller, else checks jj
+/// and runs `local`.
+pub fn call_or<T: DeserializeOwned>(
+    method: &str,
+    params: Value,
+    local: impl FnOnce() -> Result<T>,
+) -> Result<T> {
+    match discover() {
+        Some(mut client) => client.call(method, params),
+        None => {
+            crate::check_jj()?;
+            local()
+        }
+    }
+}
+
+#[cfg(unix)]
+pub use unix::{connect, Client};
+
+#[cfg(not(unix))]
+pub use fallback::{connect, Client};
+
+#[cfg(unix)]
+mod unix {
+    use super::*;
+    use crate::config;
+    use std::io::{BufRead, BufReader, Write};
+    use std::os::unix::net::UnixStream;
+
+    pub struct Client {
+        stream: BufReader<UnixStream>,
+        next_id: u64,
+        /// Sent with every request so the daemon can refuse it.
+        caller: Value,
+    }
+
+    /// Connects to the daemon for `repo`, if one is running and serves the
+    /// same weft user and environment as this process.
+    pub fn connect(repo: &Repository) -> Option<Client> {
+        if std::env::var_os("WEFT_NO_DAEMON").is_some_and(|v| !v.is_empty()) {
+            return None;
+        }
+
+        let mut client = Client::open(repo)?;
+        client.call::<Value>("hello", json!({})).ok()?;
+        Some(client)
+    }
+
+    impl Client {
+        /// Connects to the daemon for `repo` without asking whether it serves
+        /// this caller.
+        pub fn open(repo: &Repository) -> Option<Client> {
+            let stream = UnixStream::connect(socket_path(repo)).ok()?;
+            Some(Client {
+                stream: BufReader::new(stream),
+                next_id: 0,
+                caller: json!({
+                    "user": config::get_user(repo).ok()?,
+                    "env": caller_env(),
+                }),
+            })
+        }
+
+        /// Sends one request and waits for its result.
+        pub fn call<T: DeserializeOwned>(&mut self, method: &str, mut params: Value) -> Result<T> {
+            if let Some(params) = params.as_object_mut() {
+                params.insert("caller".to_string(), self.caller.clone());
+            }
+            self.next_id += 1;
+            let request = json!({
+                "jsonrpc": "2.0",
+                "id": self.next_id,
+                "method": method,
+                "params": params,
+            });
+
+            let stream = self.stream.get_mut();
+            writeln!(stream, "{}", request)?;
+            stream.flush()?;
+
+            let mut line = String::new();
+            if self.stream.read_line(&mut line)? == 0 {
+                return Err(anyhow::anyhow!("weft serve closed the connection"));
+            }
+
+            let response: Value = serde_json::from_str(&line)?;
+            if let Some(error) = response.get("error") {
+                return Err(anyhow::anyhow!(
+                    "{}",
+                    error["message"].as_str().unwrap_or("weft serve failed")
+                ));
+            }
+            Ok(serde_json::from_value(response["result"].clone())?)
+        }
+    }
+}
+
+#[cfg(not(unix))]
+mod fallback {
+    use super::*;
+
+    /// `weft serve` needs Unix sockets, so there is never a daemon to use.
+    pub struct Client;
+
+    pub fn connect(_repo: &Repository) -> Option<Client> {
+        None
+    }
+
+    impl Client {
+        pub fn open(_repo: &Repository) -> Option<Client> {
+            None
+        }
+
+        pub fn call<T: DeserializeOwned>(&mut self, _method: &str, _params: Value) -> Result<T> {
+            Err(anyhow::anyhow!("weft serve is only available on Unix"))
+        }
+    }
+}
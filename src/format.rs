@@ -0,0 +1,41 @@
+use chrono::Utc;
+
+/// Renders a unix timestamp as "3 hours ago" style text.
+pub fn relative_time(timestamp: i64) -> String {
+    let seconds = (Utc::now().timestamp() - timestamp).max(0);
+
+    let (value, unit) = match seconds {
+        s if s < 60 => return "just now".to_string(),
+        s if s < 3600 => (s / 60, "minute"),
+        s if s < 86400 => (s / 3600, "hour"),
+        s if s < 86400 * 30 => (s / 86400, "day"),
+        s if s < 86400 * 365 => (s / (86400 * 30), "month"),
+        s => (s / (86400 * 365), "year"),
+    };
+
+    if value == 1 {
+        format!("1 {} ago", unit)
+    } else {
+        format!("{} {}s ago", value, unit)
+    }
+}
+
+/// Renders a byte count as "2.1 GB" style text.
+pub fn human_size(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["KB", "MB", "GB", "TB"];
+
+    if bytes < 1000 {
+        return format!("{} B", bytes);
+    }
+
+    let mut value = bytes as f64;
+    let mut unit = "B";
+    for next in UNITS {
+        if value < 1000.0 {
+            break;
+        }
+        value /= 1000.0;
+        unit = next;
+    }
+    format!("{:.1} {}", value, unit)
+}
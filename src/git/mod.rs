@@ -11,13 +11,49 @@ pub fn get_head(repo: &Repository) -> Result<Oid> {
     Ok(head)
 }
 
-pub fn update_weft_head(repo: &Repository, user: &str, oid: Oid, msg: &str) -> Result<()> {
-    let ref_name = format!("refs/weft/{}/head", user);
-    repo.reference(&ref_name, oid, true, msg)
+pub fn weft_ref(user: &str, weft: &str) -> String {
+    format!("refs/weft/{}/{}", user, weft)
+}
+
+pub fn get_weft_head(repo: &Repository, user: &str, weft: &str) -> Result<Option<Oid>> {
+    match repo.find_reference(&weft_ref(user, weft)) {
+        Ok(ref_) => Ok(Some(ref_.peel_to_commit()?.id())),
+        Err(_) => Ok(None),
+    }
+}
+
+pub fn update_weft_head(
+    repo: &Repository,
+    user: &str,
+    weft: &str,
+    oid: Oid,
+    msg: &str,
+) -> Result<()> {
+    repo.reference(&weft_ref(user, weft), oid, true, msg)
         .context("Failed to update weft head")?;
     Ok(())
 }
 
+/// Lists every weft owned by `user` as `(name, head)`, sorted by name.
+pub fn list_wefts(repo: &Repository, user: &str) -> Result<Vec<(String, Oid)>> {
+    let prefix = format!("refs/weft/{}/", user);
+    let mut wefts = Vec::new();
+
+    for ref_ in repo.references_glob(&format!("{}*", prefix))? {
+        let ref_ = ref_?;
+        let name = match ref_.name() {
+            Some(name) => name.trim_start_matches(&prefix).to_string(),
+            None => continue,
+        };
+        if let Ok(commit) = ref_.peel_to_commit() {
+            wefts.push((name, commit.id()));
+        }
+    }
+
+    wefts.sort();
+    Ok(wefts)
+}
+
 pub fn get_op_log(repo: &Repository) -> Result<Option<String>> {
     match repo.find_reference("refs/weft/op-log") {
         Ok(ref_) => {
@@ -43,9 +79,22 @@ pub fn update_op_log(repo: &Repository, new_entry: &str) -> Result<()> {
     Ok(())
 }
 
+/// The trunk branch's name: `main`, or `master` in repositories that use it.
+pub fn main_branch(repo: &Repository) -> &'static str {
+    let prefixes = ["refs/heads/", "refs/remotes/origin/"];
+    for prefix in &prefixes {
+        for name in ["main", "master"] {
+            if repo.find_reference(&format!("{}{}", prefix, name)).is_ok() {
+                return name;
+            }
+        }
+    }
+    "main"
+}
+
 pub fn get_origin_main(repo: &Repository) -> Result<Oid> {
-    let ref_name = "refs/remotes/origin/main";
-    let ref_ = repo.find_reference(ref_name)?;
+    let ref_name = format!("refs/remotes/origin/{}", main_branch(repo));
+    let ref_ = repo.find_reference(&ref_name)?;
     Ok(ref_.peel_to_commit()?.id())
 }
 
@@ -61,3 +110,110 @@ pub fn get_main(repo: &Repository) -> Result<Oid> {
     repo.reference("refs/heads/main", head, true, "create main branch")?;
     Ok(head)
 }
+
+/// The commit wefts are woven onto: `origin/main` when known, else local main.
+pub fn get_trunk(repo: &Repository) -> Result<Oid> {
+    match get_origin_main(repo) {
+        Ok(oid) => Ok(oid),
+        Err(_) => get_main(repo),
+    }
+}
+
+/// A teammate's weft as fetched into `refs/weft-team/` by `weft fetch`.
+pub struct TeamWeft {
+    pub user: String,
+    pub weft: String,
+    pub head: Oid,
+}
+
+pub fn list_team_wefts(repo: &Repository) -> Result<Vec<TeamWeft>> {
+    let mut wefts = Vec::new();
+
+    for ref_ in repo.references_glob("refs/weft-team/*")? {
+        let ref_ = ref_?;
+        let name = match ref_.name() {
+            Some(name) => name.trim_start_matches("refs/weft-team/").to_string(),
+            None => continue,
+        };
+        let head = match ref_.peel_to_commit() {
+            Ok(commit) => commit.id(),
+            Err(_) => continue,
+        };
+
+        // Wefts shared before named wefts existed live at refs/weft/<user>.
+        let (user, weft) = match name.split_once('/') {
+            Some((user, weft)) => (user.to_string(), weft.to_string()),
+            None => (name, crate::config::DEFAULT_WEFT.to_string()),
+        };
+
+        wefts.push(TeamWeft { user, weft, head });
+    }
+
+    wefts.sort_by(|a, b| (&a.user, &a.weft).cmp(&(&b.user, &b.weft)));
+    Ok(wefts)
+}
+
+/// Commits reachable from `tip` but not from `base`, newest first.
+pub fn commits_between(repo: &Repository, base: Oid, tip: Oid) -> Result<Vec<Oid>> {
+    let mut walk = repo.revwalk()?;
+    walk.push(tip)?;
+    walk.hide(base)?;
+
+    let mut commits = Vec::new();
+    for oid in walk {
+        commits.push(oid?);
+    }
+    Ok(commits)
+}
+
+/// Whether jj recorded `oid` as a conflicted (tangled) commit.
+///
+/// jj's git backend stores conflicted trees with `.jjconflict-*` entries at
+/// the root, so this works for commits jj itself has never seen, such as a
+/// teammate's fetched weft.
+pub fn is_tangled(repo: &Repository, oid: Oid) -> Result<bool> {
+    let tree = repo.find_commit(oid)?.tree()?;
+    let tangled = tree
+        .iter()
+        .any(|entry| entry.name().is_some_and(|n| n.starts_with(".jjconflict")));
+    Ok(tangled)
+}
+
+pub fn tangled_between(repo: &Repository, base: Oid, tip: Oid) -> Result<Vec<Oid>> {
+    let mut tangled = Vec::new();
+    for oid in commits_between(repo, base, tip)? {
+        if is_tangled(repo, oid)? {
+            tangled.push(oid);
+        }
+    }
+    Ok(tangled)
+}
+
+pub fn team_ref(user: &str, weft: &str) -> String {
+    format!("refs/weft-team/{}/{}", user, weft)
+}
+
+/// Trailer recording which teammate save a commit was adopted from.
+pub const ADOPTED_FROM: &str = "Adopted-from:";
+
+/// Parses an `Adopted-from: <user> <oid>` trailer out of a commit message.
+pub fn adopted_from(message: &str) -> Option<(String, Oid)> {
+    message.lines().rev().find_map(|line| {
+        let rest = line.strip_prefix(ADOPTED_FROM)?;
+        let mut parts = rest.split_whitespace();
+        let user = parts.next()?.to_string();
+        let oid = parts.next()?.parse::<Oid>().ok()?;
+        Some((user, oid))
+    })
+}
+
+/// Whether a `git push --porcelain` was refused only because a ref leased
+/// with `--force-with-lease` had moved on the remote, as opposed to the
+/// remote declining it (hooks, protected branches, permissions).
+pub fn lease_failed(porcelain: &str) -> bool {
+    porcelain.lines().any(|line| {
+        line.starts_with('!')
+            && line.contains("[rejected]")
+            && (line.ends_with("(stale info)") || line.ends_with("(fetch first)"))
+    })
+}
@@ -0,0 +1,180 @@
+use crate::git;
+use anyhow::{Context, Result};
+use chrono::Utc;
+use git2::{Oid, Repository};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// How much of a hook's output the op-log keeps.
+const OUTPUT_LINES: usize = 20;
+
+/// What a hook is told about the operation it runs for.
+///
+/// Hooks receive these as environment variables:
+/// `WEFT_HOOK`, `WEFT_COMMAND`, `WEFT_USER`, `WEFT_WEFT`, `WEFT_HEAD`,
+/// `WEFT_TRUNK` and `WEFT_CANDIDATE`. Unknown values are set to the empty string.
+pub struct HookEnv<'a> {
+    pub command: &'a str,
+    pub user: &'a str,
+    pub weft: &'a str,
+    pub head: Option<Oid>,
+    pub trunk: Option<Oid>,
+    pub candidate: Option<&'a str>,
+}
+
+/// Hook scripts for `name`, committed ones (`.weft/hooks/`) before local ones
+/// (`.git/weft/hooks/`).
+fn scripts(repo: &Repository, name: &str) -> Vec<PathBuf> {
+    let mut dirs = Vec::new();
+    if let Some(workdir) = repo.workdir() {
+        dirs.push(workdir.join(".weft").join("hooks"));
+    }
+    dirs.push(repo.path().join("weft").join("hooks"));
+
+    dirs.into_iter()
+        .map(|dir| dir.join(name))
+        .filter(|path| path.is_file())
+        .collect()
+}
+
+#[cfg(unix)]
+fn is_executable(path: &Path) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+    path.metadata()
+        .map(|m| m.permissions().mode() & 0o111 != 0)
+        .unwrap_or(false)
+}
+
+#[cfg(not(unix))]
+fn is_executable(_path: &Path) -> bool {
+    true
+}
+
+/// Runs the `pre-<command>` hooks. A hook that exits non-zero vetoes the
+/// operation, which is returned as an error carrying the hook's output.
+///
+/// Hooks' output is never printed here, since weft may be running under the
+/// TUI or a daemon; it comes back as reports for the caller to show.
+pub fn pre(repo: &Repository, name: &str, env: &HookEnv) -> Result<Vec<String>> {
+    let mut reports = Vec::new();
+    for script in scripts(repo, name) {
+        let (code, output) = match run_script(repo, name, &script, env)? {
+            Some(run) => run,
+            None => {
+                reports.push(not_executable(repo, name, &script));
+                continue;
+            }
+        };
+        if code != 0 {
+            let mut message = format!(
+                "The {} hook {} refused weft {} (exit {}).",
+                name,
+                display(repo, &script),
+                env.command,
+                code
+            );
+            if !output.is_empty() {
+                message.push_str(&format!("\n{}", output));
+            }
+            return Err(anyhow::anyhow!(message));
+        }
+        if !output.is_empty() {
+            reports.push(output);
+        }
+    }
+    Ok(reports)
+}
+
+/// Runs the `post-<command>` hooks. The operation already happened, so a
+/// failing hook is only reported, along with everything the hooks printed.
+pub fn post(repo: &Repository, name: &str, env: &HookEnv) -> Vec<String> {
+    let mut reports = Vec::new();
+    for script in scripts(repo, name) {
+        match run_script(repo, name, &script, env) {
+            Ok(Some((code, output))) => {
+                if !output.is_empty() {
+                    reports.push(output);
+                }
+                if code != 0 {
+                    reports.push(format!(
+                        "Warning: the {} hook {} failed (exit {}).",
+                        name,
+                        display(repo, &script),
+                        code
+                    ));
+                }
+            }
+            Ok(None) => reports.push(not_executable(repo, name, &script)),
+            Err(e) => reports.push(format!("Warning: {}", e.to_string().trim_end())),
+        }
+    }
+    reports
+}
+
+fn not_executable(repo: &Repository, name: &str, script: &Path) -> String {
+    format!(
+        "Warning: skipped the {} hook {}: it is not executable (chmod +x it).",
+        name,
+        display(repo, script)
+    )
+}
+
+/// Runs one hook and returns its exit code and output, or `None` when the
+/// script is not executable. Each run is recorded in the op-log with the end
+/// of its output.
+fn run_script(
+    repo: &Repository,
+    name: &str,
+    script: &Path,
+    env: &HookEnv,
+) -> Result<Option<(i32, String)>> {
+    if !is_executable(script) {
+        return Ok(None);
+    }
+
+    let oid = |oid: Option<Oid>| oid.map(|o| o.to_string()).unwrap_or_default();
+    let output = Command::new(script)
+        .current_dir(repo.workdir().unwrap_or(repo.path()))
+        .env("WEFT_HOOK", name)
+        .env("WEFT_COMMAND", env.command)
+        .env("WEFT_USER", env.user)
+        .env("WEFT_WEFT", env.weft)
+        .env("WEFT_HEAD", oid(env.head))
+        .env("WEFT_TRUNK", oid(env.trunk))
+        .env("WEFT_CANDIDATE", env.candidate.unwrap_or(""))
+        .output()
+        .with_context(|| format!("Failed to run the {} hook {}", name, script.display()))?;
+
+    let mut text = String::from_utf8_lossy(&output.stdout).into_owned();
+    text.push_str(&String::from_utf8_lossy(&output.stderr));
+    let text = text.trim_end().to_string();
+
+    let code = output.status.code().unwrap_or(-1);
+    let lines: Vec<&str> = text.lines().collect();
+    let entry = serde_json::json!({
+        "timestamp": Utc::now().timestamp(),
+        "command": "hook",
+        "args": {
+            "hook": name,
+            "path": display(repo, script),
+            "for": env.command,
+            "exit": code,
+            "output": lines[lines.len().saturating_sub(OUTPUT_LINES)..].join("\n"),
+        },
+        "inverse": {
+            "op": "none"
+        }
+    });
+    git::update_op_log(repo, &entry.to_string())?;
+
+    Ok(Some((code, text)))
+}
+
+fn display(repo: &Repository, script: &Path) -> String {
+    let root = repo.workdir().unwrap_or(repo.path());
+    script
+        .strip_prefix(root)
+        .unwrap_or(script)
+        .display()
+        .to_string()
+}
@@ -0,0 +1,244 @@
+use crate::sign;
+use anyhow::{Context, Result};
+use git2::{Oid, Repository};
+use std::fs;
+use std::process::Command;
+use std::sync::OnceLock;
+
+/// The installed jj version, from `jj --version`.
+///
+/// Asked once per process: several commands need it, and `weft serve` keeps
+/// it for as long as the daemon runs.
+pub fn version() -> Result<semver::Version> {
+    static VERSION: OnceLock<semver::Version> = OnceLock::new();
+    if let Some(version) = VERSION.get() {
+        return Ok(version.clone());
+    }
+
+    let output = Command::new("jj").arg("--version").output()?;
+
+    if !output.status.success() {
+        return Err(anyhow::anyhow!("Failed to run jj --version"));
+    }
+
+    let version_str = String::from_utf8_lossy(&output.stdout);
+    let version = version_str
+        .split_whitespace()
+        .nth(1)
+        .ok_or_else(|| anyhow::anyhow!("Cannot parse jj version"))?;
+
+    let version = semver::Version::parse(version)?;
+    Ok(VERSION.get_or_init(|| version).clone())
+}
+
+/// Extra arguments for jj commands that write commits, so they are signed
+/// whenever git would sign them.
+pub fn signing_args(repo: &Repository) -> Result<Vec<String>> {
+    match sign::signer(repo)? {
+        Some(signer) => Ok(signer.jj_args(&version()?)),
+        None => Ok(Vec::new()),
+    }
+}
+
+/// Returns the commit id of jj's working-copy commit (`@`).
+///
+/// Any jj invocation snapshots the working copy first, so this doubles as
+/// "snapshot and tell me where we are".
+pub fn current_commit(repo: &Repository) -> Result<Oid> {
+    let output = Command::new("jj")
+        .args([
+            "--no-pager",
+            "log",
+            "-r",
+            "@",
+            "-T",
+            "commit_id",
+            "--no-graph",
+        ])
+        .current_dir(repo.path())
+        .output()
+        .context("Failed to run jj")?;
+
+    if !output.status.success() {
+        return Err(anyhow::anyhow!(
+            "Failed to get commit id: {}",
+            String::from_utf8_lossy(&output.stderr)
+        ));
+    }
+
+    let commit_id = String::from_utf8_lossy(&output.stdout).trim().to_string();
+    Ok(commit_id.parse::<Oid>()?)
+}
+
+/// Makes `commit` the working-copy commit, restoring its files on disk.
+pub fn edit(repo: &Repository, commit: Oid) -> Result<()> {
+    run(repo, &["edit", &commit.to_string()])
+}
+
+/// Starts a new, empty working-copy commit on top of `parent`.
+pub fn new_on(repo: &Repository, parent: Oid) -> Result<()> {
+    run(repo, &["new", &parent.to_string()])
+}
+
+/// Like [`new_on`], for a commit created through git that jj has not imported.
+///
+/// jj only imports commits reachable from git refs, so the commit is exposed
+/// through a temporary branch until the new working copy keeps it alive.
+pub fn new_on_imported(repo: &Repository, parent: Oid) -> Result<()> {
+    let tmp_ref = "refs/heads/weft-import";
+    repo.reference(tmp_ref, parent, true, "weft: import commit into jj")?;
+
+    let result = new_on(repo, parent);
+
+    // Clean up even when `jj new` failed, but report its error first.
+    let deleted = repo
+        .find_reference(tmp_ref)
+        .and_then(|mut reference| reference.delete());
+    let imported = run(repo, &["git", "import"]);
+
+    result?;
+    deleted?;
+    imported
+}
+
+/// Runs jj's merge tool on the conflicts in `commit`, in the user's terminal.
+pub fn resolve(repo: &Repository, commit: Oid) -> Result<()> {
+    let status = Command::new("jj")
+        .args(signing_args(repo)?)
+        .args(["resolve", "-r", &commit.to_string()])
+        .current_dir(repo.path())
+        .status()
+        .context("Failed to run jj")?;
+
+    if !status.success() {
+        return Err(anyhow::anyhow!(
+            "jj resolve did not finish; the commit is still tangled"
+        ));
+    }
+    Ok(())
+}
+
+/// Returns jj's change id for `commit`, which survives the commit being
+/// rewritten, e.g. when jj snapshots edits into the working-copy commit.
+pub fn change_id(repo: &Repository, commit: Oid) -> Result<String> {
+    let output = Command::new("jj")
+        .args([
+            "--no-pager",
+            "log",
+            "-r",
+            &commit.to_string(),
+            "-T",
+            "change_id",
+            "--no-graph",
+        ])
+        .current_dir(repo.path())
+        .output()
+        .context("Failed to run jj")?;
+
+    if !output.status.success() {
+        return Err(anyhow::anyhow!(
+            "Failed to get change id: {}",
+            String::from_utf8_lossy(&output.stderr)
+        ));
+    }
+
+    Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+/// Sets up jj in an existing git repository, sharing its `.git`.
+pub fn colocate(repo: &Repository) -> Result<()> {
+    let workdir = repo
+        .workdir()
+        .ok_or_else(|| anyhow::anyhow!("Repository has no working copy"))?;
+
+    let output = Command::new("jj")
+        .args(["git", "init", "--colocate"])
+        .current_dir(workdir)
+        .output()
+        .context("Failed to run jj")?;
+
+    if !output.status.success() {
+        return Err(anyhow::anyhow!(
+            "jj git init failed: {}",
+            String::from_utf8_lossy(&output.stderr)
+        ));
+    }
+    Ok(())
+}
+
+pub fn abandon(repo: &Repository, commits: &[Oid]) -> Result<()> {
+    let mut args = vec!["abandon".to_string()];
+    args.extend(commits.iter().map(|c| c.to_string()));
+    let args: Vec<&str> = args.iter().map(|a| a.as_str()).collect();
+    run(repo, &args)
+}
+
+/// Stops tracking `paths` (relative to the repo root) while leaving them on
+/// disk. jj only untracks ignored files, so they are added to `.git/info/exclude`.
+pub fn skip_files(repo: &Repository, paths: &[String]) -> Result<()> {
+    let workdir = repo
+        .workdir()
+        .ok_or_else(|| anyhow::anyhow!("Repository has no working copy"))?;
+
+    let exclude = repo.path().join("info").join("exclude");
+    fs::create_dir_all(repo.path().join("info"))?;
+    let mut content = fs::read_to_string(&exclude).unwrap_or_default();
+    if !content.is_empty() && !content.ends_with('\n') {
+        content.push('\n');
+    }
+    for path in paths {
+        content.push_str(&format!("/{}\n", path));
+    }
+    fs::write(&exclude, content)?;
+
+    // `jj untrack` became `jj file untrack`, taking filesets, in jj 0.19.
+    let mut args: Vec<String> = if version()? >= semver::Version::new(0, 19, 0) {
+        let mut args = vec!["file".to_string(), "untrack".to_string()];
+        args.extend(paths.iter().map(|p| {
+            format!(
+                "root-file:\"{}\"",
+                p.replace('\\', "\\\\").replace('"', "\\\"")
+            )
+        }));
+        args
+    } else {
+        let mut args = vec!["untrack".to_string()];
+        args.extend(paths.iter().cloned());
+        args
+    };
+    args.splice(0..0, signing_args(repo)?);
+
+    let output = Command::new("jj")
+        .args(&args)
+        .current_dir(workdir)
+        .output()
+        .context("Failed to run jj")?;
+
+    if !output.status.success() {
+        return Err(anyhow::anyhow!(
+            "jj untrack failed: {}",
+            String::from_utf8_lossy(&output.stderr)
+        ));
+    }
+
+    Ok(())
+}
+
+fn run(repo: &Repository, args: &[&str]) -> Result<()> {
+    let output = Command::new("jj")
+        .args(signing_args(repo)?)
+        .args(args)
+        .current_dir(repo.path())
+        .output()
+        .context("Failed to run jj")?;
+
+    if !output.status.success() {
+        return Err(anyhow::anyhow!(
+            "jj {} failed: {}",
+            args.first().unwrap_or(&""),
+            String::from_utf8_lossy(&output.stderr)
+        ));
+    }
+
+    Ok(())
+}
@@ -0,0 +1,173 @@
+//! weft as a library.
+//!
+//! [`Weft`] is opened on a repository and exposes the same operations as the
+//! `weft` command line, returning typed outcomes instead of printing. The CLI
+//! in `main.rs` is a renderer over this API.
+//!
+//! ```no_run
+//! let weft = weft::Weft::open("path/to/repo")?;
+//! let saved = weft.save("checkpoint")?;
+//! let synced = weft.sync()?;
+//! println!("{} is {} tangled", saved.commit, synced.tangled.len());
+//! # Ok::<(), anyhow::Error>(())
+//! ```
+
+pub mod commands;
+mod config;
+pub mod daemon;
+pub mod error;
+mod format;
+mod git;
+mod hooks;
+mod jj;
+mod limits;
+mod loom;
+pub mod outcome;
+mod rpc;
+mod secrets;
+mod sign;
+mod verify;
+
+pub use commands::propose::{AskDetails, ProposeOptions};
+pub use commands::weave::WeaveOptions;
+pub use git2::Oid;
+pub use loom::{CheckResult, Strategy};
+pub use outcome::{
+    CommitSummary, FileChange, Proposal, ProposeOutcome, SaveOutcome, ShareOutcome, SkippedFile,
+    StatusReport, SyncOutcome, UndoOutcome, WeaveOutcome,
+};
+
+use anyhow::{Context, Result};
+use git2::Repository;
+use std::path::Path;
+
+/// A handle on one weft repository, acting as the configured user.
+pub struct Weft {
+    repo: Repository,
+    user: String,
+}
+
+impl Weft {
+    /// Opens the repository containing `path`.
+    pub fn open(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let repo = Repository::discover(path)
+            .with_context(|| format!("Not in a git repository: {}", path.display()))?;
+        Self::from_repo(repo)
+    }
+
+    /// Opens the repository containing the current directory.
+    pub fn discover() -> Result<Self> {
+        Self::from_repo(git::discover()?)
+    }
+
+    fn from_repo(repo: Repository) -> Result<Self> {
+        let user = config::get_user(&repo)?;
+        Ok(Weft { repo, user })
+    }
+
+    pub fn repo(&self) -> &Repository {
+        &self.repo
+    }
+
+    pub fn user(&self) -> &str {
+        &self.user
+    }
+
+    /// The weft that operations act on.
+    pub fn active_weft(&self) -> Result<String> {
+        config::get_active_weft(&self.repo, &self.user)
+    }
+
+    /// Whether `weft init` has created the active weft.
+    pub fn is_initialized(&self) -> Result<bool> {
+        let weft = self.active_weft()?;
+        Ok(git::get_weft_head(&self.repo, &self.user, &weft)?.is_some())
+    }
+
+    pub fn save(&self, message: &str) -> Result<SaveOutcome> {
+        let weft = self.active_weft()?;
+        commands::save::save_weft(&self.repo, &self.user, &weft, message)
+    }
+
+    pub fn sync(&self) -> Result<SyncOutcome> {
+        let weft = self.active_weft()?;
+        commands::sync::sync_weft(&self.repo, &self.user, &weft)
+    }
+
+    pub fn status(&self) -> Result<StatusReport> {
+        commands::status::status_report(&self.repo, &self.user)
+    }
+
+    pub fn undo(&self) -> Result<UndoOutcome> {
+        commands::undo::undo_last(&self.repo, &self.user)
+    }
+
+    pub fn share(&self) -> Result<ShareOutcome> {
+        commands::share::share_weft(&self.repo, &self.user)
+    }
+
+    pub fn propose(&self, options: ProposeOptions) -> Result<ProposeOutcome> {
+        commands::propose::propose_weft(&self.repo, &self.user, options)
+    }
+
+    pub fn weave(&self, candidate_id: &str, options: WeaveOptions) -> Result<WeaveOutcome> {
+        commands::weave::weave_candidate(&self.repo, candidate_id, options)
+    }
+
+    /// The weft's changes against trunk as a unified patch, including
+    /// work that has not been saved yet.
+    pub fn diff(&self) -> Result<String> {
+        commands::diff::weft_patch(&self.repo)
+    }
+
+    /// The latest `limit` commits on the active weft, newest first.
+    pub fn log(&self, limit: usize) -> Result<Vec<CommitSummary>> {
+        let weft = self.active_weft()?;
+        let head = git::get_weft_head(&self.repo, &self.user, &weft)?
+            .ok_or_else(|| anyhow::anyhow!("Weft not initialized. Run 'weft init' first."))?;
+
+        let mut walk = self.repo.revwalk()?;
+        walk.push(head)?;
+        walk.take(limit)
+            .map(|oid| commands::status::summarize(&self.repo, oid?))
+            .collect()
+    }
+
+    /// The op-log, oldest entry first.
+    pub fn oplog(&self) -> Result<Vec<serde_json::Value>> {
+        let content = git::get_op_log(&self.repo)?.unwrap_or_default();
+        content
+            .lines()
+            .filter(|line| !line.trim().is_empty())
+            .map(|line| {
+                serde_json::from_str(line).map_err(|_| anyhow::anyhow!("Failed to parse op-log"))
+            })
+            .collect()
+    }
+}
+
+/// Checks that a compatible jj is installed, since every operation needs it.
+pub fn check_jj() -> Result<()> {
+    if which::which("jj").is_err() {
+        return Err(anyhow::anyhow!(
+            "jj not found. WEFT requires jj (Jujutsu) to work.\n\n\
+             Install jj first:\n\
+               macOS: brew install jj\n\
+               Linux: curl -sSL https://github.com/martinvonz/jj/releases/download/v0.15.1/jj-v0.15.1-x86_64-unknown-linux-gnu.tar.gz | tar -xz && sudo mv jj /usr/local/bin/\n\
+               Cargo: cargo install jj\n"
+        ));
+    }
+
+    let version = jj::version()?;
+    let required = semver::VersionReq::parse(">=0.15.0")?;
+
+    if !required.matches(&version) {
+        return Err(anyhow::anyhow!(
+            "jj version {} is too old. Please install jj ≥ 0.15.0",
+            version
+        ));
+    }
+
+    Ok(())
+}
@@ -0,0 +1,219 @@
+use crate::config::LimitsConfig;
+use crate::format;
+use crate::git;
+use anyhow::Result;
+use git2::{Delta, DiffOptions, ObjectType, Oid, Repository};
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::Read;
+use std::path::Path;
+
+/// A file a commit adds or changes that breaks the size or binary limits.
+pub struct Violation {
+    pub path: String,
+    pub size: u64,
+    pub binary: bool,
+    /// Whether the file is new in the commit, rather than changed.
+    pub added: bool,
+    pub commit: Oid,
+}
+
+/// Files that `commit` adds or changes relative to its first parent and that
+/// break `limits`.
+pub fn check_commit(
+    repo: &Repository,
+    commit: Oid,
+    limits: &LimitsConfig,
+) -> Result<Vec<Violation>> {
+    let commit = repo.find_commit(commit)?;
+    let parent_tree = match commit.parent(0) {
+        Ok(parent) => Some(parent.tree()?),
+        Err(_) => None,
+    };
+    let diff = repo.diff_tree_to_tree(parent_tree.as_ref(), Some(&commit.tree()?), None)?;
+
+    let mut violations = Vec::new();
+    for delta in diff.deltas() {
+        if !matches!(delta.status(), Delta::Added | Delta::Modified) {
+            continue;
+        }
+        let file = delta.new_file();
+        let path = match file.path() {
+            Some(path) => path.display().to_string(),
+            None => continue,
+        };
+        if is_allowed(limits, &path) {
+            continue;
+        }
+
+        let object = repo.find_object(file.id(), Some(ObjectType::Blob))?;
+        let blob = match object.as_blob() {
+            Some(blob) => blob,
+            None => continue,
+        };
+
+        let violation = Violation {
+            path,
+            size: blob.size() as u64,
+            binary: blob.is_binary(),
+            added: delta.status() == Delta::Added,
+            commit: commit.id(),
+        };
+        if breaks(&violation, limits) {
+            violations.push(violation);
+        }
+    }
+
+    Ok(violations)
+}
+
+/// Files on disk that are new or changed since `base` and that break
+/// `limits`. Only reads the working tree, so nothing is written to the object
+/// store or jj's operation log before a file is judged. Such violations have
+/// a zero `commit`.
+pub fn check_workdir(
+    repo: &Repository,
+    base: Option<Oid>,
+    limits: &LimitsConfig,
+) -> Result<Vec<Violation>> {
+    let workdir = match repo.workdir() {
+        Some(workdir) => workdir,
+        None => return Ok(Vec::new()),
+    };
+    let base_tree = match base {
+        Some(oid) => Some(repo.find_commit(oid)?.tree()?),
+        None => None,
+    };
+    let mut options = DiffOptions::new();
+    options.include_untracked(true).recurse_untracked_dirs(true);
+    let diff = repo.diff_tree_to_workdir(base_tree.as_ref(), Some(&mut options))?;
+
+    let mut violations = Vec::new();
+    for delta in diff.deltas() {
+        if !matches!(
+            delta.status(),
+            Delta::Added | Delta::Modified | Delta::Untracked
+        ) {
+            continue;
+        }
+        let path = match delta.new_file().path() {
+            Some(path) => path,
+            None => continue,
+        };
+        let display = path.display().to_string();
+        if is_allowed(limits, &display) {
+            continue;
+        }
+
+        let full = workdir.join(path);
+        let metadata = match fs::symlink_metadata(&full) {
+            Ok(metadata) if metadata.is_file() => metadata,
+            _ => continue,
+        };
+
+        let violation = Violation {
+            path: display,
+            size: metadata.len(),
+            binary: looks_binary(&full)?,
+            added: delta.status() != Delta::Modified,
+            commit: Oid::zero(),
+        };
+        if breaks(&violation, limits) {
+            violations.push(violation);
+        }
+    }
+
+    Ok(violations)
+}
+
+fn breaks(violation: &Violation, limits: &LimitsConfig) -> bool {
+    violation.size > limits.max_file_size || (violation.binary && !limits.allow_binary)
+}
+
+/// Git's heuristic: a NUL byte in the first 8000 bytes makes a file binary.
+fn looks_binary(path: &Path) -> Result<bool> {
+    let mut head = Vec::with_capacity(8000);
+    fs::File::open(path)?.take(8000).read_to_end(&mut head)?;
+    Ok(head.contains(&0))
+}
+
+/// Every offending file across the commits in `base..tip`, listed once with
+/// the newest save that touched it.
+pub fn check_range(
+    repo: &Repository,
+    base: Oid,
+    tip: Oid,
+    limits: &LimitsConfig,
+) -> Result<Vec<Violation>> {
+    let mut by_path = BTreeMap::new();
+    for oid in git::commits_between(repo, base, tip)?.into_iter().rev() {
+        for violation in check_commit(repo, oid, limits)? {
+            by_path.insert(violation.path.clone(), violation);
+        }
+    }
+    Ok(by_path.into_values().collect())
+}
+
+fn is_allowed(limits: &LimitsConfig, path: &str) -> bool {
+    limits.allow.iter().any(|allowed| {
+        if allowed.ends_with('/') {
+            path.starts_with(allowed.as_str())
+        } else {
+            path == allowed
+        }
+    })
+}
+
+/// One line per file, e.g. `  build/app.tar (2.1 GB, binary)`.
+pub fn describe(violations: &[Violation], limits: &LimitsConfig) -> String {
+    violations
+        .iter()
+        .map(|v| format!("  {} ({})", v.path, reasons(v, limits)))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Like [`describe`], naming the newest save each file appears in.
+pub fn describe_saves(
+    repo: &Repository,
+    violations: &[Violation],
+    limits: &LimitsConfig,
+) -> Result<String> {
+    let mut lines = Vec::new();
+    for violation in violations {
+        let commit = repo.find_commit(violation.commit)?;
+        lines.push(format!(
+            "  {} ({}) in save {} {}",
+            violation.path,
+            reasons(violation, limits),
+            &violation.commit.to_string()[..8],
+            commit.summary().unwrap_or("")
+        ));
+    }
+    Ok(lines.join("\n"))
+}
+
+fn reasons(violation: &Violation, limits: &LimitsConfig) -> String {
+    let mut reasons = Vec::new();
+    if violation.size > limits.max_file_size {
+        reasons.push(format!(
+            "{}, over the {} limit",
+            format::human_size(violation.size),
+            format::human_size(limits.max_file_size)
+        ));
+    } else {
+        reasons.push(format::human_size(violation.size));
+    }
+    if violation.binary && !limits.allow_binary {
+        reasons.push("binary".to_string());
+    }
+    reasons.join(", ")
+}
+
+/// How to get rid of offending files for good, shown with every refusal.
+pub fn ignore_hint() -> String {
+    "To leave these files out, add them to .gitignore (or .git/info/exclude for this \
+     clone only).\nTo keep them in your saves anyway, list them under [limits] allow in \
+     .weft.toml."
+        .to_string()
+}
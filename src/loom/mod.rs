@@ -0,0 +1,587 @@
+use crate::git;
+use crate::sign;
+use anyhow::{Context, Result};
+use git2::{Oid, Repository};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::process::Command;
+
+pub mod queue;
+pub mod review;
+pub mod woven;
+
+/// A merge candidate created by `weft propose`.
+///
+/// Each re-propose adds a revision at `refs/loom/<id>/<rev>`, numbered from 1;
+/// `head` is the latest one. Candidates proposed before revisions existed live
+/// at `refs/loom/<id>` and count as a single revision.
+pub struct Candidate {
+    pub id: String,
+    pub head: Oid,
+    pub revisions: Vec<(u32, Oid)>,
+}
+
+impl Candidate {
+    /// Number of the newest revision, the one `head` points at.
+    pub fn latest_revision(&self) -> u32 {
+        self.revisions.last().map(|(rev, _)| *rev).unwrap_or(1)
+    }
+}
+
+/// Title and description attached to a candidate at `refs/loom-meta/<id>`.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct CandidateMeta {
+    pub title: String,
+    #[serde(default)]
+    pub description: String,
+    pub author: String,
+    pub created: i64,
+}
+
+/// Outcome of the pre-weave check command, kept at `refs/loom-checks/<id>`.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct CheckResult {
+    /// The tree that was checked: the candidate as it would land on main.
+    pub commit: String,
+    /// The candidate revision the check ran for.
+    #[serde(default)]
+    pub head: String,
+    pub command: String,
+    pub passed: bool,
+    pub ran_at: i64,
+    /// The end of the check's output. The full log stays in the clone that
+    /// ran it, under `.git/weft/checks/`.
+    #[serde(default)]
+    pub tail: String,
+}
+
+/// Everything `weft candidates` reports about one candidate.
+#[derive(Serialize)]
+pub struct CandidateInfo {
+    pub id: String,
+    pub title: Option<String>,
+    pub description: Option<String>,
+    pub author: String,
+    pub head: String,
+    pub revisions: usize,
+    pub created: i64,
+    pub commits: usize,
+    pub files_changed: usize,
+    pub insertions: usize,
+    pub deletions: usize,
+    pub tangled: bool,
+    pub behind_trunk: usize,
+    pub applies: bool,
+    /// No common ancestor with trunk, so nothing above it was worked out.
+    pub unrelated: bool,
+    pub check: Option<CheckResult>,
+}
+
+/// Fetches every candidate from origin, pruning ones woven or dropped there.
+pub fn fetch(repo: &Repository) -> Result<()> {
+    let output = Command::new("git")
+        .args([
+            "fetch",
+            "--prune",
+            "origin",
+            "+refs/loom/*:refs/loom/*",
+            "+refs/loom-meta/*:refs/loom-meta/*",
+            "+refs/loom-checks/*:refs/loom-checks/*",
+            "+refs/loom-queue/*:refs/loom-queue/*",
+            "+refs/loom-votes/*:refs/loom-votes/*",
+            "+refs/loom-woven/*:refs/loom-woven/*",
+            "+refs/heads/*:refs/remotes/origin/*",
+        ])
+        .current_dir(repo.path())
+        .output()
+        .context("Failed to run git fetch")?;
+
+    if !output.status.success() {
+        return Err(anyhow::anyhow!(
+            "Failed to fetch candidates: {}",
+            String::from_utf8_lossy(&output.stderr)
+        ));
+    }
+
+    Ok(())
+}
+
+pub fn revision_ref(candidate_id: &str, rev: u32) -> String {
+    format!("refs/loom/{}/{}", candidate_id, rev)
+}
+
+pub fn list(repo: &Repository) -> Result<Vec<Candidate>> {
+    let mut revisions: BTreeMap<String, Vec<(u32, Oid)>> = BTreeMap::new();
+
+    for ref_ in repo.references()? {
+        let ref_ = ref_?;
+        let rest = match ref_.name().and_then(|n| n.strip_prefix("refs/loom/")) {
+            Some(rest) => rest.to_string(),
+            None => continue,
+        };
+        let (id, rev) = match rest.split_once('/') {
+            Some((id, rev)) => match rev.parse::<u32>() {
+                Ok(rev) => (id.to_string(), rev),
+                Err(_) => continue,
+            },
+            None => (rest, 1),
+        };
+        if let Ok(commit) = ref_.peel_to_commit() {
+            revisions.entry(id).or_default().push((rev, commit.id()));
+        }
+    }
+
+    let candidates = revisions
+        .into_iter()
+        .map(|(id, mut revisions)| {
+            revisions.sort_by_key(|(rev, _)| *rev);
+            let head = revisions
+                .last()
+                .map(|(_, oid)| *oid)
+                .unwrap_or_else(Oid::zero);
+            Candidate {
+                id,
+                head,
+                revisions,
+            }
+        })
+        .collect();
+    Ok(candidates)
+}
+
+/// Looks up one candidate among the refs fetched from origin.
+pub fn find(repo: &Repository, candidate_id: &str) -> Result<Option<Candidate>> {
+    Ok(list(repo)?.into_iter().find(|c| c.id == candidate_id))
+}
+
+pub fn meta_ref(candidate_id: &str) -> String {
+    format!("refs/loom-meta/{}", candidate_id)
+}
+
+pub fn read_meta(repo: &Repository, candidate_id: &str) -> Result<Option<CandidateMeta>> {
+    let blob = match repo.find_reference(&meta_ref(candidate_id)) {
+        Ok(ref_) => ref_.peel_to_blob()?,
+        Err(_) => return Ok(None),
+    };
+
+    let meta = serde_json::from_slice(blob.content())
+        .with_context(|| format!("Failed to parse metadata for candidate '{}'", candidate_id))?;
+    Ok(Some(meta))
+}
+
+/// Stores `meta` as a blob for the candidate's meta ref and returns the blob.
+pub fn write_meta(repo: &Repository, meta: &CandidateMeta) -> Result<Oid> {
+    Ok(repo.blob(serde_json::to_string_pretty(meta)?.as_bytes())?)
+}
+
+/// Where the candidate a weft was last proposed as is recorded. It lives on
+/// origin, so re-proposing from any clone adds a revision to the same one.
+pub fn weft_candidate_ref(user: &str, weft: &str) -> String {
+    format!("refs/loom-meta/wefts/{}/{}", user, weft)
+}
+
+/// The id of the candidate `user`'s `weft` was last proposed as, if any.
+pub fn weft_candidate(repo: &Repository, user: &str, weft: &str) -> Result<Option<String>> {
+    let blob = match repo.find_reference(&weft_candidate_ref(user, weft)) {
+        Ok(ref_) => ref_.peel_to_blob()?,
+        Err(_) => return Ok(None),
+    };
+    let id = String::from_utf8_lossy(blob.content()).trim().to_string();
+    Ok(Some(id).filter(|id| !id.is_empty()))
+}
+
+pub fn checks_ref(candidate_id: &str) -> String {
+    format!("refs/loom-checks/{}", candidate_id)
+}
+
+/// Check results recorded for a candidate, oldest first.
+pub fn read_checks(repo: &Repository, candidate_id: &str) -> Result<Vec<CheckResult>> {
+    let blob = match repo.find_reference(&checks_ref(candidate_id)) {
+        Ok(ref_) => ref_.peel_to_blob()?,
+        Err(_) => return Ok(Vec::new()),
+    };
+
+    let checks = serde_json::from_slice(blob.content())
+        .with_context(|| format!("Failed to parse checks for candidate '{}'", candidate_id))?;
+    Ok(checks)
+}
+
+/// Appends `result` to the candidate's checks and returns the new blob.
+pub fn record_check(repo: &Repository, candidate_id: &str, result: CheckResult) -> Result<Oid> {
+    let mut checks = read_checks(repo, candidate_id)?;
+    checks.push(result);
+
+    let blob = repo.blob(serde_json::to_string_pretty(&checks)?.as_bytes())?;
+    repo.reference(&checks_ref(candidate_id), blob, true, "weft: record check")?;
+    Ok(blob)
+}
+
+/// Push refspecs deleting a candidate and everything stored alongside it.
+///
+/// Only refs that exist locally are listed, since deleting a missing ref
+/// would fail an atomic push; [`fetch`] prunes, so local mirrors origin.
+pub fn cleanup_refspecs(repo: &Repository, candidate_id: &str) -> Vec<String> {
+    let mut names = vec![
+        format!("refs/loom/{}", candidate_id),
+        meta_ref(candidate_id),
+        checks_ref(candidate_id),
+        review::votes_ref(candidate_id),
+    ];
+    if let Ok(Some(candidate)) = find(repo, candidate_id) {
+        names.extend(
+            candidate
+                .revisions
+                .iter()
+                .map(|(rev, _)| revision_ref(candidate_id, *rev)),
+        );
+    }
+
+    names
+        .into_iter()
+        .filter(|name| repo.find_reference(name).is_ok())
+        .map(|name| format!(":{}", name))
+        .collect()
+}
+
+/// The commit message a candidate is woven with.
+pub fn woven_message(meta: &CandidateMeta) -> String {
+    if meta.description.trim().is_empty() {
+        format!("{}\n", meta.title.trim())
+    } else {
+        format!("{}\n\n{}\n", meta.title.trim(), meta.description.trim())
+    }
+}
+
+/// Gives `commit` the candidate's title and description as its message.
+pub fn reword(repo: &Repository, commit: Oid, meta: &CandidateMeta) -> Result<Oid> {
+    let commit = repo.find_commit(commit)?;
+    let parents: Vec<git2::Commit> = commit.parents().collect();
+    let parents: Vec<&git2::Commit> = parents.iter().collect();
+    let reworded = sign::commit(
+        repo,
+        &commit.author(),
+        &repo.signature()?,
+        &woven_message(meta),
+        &commit.tree()?,
+        &parents,
+    )?;
+    Ok(reworded)
+}
+
+/// Result of replaying a candidate's commits onto a new base.
+pub enum Replay {
+    Done(Oid),
+    Conflict(Oid),
+}
+
+/// Replays the commits in `tip` that are missing from `onto` on top of `onto`,
+/// oldest first. Commits whose changes are already in `onto` are dropped.
+pub fn replay_onto(repo: &Repository, tip: Oid, onto: Oid) -> Result<Replay> {
+    let mut commits = git::commits_between(repo, onto, tip)?;
+    commits.reverse();
+
+    let signature = repo.signature()?;
+    let mut head = repo.find_commit(onto)?;
+
+    for oid in commits {
+        let commit = repo.find_commit(oid)?;
+        let mainline = if commit.parent_count() > 1 { 1 } else { 0 };
+        let mut index = repo.cherrypick_commit(&commit, &head, mainline, None)?;
+        if index.has_conflicts() {
+            return Ok(Replay::Conflict(oid));
+        }
+
+        let tree_id = index.write_tree_to(repo)?;
+        if tree_id == head.tree_id() {
+            continue;
+        }
+
+        let tree = repo.find_tree(tree_id)?;
+        let new_oid = sign::commit(
+            repo,
+            &commit.author(),
+            &signature,
+            commit.message().unwrap_or(""),
+            &tree,
+            &[&head],
+        )?;
+        head = repo.find_commit(new_oid)?;
+    }
+
+    Ok(Replay::Done(head.id()))
+}
+
+/// Returns the first weave rule that candidate `head` breaks against `trunk`.
+///
+/// Rules are checked on the commits themselves, so the answer does not depend
+/// on whether jj has ever seen the candidate.
+pub fn validate(repo: &Repository, head: Oid, trunk: Oid) -> Result<Option<String>> {
+    if repo.merge_base(head, trunk).is_err() {
+        return Ok(Some(
+            "rule 'shares history with main' failed: the candidate has no common \
+             ancestor with main"
+                .to_string(),
+        ));
+    }
+
+    let tangled = git::tangled_between(repo, trunk, head)?;
+    if !tangled.is_empty() {
+        let mut message = format!(
+            "rule 'no tangled commits' failed: {} tangled commits in the candidate:",
+            tangled.len()
+        );
+        for oid in &tangled {
+            let commit = repo.find_commit(*oid)?;
+            message.push_str(&format!(
+                "\n  - {} {}",
+                &oid.to_string()[..8],
+                commit.summary().unwrap_or("")
+            ));
+        }
+        message.push_str("\nResolve them with 'weft untangle' and re-propose.");
+        return Ok(Some(message));
+    }
+
+    if head != trunk && !repo.graph_descendant_of(head, trunk)? {
+        if let Replay::Conflict(oid) = replay_onto(repo, head, trunk)? {
+            let commit = repo.find_commit(oid)?;
+            return Ok(Some(format!(
+                "rule 'based on main' failed: the candidate is not based on current main \
+                 and {} ({}) does not rebase cleanly onto it. Run 'weft sync' and re-propose.",
+                &oid.to_string()[..8],
+                commit.summary().unwrap_or("")
+            )));
+        }
+    }
+
+    Ok(None)
+}
+
+/// Returns the weave rule about signatures that candidate `head` breaks, if any:
+/// every commit it adds on top of `trunk` must be signed by a trusted key.
+pub fn validate_signatures(repo: &Repository, head: Oid, trunk: Oid) -> Result<Option<String>> {
+    let mut failures = Vec::new();
+    for oid in git::commits_between(repo, trunk, head)? {
+        if let Some(reason) = sign::untrusted(repo, oid)? {
+            failures.push((oid, reason));
+        }
+    }
+
+    if failures.is_empty() {
+        return Ok(None);
+    }
+
+    let mut message = format!(
+        "rule 'signed by a trusted key' failed for {} commits:",
+        failures.len()
+    );
+    for (oid, reason) in failures.iter().rev() {
+        let commit = repo.find_commit(*oid)?;
+        message.push_str(&format!(
+            "\n  - {} {} ({})",
+            &oid.to_string()[..8],
+            commit.summary().unwrap_or(""),
+            reason
+        ));
+    }
+    message.push_str("\nSet commit.gpgsign and user.signingkey, then save and re-propose.");
+    Ok(Some(message))
+}
+
+/// How a candidate's commits end up on main.
+#[derive(Deserialize, Serialize, Clone, Copy, PartialEq, Eq, Debug, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum Strategy {
+    /// Move main to the candidate; main must not have moved since it was proposed.
+    Ff,
+    /// Replay the candidate's commits onto current main.
+    Rebase,
+    /// One commit on main carrying the candidate's title and description.
+    Squash,
+    /// A two-parent merge commit joining main and the candidate.
+    Merge,
+}
+
+impl std::fmt::Display for Strategy {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            Strategy::Ff => "ff",
+            Strategy::Rebase => "rebase",
+            Strategy::Squash => "squash",
+            Strategy::Merge => "merge",
+        };
+        f.write_str(name)
+    }
+}
+
+/// Result of building the commit main will point at after a weave.
+pub enum Woven {
+    Ready(Oid),
+    Refused(String),
+}
+
+/// Builds the commit that `strategy` would put on main for `head` woven onto `main`.
+pub fn weave_commit(
+    repo: &Repository,
+    strategy: Strategy,
+    candidate_id: &str,
+    head: Oid,
+    main: Oid,
+) -> Result<Woven> {
+    let meta = read_meta(repo, candidate_id)?;
+
+    let replayed = match strategy {
+        Strategy::Ff => {
+            if head != main && !repo.graph_descendant_of(head, main)? {
+                return Ok(Woven::Refused(
+                    "main has moved since it was proposed, so it cannot be \
+                     fast-forwarded. Run 'weft sync' and re-propose, or weave with \
+                     --strategy rebase."
+                        .to_string(),
+                ));
+            }
+            head
+        }
+        Strategy::Rebase | Strategy::Squash => match replay_onto(repo, head, main)? {
+            Replay::Done(oid) => oid,
+            Replay::Conflict(oid) => {
+                let summary = repo.find_commit(oid)?.summary().unwrap_or("").to_string();
+                return Ok(Woven::Refused(format!(
+                    "it conflicts with main at {} ({})",
+                    &oid.to_string()[..8],
+                    summary
+                )));
+            }
+        },
+        Strategy::Merge => {
+            let main_commit = repo.find_commit(main)?;
+            let head_commit = repo.find_commit(head)?;
+            let mut index = repo.merge_commits(&main_commit, &head_commit, None)?;
+            if index.has_conflicts() {
+                return Ok(Woven::Refused("it conflicts with main".to_string()));
+            }
+            let tree = repo.find_tree(index.write_tree_to(repo)?)?;
+            let message = match &meta {
+                Some(meta) => woven_message(meta),
+                None => format!("Weave candidate '{}'\n", candidate_id),
+            };
+            let merge = sign::commit(
+                repo,
+                &head_commit.author(),
+                &repo.signature()?,
+                &message,
+                &tree,
+                &[&main_commit, &head_commit],
+            )?;
+            return Ok(Woven::Ready(merge));
+        }
+    };
+
+    if replayed == main {
+        return Ok(Woven::Refused(
+            "it has no changes relative to main".to_string(),
+        ));
+    }
+
+    if strategy == Strategy::Squash {
+        let tip = repo.find_commit(replayed)?;
+        let message = match &meta {
+            Some(meta) => woven_message(meta),
+            None => squash_message(repo, candidate_id, main, replayed)?,
+        };
+        let squashed = sign::commit(
+            repo,
+            &repo.find_commit(head)?.author(),
+            &repo.signature()?,
+            &message,
+            &tip.tree()?,
+            &[&repo.find_commit(main)?],
+        )?;
+        return Ok(Woven::Ready(squashed));
+    }
+
+    match &meta {
+        Some(meta) => Ok(Woven::Ready(reword(repo, replayed, meta)?)),
+        None => Ok(Woven::Ready(replayed)),
+    }
+}
+
+/// Default squash message: the candidate id and the saves it folds together.
+fn squash_message(repo: &Repository, candidate_id: &str, main: Oid, tip: Oid) -> Result<String> {
+    let mut message = format!("Weave candidate '{}'\n\n", candidate_id);
+    let mut commits = git::commits_between(repo, main, tip)?;
+    commits.reverse();
+    for oid in commits {
+        let commit = repo.find_commit(oid)?;
+        message.push_str(&format!("- {}\n", commit.summary().unwrap_or("")));
+    }
+    Ok(message)
+}
+
+/// Who proposed the candidate, as recorded in its metadata. Candidates
+/// proposed without metadata fall back to the id, `<user>-<short hash>`
+/// (user names may contain dashes).
+pub fn author(repo: &Repository, candidate_id: &str) -> Result<String> {
+    if let Some(meta) = read_meta(repo, candidate_id)? {
+        return Ok(meta.author);
+    }
+    Ok(match candidate_id.rsplit_once('-') {
+        Some((author, _)) => author,
+        None => candidate_id,
+    }
+    .to_string())
+}
+
+pub fn inspect(repo: &Repository, candidate: &Candidate, trunk: Oid) -> Result<CandidateInfo> {
+    let commit = repo.find_commit(candidate.head)?;
+    let meta = read_meta(repo, &candidate.id)?;
+
+    let mut info = CandidateInfo {
+        id: candidate.id.clone(),
+        title: meta.as_ref().map(|m| m.title.clone()),
+        description: meta.as_ref().map(|m| m.description.clone()),
+        author: author(repo, &candidate.id)?,
+        head: candidate.head.to_string(),
+        revisions: candidate.revisions.len(),
+        created: meta
+            .as_ref()
+            .map(|m| m.created)
+            .unwrap_or_else(|| commit.time().seconds()),
+        commits: 0,
+        files_changed: 0,
+        insertions: 0,
+        deletions: 0,
+        tangled: false,
+        behind_trunk: 0,
+        applies: false,
+        unrelated: true,
+        check: read_checks(repo, &candidate.id)?
+            .into_iter()
+            .rev()
+            .find(|check| check.head == candidate.head.to_string()),
+    };
+
+    // Without history in common with trunk there is nothing to compare; the
+    // candidate is still listed so one stray ref cannot hide the others.
+    let base = match repo.merge_base(trunk, candidate.head) {
+        Ok(base) => base,
+        Err(_) => return Ok(info),
+    };
+
+    let base_tree = repo.find_commit(base)?.tree()?;
+    let stats = repo
+        .diff_tree_to_tree(Some(&base_tree), Some(&commit.tree()?), None)?
+        .stats()?;
+    let (_, behind_trunk) = repo.graph_ahead_behind(candidate.head, trunk)?;
+    let merged = repo.merge_commits(&repo.find_commit(trunk)?, &commit, None)?;
+
+    info.commits = git::commits_between(repo, trunk, candidate.head)?.len();
+    info.files_changed = stats.files_changed();
+    info.insertions = stats.insertions();
+    info.deletions = stats.deletions();
+    info.tangled = !git::tangled_between(repo, trunk, candidate.head)?.is_empty();
+    info.behind_trunk = behind_trunk;
+    info.applies = !merged.has_conflicts();
+    info.unrelated = false;
+    Ok(info)
+}
@@ -0,0 +1,94 @@
+use anyhow::{Context, Result};
+use git2::{Oid, Repository};
+use serde::{Deserialize, Serialize};
+use std::process::Command;
+
+/// The merge queue lives in a single blob ref so any clone can run it.
+pub const QUEUE_REF: &str = "refs/loom-queue/state";
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct QueueEntry {
+    pub candidate: String,
+    pub added_by: String,
+    pub added_at: i64,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct Ejected {
+    pub candidate: String,
+    pub reason: String,
+    pub ejected_at: i64,
+}
+
+#[derive(Serialize, Deserialize, Default, Clone)]
+pub struct QueueState {
+    #[serde(default)]
+    pub entries: Vec<QueueEntry>,
+    #[serde(default)]
+    pub ejected: Vec<Ejected>,
+}
+
+/// Reads the local copy of the queue along with the blob it came from, which
+/// is the lease [`publish`] expects the remote to still hold.
+pub fn read(repo: &Repository) -> Result<(QueueState, Option<Oid>)> {
+    let ref_ = match repo.find_reference(QUEUE_REF) {
+        Ok(ref_) => ref_,
+        Err(_) => return Ok((QueueState::default(), None)),
+    };
+
+    let blob = ref_.peel_to_blob()?;
+    let state = serde_json::from_slice(blob.content()).context("Failed to parse merge queue")?;
+    Ok((state, Some(blob.id())))
+}
+
+/// Writes `state` as a blob and returns it with the `git push` arguments that
+/// publish it: a lease on the queue ref and the refspec. Weaves add these to
+/// their atomic push so main and the queue move together.
+pub fn stage(
+    repo: &Repository,
+    state: &QueueState,
+    lease: Option<Oid>,
+) -> Result<(Oid, [String; 2])> {
+    let blob = repo.blob(serde_json::to_string_pretty(state)?.as_bytes())?;
+    let lease = lease.map(|oid| oid.to_string()).unwrap_or_default();
+    Ok((
+        blob,
+        [
+            format!("--force-with-lease={}:{}", QUEUE_REF, lease),
+            format!("{}:{}", blob, QUEUE_REF),
+        ],
+    ))
+}
+
+/// Points the local queue at `blob` once the push that carried it went through.
+pub fn recorded(repo: &Repository, blob: Oid) -> Result<()> {
+    repo.reference(QUEUE_REF, blob, true, "weft: update merge queue")?;
+    Ok(())
+}
+
+/// Pushes `state` to origin, failing if someone else changed the queue since
+/// `lease` was read. `extra` refspecs go in the same atomic push.
+pub fn publish(
+    repo: &Repository,
+    state: &QueueState,
+    lease: Option<Oid>,
+    extra: &[String],
+) -> Result<()> {
+    let (blob, [lease, refspec]) = stage(repo, state, lease)?;
+
+    let output = Command::new("git")
+        .args(["push", "--atomic", &lease, "origin", &refspec])
+        .args(extra)
+        .current_dir(repo.path())
+        .output()
+        .context("Failed to push merge queue")?;
+
+    if !output.status.success() {
+        return Err(anyhow::anyhow!(
+            "Failed to update merge queue on origin (someone else may have changed it; try again): {}",
+            String::from_utf8_lossy(&output.stderr)
+        ));
+    }
+
+    recorded(repo, blob)
+}
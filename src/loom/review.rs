@@ -0,0 +1,135 @@
+use crate::config::ReviewConfig;
+use crate::loom::{self, Candidate};
+use anyhow::{Context, Result};
+use git2::{Oid, Repository};
+use serde::{Deserialize, Serialize};
+use std::process::Command;
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum Verdict {
+    Approve,
+    Reject,
+}
+
+/// One reviewer's vote on one revision of a candidate.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct Vote {
+    pub voter: String,
+    /// `Name <email>` of the reviewer, as in a Signed-off-by trailer.
+    pub signoff: String,
+    pub verdict: Verdict,
+    #[serde(default)]
+    pub reason: Option<String>,
+    pub revision: u32,
+    pub commit: String,
+    pub at: i64,
+}
+
+pub fn votes_ref(candidate_id: &str) -> String {
+    format!("refs/loom-votes/{}", candidate_id)
+}
+
+/// Reads every vote on a candidate, oldest first, along with the blob they
+/// came from, which is the lease [`publish`] expects origin to still hold.
+pub fn read(repo: &Repository, candidate_id: &str) -> Result<(Vec<Vote>, Option<Oid>)> {
+    let ref_ = match repo.find_reference(&votes_ref(candidate_id)) {
+        Ok(ref_) => ref_,
+        Err(_) => return Ok((Vec::new(), None)),
+    };
+
+    let blob = ref_.peel_to_blob()?;
+    let votes = serde_json::from_slice(blob.content())
+        .with_context(|| format!("Failed to parse votes for candidate '{}'", candidate_id))?;
+    Ok((votes, Some(blob.id())))
+}
+
+/// Pushes `votes` to origin, failing if someone else voted since `lease` was read.
+pub fn publish(
+    repo: &Repository,
+    candidate_id: &str,
+    votes: &[Vote],
+    lease: Option<Oid>,
+) -> Result<()> {
+    let name = votes_ref(candidate_id);
+    let blob = repo.blob(serde_json::to_string_pretty(votes)?.as_bytes())?;
+    let lease = lease.map(|oid| oid.to_string()).unwrap_or_default();
+
+    let output = Command::new("git")
+        .args([
+            "push",
+            &format!("--force-with-lease={}:{}", name, lease),
+            "origin",
+            &format!("{}:{}", blob, name),
+        ])
+        .current_dir(repo.path())
+        .output()
+        .context("Failed to push vote")?;
+
+    if !output.status.success() {
+        return Err(anyhow::anyhow!(
+            "Failed to record vote on origin (someone else may have voted at the same time; try again): {}",
+            String::from_utf8_lossy(&output.stderr)
+        ));
+    }
+
+    repo.reference(&name, blob, true, "weft: record vote")?;
+    Ok(())
+}
+
+/// Votes cast on the candidate's latest revision, one per voter (their last).
+///
+/// Votes on earlier revisions no longer count once the candidate is re-proposed.
+pub fn current_votes(candidate: &Candidate, votes: &[Vote]) -> Vec<Vote> {
+    let head = candidate.head.to_string();
+    let mut current: Vec<Vote> = Vec::new();
+    for vote in votes.iter().filter(|v| v.commit == head) {
+        current.retain(|v| v.voter != vote.voter);
+        current.push(vote.clone());
+    }
+    current
+}
+
+/// Returns why `candidate` may not be woven yet under the review rules, if anything.
+///
+/// Votes are only as trustworthy as the names voters give: `voter` is the
+/// weft user, which anyone can set, so the rules keep honest teams honest
+/// and are not an access control.
+pub fn violation(
+    repo: &Repository,
+    candidate: &Candidate,
+    rules: &ReviewConfig,
+) -> Result<Option<String>> {
+    let (votes, _) = read(repo, &candidate.id)?;
+    let votes = current_votes(candidate, &votes);
+    let author = loom::author(repo, &candidate.id)?;
+
+    if let Some(reject) = votes.iter().find(|v| v.verdict == Verdict::Reject) {
+        return Ok(Some(format!(
+            "rule 'no rejections' failed: rejected by {}: {}",
+            reject.voter,
+            reject.reason.as_deref().unwrap_or("no reason given")
+        )));
+    }
+
+    let approvals = votes
+        .iter()
+        .filter(|v| v.verdict == Verdict::Approve)
+        .filter(|v| rules.allow_author || v.voter != author)
+        .count();
+
+    if approvals < rules.approvals {
+        let from = if rules.allow_author {
+            ""
+        } else {
+            " from someone other than the author"
+        };
+        return Ok(Some(format!(
+            "rule '{} approvals' failed: the latest revision has {} approvals{}. \
+             Ask a reviewer to run 'weft approve {}'.",
+            rules.approvals, approvals, from, candidate.id
+        )));
+    }
+
+    Ok(None)
+}
@@ -0,0 +1,61 @@
+use crate::loom::Strategy;
+use anyhow::Result;
+use git2::{Oid, Repository};
+use serde::{Deserialize, Serialize};
+
+/// Candidates leave no refs behind once woven, so each weave also appends
+/// a record to this JSON-lines blob, pushed in the same atomic push.
+pub const WOVEN_REF: &str = "refs/loom-woven/log";
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct WovenRecord {
+    pub candidate: String,
+    pub title: Option<String>,
+    pub author: String,
+    pub revision: u32,
+    pub strategy: Strategy,
+    /// The commit main was moved to.
+    pub commit: String,
+    /// Who ran the weave.
+    pub by: String,
+    pub at: i64,
+}
+
+/// Every woven candidate known locally, oldest first.
+pub fn read(repo: &Repository) -> Result<Vec<WovenRecord>> {
+    let blob = match repo.find_reference(WOVEN_REF) {
+        Ok(ref_) => ref_.peel_to_blob()?,
+        Err(_) => return Ok(Vec::new()),
+    };
+
+    Ok(String::from_utf8_lossy(blob.content())
+        .lines()
+        .filter_map(|line| serde_json::from_str(line).ok())
+        .collect())
+}
+
+/// Writes the log with `record` appended and returns the new blob along with
+/// the refspec that publishes it.
+///
+/// The refspec forces: it only goes out in an atomic push leased on main, and
+/// every weave moves main, so a lost update would fail the lease first.
+pub fn append(repo: &Repository, record: &WovenRecord) -> Result<(Oid, String)> {
+    let mut content = match repo.find_reference(WOVEN_REF) {
+        Ok(ref_) => String::from_utf8_lossy(ref_.peel_to_blob()?.content()).into_owned(),
+        Err(_) => String::new(),
+    };
+    if !content.is_empty() && !content.ends_with('\n') {
+        content.push('\n');
+    }
+    content.push_str(&serde_json::to_string(record)?);
+    content.push('\n');
+
+    let blob = repo.blob(content.as_bytes())?;
+    Ok((blob, format!("+{}:{}", blob, WOVEN_REF)))
+}
+
+/// Points the local log at `blob` once the push that carried it went through.
+pub fn recorded(repo: &Repository, blob: Oid) -> Result<()> {
+    repo.reference(WOVEN_REF, blob, true, "weft: record woven candidate")?;
+    Ok(())
+}
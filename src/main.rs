@@ -1,11 +1,10 @@
 use anyhow::Result;
-use clap::{Parser, Subcommand};
-use std::process::Command;
-
-mod commands;
-mod config;
-mod error;
-mod git;
+use clap::{CommandFactory, Parser, Subcommand};
+use clap_complete::engine::ArgValueCandidates;
+use clap_complete::CompleteEnv;
+use std::path::PathBuf;
+use weft::commands;
+use weft::commands::completions::{self, candidate_ids, own_wefts, team_saves, team_wefts};
 
 #[derive(Parser)]
 #[command(name = "weft")]
@@ -25,72 +24,241 @@ enum Commands {
     Status,
     #[command(about = "Undo the last operation")]
     Undo,
+    #[command(about = "Resolve a tangled commit with jj (default: the oldest one)")]
+    Untangle {
+        #[arg(help = "Tangled commit to resolve")]
+        commit: Option<String>,
+    },
     #[command(about = "Initialize weft in an existing git repo")]
     Init,
     #[command(about = "Push your weft to remote namespace")]
     Share,
     #[command(about = "Create a merge candidate for integration")]
-    Propose,
+    Propose {
+        #[arg(
+            long,
+            help = "Short title shown to reviewers and used for the woven commit"
+        )]
+        title: Option<String>,
+        #[arg(long, help = "Longer description of the candidate", requires = "title")]
+        description: Option<String>,
+        #[arg(long, help = "Sync your weft onto trunk before proposing")]
+        sync: bool,
+    },
     #[command(about = "Weave a candidate into main")]
-    Weave { candidate_id: String },
+    Weave {
+        #[arg(add = ArgValueCandidates::new(candidate_ids))]
+        candidate_id: String,
+        #[arg(
+            long,
+            value_enum,
+            help = "How the candidate lands on main (default: [weave] strategy, else ff)"
+        )]
+        strategy: Option<weft::Strategy>,
+        #[arg(
+            long,
+            help = "Refuse candidates with commits not signed by a trusted key"
+        )]
+        require_signed: bool,
+    },
+    #[command(about = "Start a new named weft from trunk and switch to it")]
+    New { name: String },
+    #[command(about = "Switch to another of your wefts (snapshots the current one)")]
+    Switch {
+        #[arg(add = ArgValueCandidates::new(own_wefts))]
+        name: String,
+    },
+    #[command(about = "List your wefts")]
+    List,
+    #[command(about = "Fetch teammates' shared wefts from the remote")]
+    Fetch,
+    #[command(about = "Show teammates' shared wefts")]
+    Team,
+    #[command(about = "Copy a teammate's shared saves onto your weft")]
+    Adopt {
+        #[arg(
+            help = "Teammate, or <teammate>/<weft> for a named weft",
+            add = ArgValueCandidates::new(team_wefts)
+        )]
+        user: String,
+        #[arg(
+            help = "Saves to adopt (default: all of them)",
+            add = ArgValueCandidates::new(team_saves)
+        )]
+        saves: Vec<String>,
+    },
+    #[command(about = "List open merge candidates")]
+    Candidates {
+        #[arg(long, help = "Only show your own candidates")]
+        mine: bool,
+        #[arg(long, help = "Print candidates as JSON")]
+        json: bool,
+        #[arg(long, help = "Only show candidates that trunk has moved past")]
+        stale: bool,
+    },
+    #[command(about = "Show what changed between revisions of a candidate")]
+    Diff {
+        #[arg(
+            long,
+            help = "Candidate to compare revisions of",
+            add = ArgValueCandidates::new(candidate_ids)
+        )]
+        candidate: String,
+        #[arg(
+            long,
+            help = "Revisions to compare, as FROM..TO (default: the last two)"
+        )]
+        revs: Option<String>,
+    },
+    #[command(about = "Show a candidate's diff against trunk and its votes")]
+    Review {
+        #[arg(add = ArgValueCandidates::new(candidate_ids))]
+        candidate_id: String,
+    },
+    #[command(about = "Approve the latest revision of a candidate")]
+    Approve {
+        #[arg(add = ArgValueCandidates::new(candidate_ids))]
+        candidate_id: String,
+    },
+    #[command(about = "Reject the latest revision of a candidate")]
+    Reject {
+        #[arg(add = ArgValueCandidates::new(candidate_ids))]
+        candidate_id: String,
+        #[arg(long, help = "Why the candidate should not be woven")]
+        reason: String,
+    },
+    #[command(about = "Serve a read-only dashboard of wefts and candidates on localhost")]
+    Web {
+        #[arg(long, default_value_t = 8080, help = "Port to listen on")]
+        port: u16,
+    },
+    #[command(about = "Full-screen view of your weft, team and candidates")]
+    Tui,
+    #[command(about = "Serve weft to AI agents over the Model Context Protocol (stdio)")]
+    Mcp {
+        #[arg(
+            long,
+            help = "Agent name for the op-log (default: the client's name from initialize)"
+        )]
+        agent: Option<String>,
+    },
+    #[command(about = "Keep a daemon running that weft commands in this repo go through")]
+    Serve {
+        #[arg(long, help = "Stop the running daemon")]
+        stop: bool,
+    },
+    #[command(about = "Check jj, refs, the op-log, trunk, the remote and locks")]
+    Doctor {
+        #[arg(long, help = "Repair what can be repaired without losing anything")]
+        fix: bool,
+        #[arg(
+            long,
+            help = "Push probe refs to origin and delete them, to check its hooks and permissions"
+        )]
+        probe: bool,
+    },
+    #[command(about = "Print a shell completion script")]
+    Completions {
+        #[arg(value_enum)]
+        shell: completions::Shell,
+    },
+    #[command(about = "Generate man pages")]
+    Man {
+        #[arg(long, help = "Write a page per command into this directory")]
+        out: Option<PathBuf>,
+    },
+    #[command(about = "Serialize weaves through a shared merge queue")]
+    Queue {
+        #[command(subcommand)]
+        action: QueueAction,
+    },
+}
+
+#[derive(Subcommand)]
+enum QueueAction {
+    #[command(about = "Add a candidate to the end of the queue")]
+    Add {
+        #[arg(add = ArgValueCandidates::new(candidate_ids))]
+        candidate_id: String,
+    },
+    #[command(about = "Weave queued candidates one at a time, ejecting failures")]
+    Run {
+        #[arg(long, help = "Run the [weave] check on each candidate before weaving")]
+        verify: bool,
+    },
+    #[command(about = "Show queued and recently ejected candidates")]
+    List,
 }
 
 fn main() -> Result<()> {
+    // Answers the completion scripts from `weft completions` and exits.
+    CompleteEnv::with_factory(Cli::command)
+        .var(completions::COMPLETE_VAR)
+        .complete();
+
     let cli = Cli::parse();
 
-    check_jj_prerequisite()?;
-    check_jj_version()?;
+    // Commands that go through a running daemon check jj only when they fall
+    // back to running in-process; completions and man pages don't need jj.
+    let needs_jj = !matches!(
+        cli.command,
+        Commands::Save { .. }
+            | Commands::Sync
+            | Commands::Status
+            | Commands::Undo
+            | Commands::Share
+            | Commands::Weave { .. }
+            | Commands::Completions { .. }
+            | Commands::Man { .. }
+    );
+    if needs_jj {
+        weft::check_jj()?;
+    }
 
     match cli.command {
         Commands::Save { message } => commands::save::run(&message),
         Commands::Sync => commands::sync::run(),
         Commands::Status => commands::status::run(),
         Commands::Undo => commands::undo::run(),
+        Commands::Untangle { commit } => commands::untangle::run(commit.as_deref()),
         Commands::Init => commands::init::run(),
         Commands::Share => commands::share::run(),
-        Commands::Propose => commands::propose::run(),
-        Commands::Weave { candidate_id } => commands::weave::run(&candidate_id),
-    }
-}
-
-fn check_jj_prerequisite() -> Result<()> {
-    match which::which("jj") {
-        Ok(_) => Ok(()),
-        Err(_) => {
-            let msg = format!(
-                "jj not found. WEFT requires jj (Jujutsu) to work.\n\n\
-                 Install jj first:\n\
-                   macOS: brew install jj\n\
-                   Linux: curl -sSL https://github.com/martinvonz/jj/releases/download/v0.15.1/jj-v0.15.1-x86_64-unknown-linux-gnu.tar.gz | tar -xz && sudo mv jj /usr/local/bin/\n\
-                   Cargo: cargo install jj\n"
-            );
-            Err(anyhow::anyhow!(msg))
-        }
+        Commands::Propose {
+            title,
+            description,
+            sync,
+        } => commands::propose::run(title.as_deref(), description.as_deref(), sync),
+        Commands::Weave {
+            candidate_id,
+            strategy,
+            require_signed,
+        } => commands::weave::run(&candidate_id, strategy, require_signed),
+        Commands::New { name } => commands::new::run(&name),
+        Commands::Switch { name } => commands::switch::run(&name),
+        Commands::List => commands::list::run(),
+        Commands::Fetch => commands::fetch::run(),
+        Commands::Team => commands::team::run(),
+        Commands::Adopt { user, saves } => commands::adopt::run(&user, &saves),
+        Commands::Candidates { mine, json, stale } => commands::candidates::run(mine, json, stale),
+        Commands::Diff { candidate, revs } => commands::diff::run(&candidate, revs.as_deref()),
+        Commands::Review { candidate_id } => commands::review::review(&candidate_id),
+        Commands::Approve { candidate_id } => commands::review::approve(&candidate_id),
+        Commands::Reject {
+            candidate_id,
+            reason,
+        } => commands::review::reject(&candidate_id, &reason),
+        Commands::Web { port } => commands::web::run(port),
+        Commands::Tui => commands::tui::run(),
+        Commands::Mcp { agent } => commands::mcp::run(agent.as_deref()),
+        Commands::Serve { stop: false } => commands::serve::run(),
+        Commands::Serve { stop: true } => commands::serve::stop(),
+        Commands::Doctor { fix, probe } => commands::doctor::run(fix, probe),
+        Commands::Completions { shell } => completions::run(shell, Cli::command()),
+        Commands::Man { out } => commands::man::run(Cli::command(), out.as_deref()),
+        Commands::Queue { action } => match action {
+            QueueAction::Add { candidate_id } => commands::queue::add(&candidate_id),
+            QueueAction::Run { verify } => commands::queue::run(verify),
+            QueueAction::List => commands::queue::list(),
+        },
     }
 }
-
-fn check_jj_version() -> Result<()> {
-    let output = Command::new("jj").arg("--version").output()?;
-
-    if !output.status.success() {
-        return Err(anyhow::anyhow!("Failed to run jj --version"));
-    }
-
-    let version_str = String::from_utf8_lossy(&output.stdout);
-    let version = version_str
-        .split_whitespace()
-        .nth(1)
-        .ok_or_else(|| anyhow::anyhow!("Cannot parse jj version"))?;
-
-    let required = semver::VersionReq::parse(">=0.15.0")?;
-    let parsed_version = semver::Version::parse(version)?;
-
-    if !required.matches(&parsed_version) {
-        return Err(anyhow::anyhow!(
-            "jj version {} is too old. Please install jj ≥ 0.15.0",
-            version
-        ));
-    }
-
-    Ok(())
-}
@@ -0,0 +1,190 @@
+//! Typed results of the operations on [`crate::Weft`].
+
+use crate::loom::{CheckResult, Strategy};
+use git2::Oid;
+use serde::{Deserialize, Serialize};
+
+/// A commit as weft reports it: id, first line of the message and time.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct CommitSummary {
+    #[serde(with = "oid_string")]
+    pub id: Oid,
+    pub summary: String,
+    pub time: i64,
+}
+
+/// A file left out of a save because it broke the `[limits]` in `.weft.toml`.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct SkippedFile {
+    pub path: String,
+    pub size: u64,
+    pub binary: bool,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct SaveOutcome {
+    pub weft: String,
+    #[serde(with = "oid_string")]
+    pub commit: Oid,
+    pub message: String,
+    pub skipped: Vec<SkippedFile>,
+    /// What the hooks that ran printed, for the caller to show.
+    #[serde(default)]
+    pub hooks: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct SyncOutcome {
+    pub weft: String,
+    #[serde(with = "oid_string")]
+    pub head: Oid,
+    #[serde(with = "oid_string")]
+    pub trunk: Oid,
+    /// Commits on the weft that jj recorded as conflicted, newest first.
+    #[serde(with = "oids_string")]
+    pub tangled: Vec<Oid>,
+    /// Adopted saves dropped because they already landed in trunk.
+    #[serde(with = "oids_string")]
+    pub dropped: Vec<Oid>,
+    /// What jj reported when the rebase did not go through cleanly.
+    pub issues: Option<String>,
+    /// What the hooks that ran printed, for the caller to show.
+    #[serde(default)]
+    pub hooks: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct StatusReport {
+    pub weft: String,
+    #[serde(with = "oid_string")]
+    pub head: Oid,
+    #[serde(with = "opt_oid_string")]
+    pub trunk: Option<Oid>,
+    pub ahead: usize,
+    pub behind: usize,
+    /// Whether the working copy differs from the last save.
+    pub unsaved_changes: bool,
+    pub tangled: Vec<CommitSummary>,
+    /// The latest saves on the weft, newest first.
+    pub recent: Vec<CommitSummary>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct UndoOutcome {
+    /// The weft command that was undone, e.g. `save`.
+    pub command: String,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct ShareOutcome {
+    pub weft: String,
+    #[serde(with = "oid_string")]
+    pub head: Oid,
+    pub remote_url: String,
+    pub remote_ref: String,
+}
+
+/// A file changed by a candidate, with git's status letter (`A`, `M`, `D`, ...).
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct FileChange {
+    pub status: char,
+    pub path: String,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub enum ProposeOutcome {
+    /// The weft has no changes relative to trunk; nothing was pushed.
+    NothingToPropose {
+        weft: String,
+    },
+    /// This exact weft head is already the candidate's latest revision.
+    AlreadyProposed {
+        weft: String,
+        candidate_id: String,
+        revision: u32,
+    },
+    Proposed(Proposal),
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Proposal {
+    pub weft: String,
+    pub candidate_id: String,
+    pub revision: u32,
+    pub candidate_ref: String,
+    pub title: Option<String>,
+    /// The commits proposed, oldest first.
+    pub commits: Vec<CommitSummary>,
+    pub files: Vec<FileChange>,
+    pub behind_trunk: usize,
+    /// What the hooks that ran printed, for the caller to show.
+    #[serde(default)]
+    pub hooks: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct WeaveOutcome {
+    pub candidate_id: String,
+    pub strategy: Strategy,
+    #[serde(with = "oid_string")]
+    pub woven: Oid,
+    pub title: Option<String>,
+    pub check: Option<CheckResult>,
+    /// The auto-save made of unsaved work before the weft was synced.
+    #[serde(with = "opt_oid_string")]
+    pub saved: Option<Oid>,
+    /// The weft synced onto the new main, if it could be.
+    pub synced: Option<SyncOutcome>,
+    /// Things that went wrong after main on origin was already updated.
+    pub warnings: Vec<String>,
+    /// What the hooks that ran printed, for the caller to show.
+    #[serde(default)]
+    pub hooks: Vec<String>,
+}
+
+mod oid_string {
+    use git2::Oid;
+    use serde::{de::Error, Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(oid: &Oid, s: S) -> Result<S::Ok, S::Error> {
+        s.serialize_str(&oid.to_string())
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(d: D) -> Result<Oid, D::Error> {
+        String::deserialize(d)?.parse().map_err(D::Error::custom)
+    }
+}
+
+mod opt_oid_string {
+    use git2::Oid;
+    use serde::{de::Error, Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(oid: &Option<Oid>, s: S) -> Result<S::Ok, S::Error> {
+        match oid {
+            Some(oid) => s.serialize_some(&oid.to_string()),
+            None => s.serialize_none(),
+        }
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(d: D) -> Result<Option<Oid>, D::Error> {
+        Option::<String>::deserialize(d)?
+            .map(|oid| oid.parse().map_err(D::Error::custom))
+            .transpose()
+    }
+}
+
+mod oids_string {
+    use git2::Oid;
+    use serde::{de::Error, Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(oids: &[Oid], s: S) -> Result<S::Ok, S::Error> {
+        s.collect_seq(oids.iter().map(|oid| oid.to_string()))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(d: D) -> Result<Vec<Oid>, D::Error> {
+        Vec::<String>::deserialize(d)?
+            .iter()
+            .map(|oid| oid.parse().map_err(D::Error::custom))
+            .collect()
+    }
+}
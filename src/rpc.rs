@@ -0,0 +1,110 @@
+//! JSON dispatch of [`Weft`] operations, shared by `weft mcp` and `weft serve`.
+
+use crate::commands::diff;
+use crate::loom::{self, Strategy};
+use crate::{ProposeOptions, WeaveOptions, Weft};
+use anyhow::Result;
+use serde_json::{json, Value};
+
+pub const PARSE_ERROR: i64 = -32700;
+pub const INVALID_REQUEST: i64 = -32600;
+pub const METHOD_NOT_FOUND: i64 = -32601;
+pub const INVALID_PARAMS: i64 = -32602;
+pub const INTERNAL_ERROR: i64 = -32603;
+
+pub struct RpcError {
+    pub code: i64,
+    pub message: String,
+}
+
+impl RpcError {
+    pub fn new(code: i64, message: impl Into<String>) -> Self {
+        RpcError {
+            code,
+            message: message.into(),
+        }
+    }
+}
+
+pub fn error_response(id: Value, error: RpcError) -> Value {
+    json!({
+        "jsonrpc": "2.0",
+        "id": id,
+        "error": { "code": error.code, "message": error.message }
+    })
+}
+
+/// Runs `method` with JSON `params` and returns its outcome as JSON, or
+/// `None` if there is no such method.
+pub fn call(weft: &Weft, method: &str, params: &Value) -> Result<Option<Value>> {
+    let value = match method {
+        "save" => {
+            let message = params["message"]
+                .as_str()
+                .ok_or_else(|| anyhow::anyhow!("'message' is required"))?;
+            serde_json::to_value(weft.save(message)?)?
+        }
+        "sync" => serde_json::to_value(weft.sync()?)?,
+        "status" => serde_json::to_value(weft.status()?)?,
+        "diff" => match params["candidate"].as_str() {
+            Some(candidate) => candidate_diff(weft, candidate, params["revs"].as_str())?,
+            None => json!({ "patch": weft.diff()? }),
+        },
+        "undo" => serde_json::to_value(weft.undo()?)?,
+        "log" => {
+            let limit = params["limit"].as_u64().unwrap_or(20) as usize;
+            serde_json::to_value(weft.log(limit)?)?
+        }
+        "oplog" => {
+            let limit = params["limit"].as_u64().unwrap_or(20) as usize;
+            let entries = weft.oplog()?;
+            let skip = entries.len().saturating_sub(limit);
+            json!(&entries[skip..])
+        }
+        "share" => serde_json::to_value(weft.share()?)?,
+        "propose" => {
+            let options = ProposeOptions {
+                title: params["title"].as_str().map(str::to_string),
+                description: params["description"].as_str().map(str::to_string),
+                ask_details: None,
+            };
+            serde_json::to_value(weft.propose(options)?)?
+        }
+        "weave" => {
+            let candidate_id = params["candidate_id"]
+                .as_str()
+                .ok_or_else(|| anyhow::anyhow!("'candidate_id' is required"))?;
+            let strategy = match &params["strategy"] {
+                Value::Null => None,
+                strategy => Some(serde_json::from_value::<Strategy>(strategy.clone())?),
+            };
+            let options = WeaveOptions {
+                strategy,
+                require_signed: params["require_signed"].as_bool().unwrap_or(false),
+            };
+            serde_json::to_value(weft.weave(candidate_id, options)?)?
+        }
+        _ => return Ok(None),
+    };
+    Ok(Some(value))
+}
+
+fn candidate_diff(weft: &Weft, candidate_id: &str, revs: Option<&str>) -> Result<Value> {
+    let repo = weft.repo();
+    // Fall back to the revisions known locally when the remote is unreachable.
+    let _ = loom::fetch(repo);
+
+    let range = diff::revision_range(repo, candidate_id, revs)?;
+    let patch = repo.diff_tree_to_tree(
+        Some(&repo.find_commit(range.from_oid)?.tree()?),
+        Some(&repo.find_commit(range.to_oid)?.tree()?),
+        None,
+    )?;
+
+    Ok(json!({
+        "candidate": candidate_id,
+        "from": range.from,
+        "to": range.to,
+        "patch": diff::patch_text(&patch)?
+    }))
+}
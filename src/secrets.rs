@@ -0,0 +1,183 @@
+use crate::config::{SecretAllow, SecretsConfig};
+use crate::git;
+use anyhow::{Context, Result};
+use git2::{DiffOptions, Oid, Repository};
+use regex::Regex;
+
+/// Credential patterns every push is checked against.
+const BUILTIN_PATTERNS: &[(&str, &str)] = &[
+    ("AWS access key", r"\b(AKIA|ASIA)[0-9A-Z]{16}\b"),
+    (
+        "AWS secret key",
+        r#"(?i)aws_secret_access_key\s*[:=]\s*["']?[A-Za-z0-9/+=]{40}"#,
+    ),
+    ("GitHub token", r"\bgh[pousr]_[A-Za-z0-9]{36,}\b"),
+    ("GitHub token", r"\bgithub_pat_[A-Za-z0-9_]{22,}\b"),
+    ("GitLab token", r"\bglpat-[A-Za-z0-9_-]{20,}\b"),
+    ("Slack token", r"\bxox[abprs]-[A-Za-z0-9-]{10,}\b"),
+    ("Stripe secret key", r"\b[sr]k_live_[A-Za-z0-9]{20,}\b"),
+    ("Google API key", r"\bAIza[0-9A-Za-z_-]{35}\b"),
+    ("Anthropic API key", r"\bsk-ant-[A-Za-z0-9_-]{20,}"),
+    ("OpenAI API key", r"\bsk-(proj-)?[A-Za-z0-9_-]{32,}"),
+    (
+        "private key",
+        r"-----BEGIN ((RSA|DSA|EC|OPENSSH|PGP|ENCRYPTED) )?PRIVATE KEY( BLOCK)?-----",
+    ),
+    (
+        "hardcoded credential",
+        r#"(?i)\b(api[_-]?key|secret[_-]?key|access[_-]?token|auth[_-]?token|password)\b["']?\s*[:=]\s*["'][^"'\s]{12,}["']"#,
+    ),
+];
+
+/// A line added in some save that looks like a credential.
+pub struct Hit {
+    pub commit: Oid,
+    pub path: String,
+    pub line: u32,
+    pub pattern: String,
+    pub text: String,
+}
+
+struct Pattern {
+    name: String,
+    regex: Regex,
+}
+
+fn patterns(config: &SecretsConfig) -> Result<Vec<Pattern>> {
+    let mut patterns = Vec::new();
+    for (name, regex) in BUILTIN_PATTERNS {
+        patterns.push(Pattern {
+            name: name.to_string(),
+            regex: Regex::new(regex)?,
+        });
+    }
+    for custom in &config.patterns {
+        patterns.push(Pattern {
+            name: custom.name.clone(),
+            regex: Regex::new(&custom.regex).with_context(|| {
+                format!(
+                    "Invalid regex for secret pattern '{}' in .weft.toml",
+                    custom.name
+                )
+            })?,
+        });
+    }
+    Ok(patterns)
+}
+
+/// Scans every line added by the commits in `base..tip` for credentials,
+/// skipping hits allowed in `.weft.toml`. Hits are ordered oldest save first.
+pub fn scan(repo: &Repository, base: Oid, tip: Oid, config: &SecretsConfig) -> Result<Vec<Hit>> {
+    let patterns = patterns(config)?;
+    let mut commits = git::commits_between(repo, base, tip)?;
+    commits.reverse();
+
+    let mut hits = Vec::new();
+    for oid in commits {
+        let commit = repo.find_commit(oid)?;
+        let parent_tree = match commit.parent(0) {
+            Ok(parent) => Some(parent.tree()?),
+            Err(_) => None,
+        };
+        let diff = repo.diff_tree_to_tree(
+            parent_tree.as_ref(),
+            Some(&commit.tree()?),
+            Some(DiffOptions::new().context_lines(0)),
+        )?;
+
+        diff.foreach(
+            &mut |_, _| true,
+            None,
+            None,
+            Some(&mut |delta, _, line| {
+                if line.origin() != '+' {
+                    return true;
+                }
+                let content = String::from_utf8_lossy(line.content());
+                let path = delta
+                    .new_file()
+                    .path()
+                    .map(|p| p.display().to_string())
+                    .unwrap_or_default();
+                for pattern in &patterns {
+                    if let Some(found) = pattern.regex.find(&content) {
+                        let hit = Hit {
+                            commit: oid,
+                            path: path.clone(),
+                            line: line.new_lineno().unwrap_or(0),
+                            pattern: pattern.name.clone(),
+                            text: found.as_str().to_string(),
+                        };
+                        if !config.allow.iter().any(|a| allows(a, &hit)) {
+                            hits.push(hit);
+                        }
+                        break;
+                    }
+                }
+                true
+            }),
+        )?;
+    }
+
+    Ok(hits)
+}
+
+fn allows(entry: &SecretAllow, hit: &Hit) -> bool {
+    if entry.path.is_none() && entry.text.is_none() && entry.pattern.is_none() {
+        return false;
+    }
+    let path = entry.path.as_ref().is_none_or(|p| {
+        if p.ends_with('/') {
+            hit.path.starts_with(p.as_str())
+        } else {
+            hit.path == *p
+        }
+    });
+    let text = entry.text.as_ref().is_none_or(|t| hit.text == *t);
+    let pattern = entry.pattern.as_ref().is_none_or(|p| hit.pattern == *p);
+    path && text && pattern
+}
+
+/// Shows enough of a secret to find it without printing it again.
+fn redact(text: &str) -> String {
+    let shown: String = text.chars().take(6).collect();
+    format!("{}…", shown)
+}
+
+/// The error shown when `hits` block `action` (e.g. "share").
+pub fn blocked(repo: &Repository, action: &str, hits: &[Hit]) -> anyhow::Error {
+    let mut message = format!(
+        "Refusing to {}: found {} possible secrets in your saves:",
+        action,
+        hits.len()
+    );
+    for hit in hits {
+        let summary = repo
+            .find_commit(hit.commit)
+            .ok()
+            .and_then(|c| c.summary().map(|s| s.to_string()))
+            .unwrap_or_default();
+        message.push_str(&format!(
+            "\n  {}:{} {} ({}) in save {} {}",
+            hit.path,
+            hit.line,
+            hit.pattern,
+            redact(&hit.text),
+            &hit.commit.to_string()[..8],
+            summary
+        ));
+    }
+    message.push_str(
+        "\n\nRemove the secret from every save listed (pushing later saves is not enough,\n\
+         the history is pushed too) and rotate it if it was ever shared.\n\
+         If a hit is not a real secret, allow it in .weft.toml:\n\n\
+         [[secrets.allow]]\n",
+    );
+    if let Some(hit) = hits.first() {
+        message.push_str(&format!(
+            "path = \"{}\"\npattern = \"{}\"",
+            hit.path, hit.pattern
+        ));
+    }
+    anyhow::anyhow!(message)
+}
@@ -0,0 +1,256 @@
+use anyhow::{Context, Result};
+use git2::{Oid, Repository, Signature, Tree};
+use std::fs;
+use std::io::Write;
+use std::process::{Command, Stdio};
+
+/// Signature formats git supports through `gpg.format`.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Format {
+    OpenPgp,
+    Ssh,
+    X509,
+}
+
+/// How commits weft creates get signed, read from git's own settings.
+pub struct Signer {
+    pub format: Format,
+    /// `user.signingkey`; OpenPGP falls back to the committer identity.
+    pub key: Option<String>,
+    pub program: String,
+}
+
+/// Returns a signer when `commit.gpgsign` is on, honoring `gpg.format`,
+/// `user.signingkey` and the `gpg.*.program` overrides.
+pub fn signer(repo: &Repository) -> Result<Option<Signer>> {
+    let config = repo.config()?;
+    if !config.get_bool("commit.gpgsign").unwrap_or(false) {
+        return Ok(None);
+    }
+
+    let format = match config.get_string("gpg.format").as_deref() {
+        Ok("openpgp") | Err(_) => Format::OpenPgp,
+        Ok("ssh") => Format::Ssh,
+        Ok("x509") => Format::X509,
+        Ok(other) => {
+            return Err(anyhow::anyhow!(
+                "Unsupported gpg.format '{}'. Use openpgp, ssh or x509.",
+                other
+            ));
+        }
+    };
+
+    let program = match format {
+        Format::OpenPgp => config
+            .get_string("gpg.openpgp.program")
+            .or_else(|_| config.get_string("gpg.program"))
+            .unwrap_or_else(|_| "gpg".to_string()),
+        Format::Ssh => config
+            .get_string("gpg.ssh.program")
+            .unwrap_or_else(|_| "ssh-keygen".to_string()),
+        Format::X509 => config
+            .get_string("gpg.x509.program")
+            .unwrap_or_else(|_| "gpgsm".to_string()),
+    };
+
+    let key = config
+        .get_string("user.signingkey")
+        .ok()
+        .filter(|k| !k.is_empty());
+    if format == Format::Ssh && key.is_none() {
+        return Err(anyhow::anyhow!(
+            "commit.gpgsign is set with gpg.format=ssh, but user.signingkey is not. \
+             Set it to your public key or its path."
+        ));
+    }
+
+    Ok(Some(Signer {
+        format,
+        key,
+        program,
+    }))
+}
+
+impl Signer {
+    /// Produces a detached signature over `payload`, as git stores in `gpgsig`.
+    pub fn sign(&self, repo: &Repository, payload: &str) -> Result<String> {
+        match self.format {
+            Format::OpenPgp | Format::X509 => self.sign_gpg(repo, payload),
+            Format::Ssh => self.sign_ssh(payload),
+        }
+    }
+
+    fn sign_gpg(&self, repo: &Repository, payload: &str) -> Result<String> {
+        let key = match &self.key {
+            Some(key) => key.clone(),
+            None => {
+                let committer = repo.signature()?;
+                format!(
+                    "{} <{}>",
+                    committer.name().unwrap_or(""),
+                    committer.email().unwrap_or("")
+                )
+            }
+        };
+
+        let mut child = Command::new(&self.program)
+            .args(["--status-fd=2", "-bsau", &key])
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .with_context(|| format!("Failed to run '{}' to sign a commit", self.program))?;
+        child
+            .stdin
+            .take()
+            .context("Failed to write to the signing program")?
+            .write_all(payload.as_bytes())?;
+        let output = child.wait_with_output()?;
+
+        if !output.status.success() {
+            return Err(anyhow::anyhow!(
+                "Failed to sign commit with '{}': {}",
+                self.program,
+                String::from_utf8_lossy(&output.stderr)
+            ));
+        }
+
+        Ok(String::from_utf8_lossy(&output.stdout).to_string())
+    }
+
+    fn sign_ssh(&self, payload: &str) -> Result<String> {
+        let dir = tempfile::tempdir()?;
+        let data = dir.path().join("commit");
+        fs::write(&data, payload)?;
+
+        // A literal public key means the private half is in the ssh agent.
+        let key = self.key.as_deref().unwrap_or("");
+        let literal = key.strip_prefix("key::").unwrap_or(key);
+        let mut command = Command::new(&self.program);
+        command.args(["-Y", "sign", "-n", "git", "-f"]);
+        if literal.starts_with("ssh-") || literal.starts_with("ecdsa-") {
+            let pubkey = dir.path().join("key.pub");
+            fs::write(&pubkey, literal)?;
+            command.arg(&pubkey).arg("-U");
+        } else {
+            command.arg(expand_home(key));
+        }
+
+        let output = command
+            .arg(&data)
+            .output()
+            .with_context(|| format!("Failed to run '{}' to sign a commit", self.program))?;
+        if !output.status.success() {
+            return Err(anyhow::anyhow!(
+                "Failed to sign commit with '{}': {}",
+                self.program,
+                String::from_utf8_lossy(&output.stderr)
+            ));
+        }
+
+        let signature = fs::read_to_string(dir.path().join("commit.sig"))
+            .context("The ssh signing program produced no signature")?;
+        Ok(signature)
+    }
+
+    /// `--config` arguments that make jj sign the commits it writes the same way.
+    ///
+    /// jj 0.25 replaced `--config-toml` with `--config` and `signing.sign-all`
+    /// with `signing.behavior`.
+    pub fn jj_args(&self, jj_version: &semver::Version) -> Vec<String> {
+        let backend = match self.format {
+            Format::OpenPgp => "gpg",
+            Format::Ssh => "ssh",
+            Format::X509 => "gpgsm",
+        };
+        let program_key = format!("signing.backends.{}.program", backend);
+
+        let mut settings = vec![("signing.backend".to_string(), backend.to_string())];
+        if let Some(key) = &self.key {
+            let key = key.strip_prefix("key::").unwrap_or(key);
+            settings.push(("signing.key".to_string(), key.to_string()));
+        }
+        settings.push((program_key, self.program.clone()));
+
+        if *jj_version >= semver::Version::new(0, 25, 0) {
+            let mut args = vec!["--config".to_string(), "signing.behavior=own".to_string()];
+            for (name, value) in settings {
+                args.push("--config".to_string());
+                args.push(format!("{}={}", name, toml_string(&value)));
+            }
+            args
+        } else {
+            let mut toml = "signing.sign-all = true\n".to_string();
+            for (name, value) in settings {
+                toml.push_str(&format!("{} = {}\n", name, toml_string(&value)));
+            }
+            vec!["--config-toml".to_string(), toml]
+        }
+    }
+}
+
+fn toml_string(value: &str) -> String {
+    toml::Value::String(value.to_string()).to_string()
+}
+
+fn expand_home(path: &str) -> String {
+    match (path.strip_prefix("~/"), std::env::var("HOME")) {
+        (Some(rest), Ok(home)) => format!("{}/{}", home, rest),
+        _ => path.to_string(),
+    }
+}
+
+/// Writes a commit object without moving any ref, signed if the user's git
+/// config asks for signed commits. Use instead of `Repository::commit(None, ..)`.
+pub fn commit(
+    repo: &Repository,
+    author: &Signature,
+    committer: &Signature,
+    message: &str,
+    tree: &Tree,
+    parents: &[&git2::Commit],
+) -> Result<Oid> {
+    let signer = match signer(repo)? {
+        Some(signer) => signer,
+        None => return Ok(repo.commit(None, author, committer, message, tree, parents)?),
+    };
+
+    let buffer = repo.commit_create_buffer(author, committer, message, tree, parents)?;
+    let payload = buffer
+        .as_str()
+        .context("Commit contents are not valid UTF-8")?
+        .to_string();
+    let signature = signer.sign(repo, &payload)?;
+    Ok(repo.commit_signed(&payload, &signature, None)?)
+}
+
+/// Returns why `commit` does not carry a good signature from a trusted key,
+/// or `None` if it does.
+///
+/// Trust is decided by `git verify-commit`, so it follows the user's keyring,
+/// `gpg.ssh.allowedSignersFile` and `gpg.minTrustLevel`.
+pub fn untrusted(repo: &Repository, commit: Oid) -> Result<Option<String>> {
+    if repo.extract_signature(&commit, None).is_err() {
+        return Ok(Some("not signed".to_string()));
+    }
+
+    let output = Command::new("git")
+        .args(["verify-commit", &commit.to_string()])
+        .current_dir(repo.path())
+        .output()
+        .context("Failed to run git verify-commit")?;
+
+    if output.status.success() {
+        return Ok(None);
+    }
+
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    let reason = stderr
+        .lines()
+        .rev()
+        .find(|l| !l.trim().is_empty())
+        .unwrap_or("signature could not be verified")
+        .trim()
+        .to_string();
+    Ok(Some(format!("signature not trusted: {}", reason)))
+}
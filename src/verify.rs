@@ -0,0 +1,93 @@
+use crate::loom::{self, CheckResult};
+use anyhow::{Context, Result};
+use chrono::Utc;
+use git2::{Oid, Repository};
+use std::fs;
+use std::path::PathBuf;
+use std::process::Command;
+
+/// How much of a check's output is kept in the published result.
+const TAIL_LINES: usize = 20;
+
+/// Runs `check` against `commit` in a throwaway worktree and records the result.
+///
+/// The worktree is detached at exactly `commit`, so the check sees the tree
+/// that would land on main and nothing from the user's working copy. `head`
+/// is the candidate revision being woven.
+pub fn run_check(
+    repo: &Repository,
+    candidate_id: &str,
+    head: Oid,
+    commit: Oid,
+    check: &str,
+) -> Result<CheckResult> {
+    let worktree = tempfile::Builder::new().prefix("weft-check-").tempdir()?;
+
+    let output = Command::new("git")
+        .args(["worktree", "add", "--detach"])
+        .arg(worktree.path())
+        .arg(commit.to_string())
+        .current_dir(repo.path())
+        .output()
+        .context("Failed to create check worktree")?;
+
+    if !output.status.success() {
+        return Err(anyhow::anyhow!(
+            "Failed to create check worktree: {}",
+            String::from_utf8_lossy(&output.stderr)
+        ));
+    }
+
+    let result = Command::new("sh")
+        .args(["-c", check])
+        .current_dir(worktree.path())
+        .output()
+        .with_context(|| format!("Failed to run check '{}'", check));
+
+    let _ = Command::new("git")
+        .args(["worktree", "remove", "--force"])
+        .arg(worktree.path())
+        .current_dir(repo.path())
+        .output();
+
+    let output = result?;
+
+    let log_path = log_path(repo, candidate_id, commit);
+    if let Some(dir) = log_path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+
+    let mut log = output.stdout;
+    log.extend_from_slice(&output.stderr);
+    fs::write(&log_path, &log)?;
+
+    let log = String::from_utf8_lossy(&log);
+    let lines: Vec<&str> = log.lines().collect();
+    let result = CheckResult {
+        commit: commit.to_string(),
+        head: head.to_string(),
+        command: check.to_string(),
+        passed: output.status.success(),
+        ran_at: Utc::now().timestamp(),
+        tail: lines[lines.len().saturating_sub(TAIL_LINES)..].join("\n"),
+    };
+
+    loom::record_check(repo, candidate_id, result.clone())?;
+
+    Ok(result)
+}
+
+/// Where the full output of the check on `commit` is saved in this clone.
+pub fn log_path(repo: &Repository, candidate_id: &str, commit: Oid) -> PathBuf {
+    repo.path().join("weft").join("checks").join(format!(
+        "{}-{}.log",
+        candidate_id,
+        &commit.to_string()[..8]
+    ))
+}
+
+/// The last `lines` lines of a check's output, for showing why it failed.
+pub fn log_tail(result: &CheckResult, lines: usize) -> String {
+    let all: Vec<&str> = result.tail.lines().collect();
+    all[all.len().saturating_sub(lines)..].join("\n")
+}
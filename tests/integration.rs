@@ -4,7 +4,7 @@ use tempfile::TempDir;
 
 fn setup_git_repo(tmp: &TempDir) {
     let output = Command::new("git")
-        .args(&["init", "-b", "main"])
+        .args(["init", "-b", "main"])
         .current_dir(tmp.path())
         .output()
         .expect("Failed to init git repo");
@@ -16,7 +16,7 @@ fn setup_git_repo(tmp: &TempDir) {
     );
 
     let config = Command::new("git")
-        .args(&["config", "user.email", "test@example.com"])
+        .args(["config", "user.email", "test@example.com"])
         .current_dir(tmp.path())
         .output()
         .expect("Failed to set git email");
@@ -24,7 +24,7 @@ fn setup_git_repo(tmp: &TempDir) {
     assert!(config.status.success());
 
     let config = Command::new("git")
-        .args(&["config", "user.name", "Test User"])
+        .args(["config", "user.name", "Test User"])
         .current_dir(tmp.path())
         .output()
         .expect("Failed to set git name");
@@ -34,7 +34,7 @@ fn setup_git_repo(tmp: &TempDir) {
     fs::write(tmp.path().join("README.md"), "test repo").expect("Failed to write README");
 
     let add = Command::new("git")
-        .args(&["add", "."])
+        .args(["add", "."])
         .current_dir(tmp.path())
         .output()
         .expect("Failed to git add");
@@ -42,7 +42,7 @@ fn setup_git_repo(tmp: &TempDir) {
     assert!(add.status.success());
 
     let commit = Command::new("git")
-        .args(&["commit", "-m", "initial commit"])
+        .args(["commit", "-m", "initial commit"])
         .current_dir(tmp.path())
         .output()
         .expect("Failed to git commit");
@@ -54,7 +54,7 @@ fn setup_git_repo(tmp: &TempDir) {
     );
 
     let jj_init = Command::new("jj")
-        .args(&["git", "init"])
+        .args(["git", "init"])
         .current_dir(tmp.path())
         .output()
         .expect("Failed to init jj repo");
@@ -109,7 +109,7 @@ fn test_weft_init_creates_refs() {
     );
 
     let refs_output = Command::new("git")
-        .args(&["for-each-ref", "refs/weft"])
+        .args(["for-each-ref", "refs/weft"])
         .current_dir(tmp.path())
         .output()
         .expect("Failed to check refs");
@@ -141,7 +141,7 @@ fn test_weft_save_creates_commit() {
     assert!(stdout.contains("Saved"), "Expected save confirmation");
 
     let log_output = Command::new("jj")
-        .args(&["log", "-r", "@", "-T", "description"])
+        .args(["log", "-r", "@", "-T", "description"])
         .current_dir(tmp.path())
         .output()
         .expect("Failed to check jj log");
@@ -209,7 +209,7 @@ fn test_save_creates_weft_ref() {
     run_weft(&tmp, &["save", "checkpoint"]);
 
     let refs_output = Command::new("git")
-        .args(&["for-each-ref", "refs/weft"])
+        .args(["for-each-ref", "refs/weft"])
         .current_dir(tmp.path())
         .output()
         .expect("Failed to check weft refs");
@@ -308,7 +308,7 @@ fn test_undo_reverts_save() {
     fs::write(tmp.path().join("file.txt"), "original").expect("Failed to write file");
 
     let before_undo = Command::new("jj")
-        .args(&["log", "-r", "@", "-T", "description"])
+        .args(["log", "-r", "@", "-T", "description"])
         .current_dir(tmp.path())
         .output()
         .expect("Failed to get jj log");
@@ -319,7 +319,7 @@ fn test_undo_reverts_save() {
     run_weft(&tmp, &["undo"]);
 
     let after_undo = Command::new("jj")
-        .args(&["log", "-r", "@", "-T", "description"])
+        .args(["log", "-r", "@", "-T", "description"])
         .current_dir(tmp.path())
         .output()
         .expect("Failed to get jj log after undo");
@@ -351,6 +351,22 @@ fn test_status_shows_tangled_count() {
     assert!(combined.contains("Weft:"), "Expected Weft status section");
 }
 
+#[test]
+fn test_untangle_without_tangles() {
+    let tmp = TempDir::new().unwrap();
+    setup_git_repo(&tmp);
+
+    run_weft(&tmp, &["init"]);
+
+    let output = run_weft(&tmp, &["untangle"]);
+    assert!(output.status.success());
+    assert!(String::from_utf8_lossy(&output.stdout).contains("Nothing is tangled"));
+
+    let output = run_weft(&tmp, &["untangle", "HEAD"]);
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("is not tangled"));
+}
+
 fn run_weft_with_env(tmp: &TempDir, args: &[&str], user: &str) -> std::process::Output {
     let weft_path = "/home/skootsky/source-code2026/weft/target/release/weft";
 
@@ -372,7 +388,7 @@ fn test_two_users_sync_same_conflict() {
     run_weft_with_env(&tmp, &["save", "alice work 1"], "alice");
 
     let refs_output = Command::new("git")
-        .args(&["for-each-ref", "refs/weft"])
+        .args(["for-each-ref", "refs/weft"])
         .current_dir(tmp.path())
         .output()
         .expect("Failed to check refs");
@@ -410,7 +426,7 @@ fn test_undo_multiple_operations() {
 #[test]
 fn test_jj_version_check() {
     let output = Command::new("/home/skootsky/source-code2026/weft/target/release/weft")
-        .args(&["--version"])
+        .args(["--version"])
         .output()
         .expect("Failed to get weft version");
 
@@ -430,7 +446,7 @@ fn test_detects_master_vs_main() {
     let tmp = TempDir::new().unwrap();
 
     let output = Command::new("git")
-        .args(&["init", "-b", "master"])
+        .args(["init", "-b", "master"])
         .current_dir(tmp.path())
         .output()
         .expect("Failed to init git repo");
@@ -438,31 +454,31 @@ fn test_detects_master_vs_main() {
     assert!(output.status.success());
 
     Command::new("git")
-        .args(&["config", "user.email", "test@example.com"])
+        .args(["config", "user.email", "test@example.com"])
         .current_dir(tmp.path())
         .output()
         .expect("Failed to set email");
 
     Command::new("git")
-        .args(&["config", "user.name", "Test"])
+        .args(["config", "user.name", "Test"])
         .current_dir(tmp.path())
         .output()
         .expect("Failed to set name");
 
     fs::write(tmp.path().join("file.txt"), "content").expect("Failed to write file");
     Command::new("git")
-        .args(&["add", "."])
+        .args(["add", "."])
         .current_dir(tmp.path())
         .output()
         .expect("Failed to add");
     Command::new("git")
-        .args(&["commit", "-m", "init"])
+        .args(["commit", "-m", "init"])
         .current_dir(tmp.path())
         .output()
         .expect("Failed to commit");
 
     Command::new("jj")
-        .args(&["git", "init"])
+        .args(["git", "init"])
         .current_dir(tmp.path())
         .output()
         .expect("Failed to init jj");
@@ -509,7 +525,7 @@ fn test_user_with_special_chars() {
     );
 
     let refs_output = Command::new("git")
-        .args(&["for-each-ref", "refs/weft"])
+        .args(["for-each-ref", "refs/weft"])
         .current_dir(tmp.path())
         .output()
         .expect("Failed to check refs");
@@ -523,7 +539,7 @@ fn test_init_in_empty_repo() {
     let tmp = TempDir::new().unwrap();
 
     let output = Command::new("git")
-        .args(&["init", "-b", "main"])
+        .args(["init", "-b", "main"])
         .current_dir(tmp.path())
         .output()
         .expect("Failed to init git repo");
@@ -531,7 +547,7 @@ fn test_init_in_empty_repo() {
     assert!(output.status.success());
 
     Command::new("jj")
-        .args(&["git", "init"])
+        .args(["git", "init"])
         .current_dir(tmp.path())
         .output()
         .expect("Failed to init jj");
@@ -555,13 +571,13 @@ fn setup_git_repo_with_remote(tmp: &TempDir) {
 
     let bare_path = tmp.path().join("remote.git");
     Command::new("git")
-        .args(&["init", "--bare", bare_path.to_str().unwrap()])
+        .args(["init", "--bare", bare_path.to_str().unwrap()])
         .current_dir(tmp.path())
         .output()
         .expect("Failed to create bare remote");
 
     Command::new("git")
-        .args(&["remote", "add", "origin", bare_path.to_str().unwrap()])
+        .args(["remote", "add", "origin", bare_path.to_str().unwrap()])
         .current_dir(tmp.path())
         .output()
         .expect("Failed to add remote");
@@ -590,7 +606,7 @@ fn test_share_pushes_weft_ref() {
     );
 
     let refs_output = Command::new("git")
-        .args(&["ls-remote", "origin", "refs/weft/*"])
+        .args(["ls-remote", "origin", "refs/weft/*"])
         .current_dir(tmp.path())
         .output()
         .expect("Failed to check remote refs");
@@ -660,7 +676,7 @@ fn test_propose_creates_candidate_ref() {
     );
 
     let refs_output = Command::new("git")
-        .args(&["ls-remote", "origin", "refs/loom/*"])
+        .args(["ls-remote", "origin", "refs/loom/*"])
         .current_dir(tmp.path())
         .output()
         .expect("Failed to check remote refs");
@@ -708,3 +724,1819 @@ fn test_weave_fails_on_tangled_candidate() {
         assert!(combined.contains("Woven"), "Expected success message");
     }
 }
+
+#[test]
+fn test_new_creates_named_weft() {
+    let tmp = TempDir::new().unwrap();
+    setup_git_repo(&tmp);
+
+    run_weft(&tmp, &["init"]);
+    fs::write(tmp.path().join("file.txt"), "feature work").expect("Failed to write file");
+    run_weft(&tmp, &["save", "feature work"]);
+
+    let output = run_weft(&tmp, &["new", "bugfix"]);
+    assert!(
+        output.status.success(),
+        "weft new failed: {}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+
+    let refs_output = Command::new("git")
+        .args(["for-each-ref", "refs/weft"])
+        .current_dir(tmp.path())
+        .output()
+        .expect("Failed to check refs");
+
+    let refs = String::from_utf8_lossy(&refs_output.stdout);
+    assert!(
+        refs.contains("refs/weft/test-user/bugfix"),
+        "Expected named weft ref, got: {}",
+        refs
+    );
+    assert!(
+        !tmp.path().join("file.txt").exists(),
+        "New weft should start from trunk without feature files"
+    );
+}
+
+#[test]
+fn test_switch_restores_working_copy() {
+    let tmp = TempDir::new().unwrap();
+    setup_git_repo(&tmp);
+
+    run_weft(&tmp, &["init"]);
+    fs::write(tmp.path().join("file.txt"), "feature work").expect("Failed to write file");
+    run_weft(&tmp, &["save", "feature work"]);
+
+    run_weft(&tmp, &["new", "bugfix"]);
+    fs::write(tmp.path().join("fix.txt"), "the fix").expect("Failed to write file");
+
+    let output = run_weft(&tmp, &["switch", "head"]);
+    assert!(
+        output.status.success(),
+        "weft switch failed: {}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    assert!(tmp.path().join("file.txt").exists());
+    assert!(!tmp.path().join("fix.txt").exists());
+
+    run_weft(&tmp, &["switch", "bugfix"]);
+    assert!(
+        tmp.path().join("fix.txt").exists(),
+        "Unsaved work on bugfix should be restored"
+    );
+
+    let list = run_weft(&tmp, &["list"]);
+    let stdout = String::from_utf8_lossy(&list.stdout);
+    assert!(
+        stdout.contains("* bugfix"),
+        "Expected active marker, got: {}",
+        stdout
+    );
+    assert!(
+        stdout.contains("  head"),
+        "Expected default weft, got: {}",
+        stdout
+    );
+}
+
+#[test]
+fn test_switch_unknown_weft_fails() {
+    let tmp = TempDir::new().unwrap();
+    setup_git_repo(&tmp);
+
+    run_weft(&tmp, &["init"]);
+
+    let output = run_weft(&tmp, &["switch", "nope"]);
+    assert!(!output.status.success());
+
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("weft new nope"), "got: {}", stderr);
+}
+
+#[test]
+fn test_switch_rejects_invalid_name() {
+    let tmp = TempDir::new().unwrap();
+    setup_git_repo(&tmp);
+
+    run_weft(&tmp, &["init"]);
+
+    let output = run_weft(&tmp, &["switch", "../x"]);
+    assert!(!output.status.success());
+
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("Invalid weft name"), "got: {}", stderr);
+}
+
+#[test]
+fn test_undo_new_removes_weft() {
+    let tmp = TempDir::new().unwrap();
+    setup_git_repo(&tmp);
+
+    run_weft(&tmp, &["init"]);
+    run_weft(&tmp, &["new", "bugfix"]);
+
+    let output = run_weft(&tmp, &["undo"]);
+    assert!(
+        output.status.success(),
+        "weft undo failed: {}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+
+    let refs_output = Command::new("git")
+        .args(["for-each-ref", "refs/weft/test-user"])
+        .current_dir(tmp.path())
+        .output()
+        .expect("Failed to check refs");
+    let refs = String::from_utf8_lossy(&refs_output.stdout);
+    assert!(!refs.contains("bugfix"), "got: {}", refs);
+}
+
+#[test]
+fn test_fetch_and_team_show_shared_wefts() {
+    let tmp = TempDir::new().unwrap();
+    setup_git_repo_with_remote(&tmp);
+
+    run_weft_with_env(&tmp, &["init"], "alice");
+    fs::write(tmp.path().join("file.txt"), "alice work").expect("Failed to write file");
+    run_weft_with_env(&tmp, &["save", "alice login page"], "alice");
+    let share = run_weft_with_env(&tmp, &["share"], "alice");
+    assert!(share.status.success(), "alice share should succeed");
+
+    let output = run_weft_with_env(&tmp, &["fetch"], "bob");
+    assert!(
+        output.status.success(),
+        "weft fetch failed: {}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+
+    let refs_output = Command::new("git")
+        .args(["for-each-ref", "refs/weft-team"])
+        .current_dir(tmp.path())
+        .output()
+        .expect("Failed to check refs");
+    let refs = String::from_utf8_lossy(&refs_output.stdout);
+    assert!(refs.contains("refs/weft-team/alice/head"), "got: {}", refs);
+
+    let output = run_weft_with_env(&tmp, &["team"], "bob");
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(output.status.success());
+    assert!(stdout.contains("alice/head"), "got: {}", stdout);
+    assert!(stdout.contains("alice login page"), "got: {}", stdout);
+    assert!(stdout.contains("ahead"), "got: {}", stdout);
+}
+
+#[test]
+fn test_fetch_fails_without_remote() {
+    let tmp = TempDir::new().unwrap();
+    setup_git_repo(&tmp);
+
+    let output = run_weft(&tmp, &["fetch"]);
+    assert!(!output.status.success());
+
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("origin"), "got: {}", stderr);
+}
+
+fn clone_teammate_repo(tmp: &TempDir, teammate: &TempDir) {
+    let bare_path = tmp.path().join("remote.git");
+
+    Command::new("git")
+        .args(["push", "origin", "main"])
+        .current_dir(tmp.path())
+        .output()
+        .expect("Failed to push main");
+
+    let clone = Command::new("git")
+        .args(["clone", bare_path.to_str().unwrap(), "."])
+        .current_dir(teammate.path())
+        .output()
+        .expect("Failed to clone remote");
+    assert!(clone.status.success());
+
+    Command::new("git")
+        .args(["config", "user.name", "Bob"])
+        .current_dir(teammate.path())
+        .output()
+        .expect("Failed to set git name");
+
+    Command::new("jj")
+        .args(["git", "init", "--colocate"])
+        .current_dir(teammate.path())
+        .output()
+        .expect("Failed to init jj");
+}
+
+#[test]
+fn test_adopt_copies_teammate_saves() {
+    let tmp = TempDir::new().unwrap();
+    setup_git_repo_with_remote(&tmp);
+    let bob = TempDir::new().unwrap();
+    clone_teammate_repo(&tmp, &bob);
+
+    run_weft_with_env(&tmp, &["init"], "alice");
+    fs::write(tmp.path().join("api.txt"), "new api").expect("Failed to write file");
+    run_weft_with_env(&tmp, &["save", "alice api"], "alice");
+    run_weft_with_env(&tmp, &["share"], "alice");
+
+    run_weft(&bob, &["init"]);
+    run_weft(&bob, &["fetch"]);
+    let output = run_weft(&bob, &["adopt", "alice"]);
+    let combined = format!(
+        "{}{}",
+        String::from_utf8_lossy(&output.stdout),
+        String::from_utf8_lossy(&output.stderr)
+    );
+
+    assert!(output.status.success(), "weft adopt failed: {}", combined);
+    assert!(combined.contains("Adopted 1 saves"), "got: {}", combined);
+    assert!(
+        bob.path().join("api.txt").exists(),
+        "Adopted save should be in the working copy"
+    );
+
+    let log_output = Command::new("jj")
+        .args(["log", "-r", "@-", "-T", "description"])
+        .current_dir(bob.path())
+        .output()
+        .expect("Failed to check jj log");
+    let log = String::from_utf8_lossy(&log_output.stdout);
+    assert!(log.contains("Adopted-from: alice"), "got: {}", log);
+}
+
+#[test]
+fn test_undo_adopt_restores_weft_head() {
+    let tmp = TempDir::new().unwrap();
+    setup_git_repo_with_remote(&tmp);
+    let bob = TempDir::new().unwrap();
+    clone_teammate_repo(&tmp, &bob);
+
+    run_weft_with_env(&tmp, &["init"], "alice");
+    fs::write(tmp.path().join("api.txt"), "new api").expect("Failed to write file");
+    run_weft_with_env(&tmp, &["save", "alice api"], "alice");
+    run_weft_with_env(&tmp, &["share"], "alice");
+
+    run_weft(&bob, &["init"]);
+    run_weft(&bob, &["fetch"]);
+    let weft_head = |repo: &TempDir| {
+        let output = Command::new("git")
+            .args(["rev-parse", "refs/weft/test-user/head"])
+            .current_dir(repo.path())
+            .output()
+            .expect("Failed to read weft head");
+        String::from_utf8_lossy(&output.stdout).trim().to_string()
+    };
+    let before = weft_head(&bob);
+
+    let output = run_weft(&bob, &["adopt", "alice"]);
+    assert!(output.status.success(), "weft adopt should succeed");
+    assert_ne!(weft_head(&bob), before);
+
+    let output = run_weft(&bob, &["undo"]);
+    assert!(
+        output.status.success(),
+        "weft undo failed: {}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    assert_eq!(weft_head(&bob), before);
+    assert!(!bob.path().join("api.txt").exists());
+}
+
+#[test]
+fn test_adopt_requires_fetched_weft() {
+    let tmp = TempDir::new().unwrap();
+    setup_git_repo(&tmp);
+
+    run_weft(&tmp, &["init"]);
+
+    let output = run_weft(&tmp, &["adopt", "nobody"]);
+    assert!(!output.status.success());
+
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("weft fetch"), "got: {}", stderr);
+}
+
+#[test]
+fn test_candidates_lists_proposed_candidate() {
+    let tmp = TempDir::new().unwrap();
+    setup_git_repo_with_remote(&tmp);
+
+    run_weft(&tmp, &["init"]);
+    fs::write(tmp.path().join("file.txt"), "content").expect("Failed to write file");
+    run_weft(&tmp, &["save", "candidate work"]);
+    let propose = run_weft(&tmp, &["propose"]);
+    assert!(propose.status.success(), "propose should succeed");
+
+    let output = run_weft(&tmp, &["candidates"]);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(
+        output.status.success(),
+        "weft candidates failed: {}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    assert!(stdout.contains("test-user-"), "got: {}", stdout);
+    assert!(stdout.contains("files changed"), "got: {}", stdout);
+
+    let output = run_weft(&tmp, &["candidates", "--json"]);
+    let json: serde_json::Value =
+        serde_json::from_slice(&output.stdout).expect("candidates --json should print JSON");
+    assert_eq!(json[0]["author"], "test-user");
+    assert_eq!(json[0]["tangled"], false);
+
+    let output = run_weft_with_env(&tmp, &["candidates", "--mine"], "alice");
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("No open candidates"), "got: {}", stdout);
+}
+
+#[test]
+fn test_propose_with_title_stores_metadata() {
+    let tmp = TempDir::new().unwrap();
+    setup_git_repo_with_remote(&tmp);
+
+    run_weft(&tmp, &["init"]);
+    fs::write(tmp.path().join("file.txt"), "content").expect("Failed to write file");
+    run_weft(&tmp, &["save", "wip"]);
+
+    let output = run_weft(
+        &tmp,
+        &[
+            "propose",
+            "--title",
+            "Add login page",
+            "--description",
+            "Adds the login form and session handling.",
+        ],
+    );
+    assert!(
+        output.status.success(),
+        "weft propose failed: {}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+
+    let refs_output = Command::new("git")
+        .args(["ls-remote", "origin", "refs/loom-meta/*"])
+        .current_dir(tmp.path())
+        .output()
+        .expect("Failed to check remote refs");
+    let refs = String::from_utf8_lossy(&refs_output.stdout);
+    assert!(refs.contains("refs/loom-meta/test-user-"), "got: {}", refs);
+
+    let output = run_weft(&tmp, &["candidates"]);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("Add login page"), "got: {}", stdout);
+
+    let stdout =
+        String::from_utf8_lossy(&run_weft(&tmp, &["candidates", "--json"]).stdout).to_string();
+    let json: serde_json::Value = serde_json::from_str(&stdout).expect("expected JSON");
+    let candidate_id = json[0]["id"].as_str().unwrap().to_string();
+
+    let output = run_weft(&tmp, &["weave", &candidate_id]);
+    assert!(
+        output.status.success(),
+        "weft weave failed: {}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+
+    let log_output = Command::new("git")
+        .args(["log", "-1", "--format=%B", "origin/main"])
+        .current_dir(tmp.path())
+        .output()
+        .expect("Failed to read main");
+    let message = String::from_utf8_lossy(&log_output.stdout);
+    assert!(message.starts_with("Add login page"), "got: {}", message);
+    assert!(message.contains("session handling"), "got: {}", message);
+}
+
+fn propose_and_get_candidate_id(tmp: &TempDir) -> String {
+    let propose_output = run_weft(tmp, &["propose"]);
+    assert!(
+        propose_output.status.success(),
+        "propose should succeed: {}",
+        String::from_utf8_lossy(&propose_output.stderr)
+    );
+
+    let propose_stdout = String::from_utf8_lossy(&propose_output.stdout);
+    propose_stdout
+        .split("weft weave ")
+        .last()
+        .and_then(|rest| rest.split_whitespace().next())
+        .expect("propose should print the candidate id")
+        .to_string()
+}
+
+#[test]
+fn test_weave_refuses_when_check_fails() {
+    let tmp = TempDir::new().unwrap();
+    setup_git_repo_with_remote(&tmp);
+
+    run_weft(&tmp, &["init"]);
+    fs::write(tmp.path().join("file.txt"), "content").expect("Failed to write file");
+    fs::write(
+        tmp.path().join(".weft.toml"),
+        "[weave]\ncheck = \"echo checking && test -f missing.txt\"\n",
+    )
+    .expect("Failed to write config");
+    run_weft(&tmp, &["save", "work"]);
+
+    let candidate_id = propose_and_get_candidate_id(&tmp);
+    let output = run_weft(&tmp, &["weave", &candidate_id]);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+
+    assert!(!output.status.success(), "weave should be refused");
+    assert!(stderr.contains("Weave refused"), "got: {}", stderr);
+    assert!(
+        stderr.contains("checking"),
+        "expected check output, got: {}",
+        stderr
+    );
+
+    let refs_output = Command::new("git")
+        .args(["ls-remote", "origin", "refs/heads/main"])
+        .current_dir(tmp.path())
+        .output()
+        .expect("Failed to check remote refs");
+    assert!(
+        refs_output.stdout.is_empty(),
+        "main should not be updated on the remote"
+    );
+
+    let refs_output = Command::new("git")
+        .args(["ls-remote", "origin", "refs/loom-checks/*"])
+        .current_dir(tmp.path())
+        .output()
+        .expect("Failed to check remote refs");
+    assert!(
+        !refs_output.stdout.is_empty(),
+        "check result should be recorded on the remote"
+    );
+}
+
+#[test]
+fn test_check_result_follows_latest_revision() {
+    let tmp = TempDir::new().unwrap();
+    setup_git_repo_with_remote(&tmp);
+
+    run_weft(&tmp, &["init"]);
+    fs::write(tmp.path().join("file.txt"), "content").expect("Failed to write file");
+    fs::write(
+        tmp.path().join(".weft.toml"),
+        "[weave]\ncheck = \"echo checking && test -f missing.txt\"\n",
+    )
+    .expect("Failed to write config");
+    run_weft(&tmp, &["save", "work"]);
+
+    let candidate_id = propose_and_get_candidate_id(&tmp);
+    let output = run_weft(&tmp, &["weave", &candidate_id]);
+    assert!(!output.status.success(), "weave should be refused");
+
+    let output = run_weft(&tmp, &["candidates", "--json"]);
+    let candidates: serde_json::Value =
+        serde_json::from_slice(&output.stdout).expect("candidates --json should print JSON");
+    let check = &candidates[0]["check"];
+    assert_eq!(check["passed"], false);
+    assert!(check["tail"].as_str().unwrap().contains("checking"));
+    assert!(check.get("log").is_none(), "no local paths are published");
+
+    fs::write(tmp.path().join("file.txt"), "more").expect("Failed to write file");
+    run_weft(&tmp, &["save", "more work"]);
+    assert_eq!(propose_and_get_candidate_id(&tmp), candidate_id);
+
+    let output = run_weft(&tmp, &["candidates", "--json"]);
+    let candidates: serde_json::Value =
+        serde_json::from_slice(&output.stdout).expect("candidates --json should print JSON");
+    assert!(
+        candidates[0]["check"].is_null(),
+        "the check ran on an earlier revision"
+    );
+}
+
+#[test]
+fn test_repropose_finds_candidate_on_origin() {
+    let tmp = TempDir::new().unwrap();
+    setup_git_repo_with_remote(&tmp);
+
+    run_weft(&tmp, &["init"]);
+    fs::write(tmp.path().join("file.txt"), "content").expect("Failed to write file");
+    run_weft(&tmp, &["save", "work"]);
+    let candidate_id = propose_and_get_candidate_id(&tmp);
+
+    // Drop every local trace of the proposal, as in a fresh clone.
+    Command::new("git")
+        .args(["update-ref", "-d", "refs/loom-meta/wefts/test-user/head"])
+        .current_dir(tmp.path())
+        .output()
+        .expect("Failed to delete ref");
+
+    fs::write(tmp.path().join("file.txt"), "more").expect("Failed to write file");
+    run_weft(&tmp, &["save", "more work"]);
+    assert_eq!(propose_and_get_candidate_id(&tmp), candidate_id);
+}
+
+#[test]
+fn test_weave_runs_passing_check() {
+    let tmp = TempDir::new().unwrap();
+    setup_git_repo_with_remote(&tmp);
+
+    run_weft(&tmp, &["init"]);
+    fs::write(tmp.path().join("file.txt"), "content").expect("Failed to write file");
+    fs::write(
+        tmp.path().join(".weft.toml"),
+        "[weave]\ncheck = \"test -f file.txt\"\n",
+    )
+    .expect("Failed to write config");
+    run_weft(&tmp, &["save", "work"]);
+
+    let candidate_id = propose_and_get_candidate_id(&tmp);
+    let output = run_weft(&tmp, &["weave", &candidate_id]);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+
+    assert!(
+        output.status.success(),
+        "weave should succeed: {}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    assert!(stdout.contains("Check passed"), "got: {}", stdout);
+}
+
+#[test]
+fn test_queue_weaves_candidates_in_order() {
+    let tmp = TempDir::new().unwrap();
+    setup_git_repo_with_remote(&tmp);
+    Command::new("git")
+        .args(["push", "origin", "main"])
+        .current_dir(tmp.path())
+        .output()
+        .expect("Failed to push main");
+
+    run_weft(&tmp, &["init"]);
+    fs::write(tmp.path().join("a.txt"), "first feature").expect("Failed to write file");
+    run_weft(&tmp, &["save", "first feature"]);
+    let first = propose_and_get_candidate_id(&tmp);
+
+    run_weft(&tmp, &["new", "second"]);
+    fs::write(tmp.path().join("b.txt"), "second feature").expect("Failed to write file");
+    run_weft(&tmp, &["save", "second feature"]);
+    let second = propose_and_get_candidate_id(&tmp);
+
+    for candidate in [&first, &second] {
+        let output = run_weft(&tmp, &["queue", "add", candidate]);
+        assert!(
+            output.status.success(),
+            "queue add failed: {}",
+            String::from_utf8_lossy(&output.stderr)
+        );
+    }
+
+    let output = run_weft(&tmp, &["queue", "list"]);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains(&format!("1. {}", first)), "got: {}", stdout);
+    assert!(
+        stdout.contains(&format!("2. {}", second)),
+        "got: {}",
+        stdout
+    );
+
+    let output = run_weft(&tmp, &["queue", "run"]);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(
+        output.status.success(),
+        "queue run failed: {}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    assert!(stdout.contains("2 woven, 0 ejected"), "got: {}", stdout);
+
+    let tree_output = Command::new("git")
+        .args(["ls-tree", "--name-only", "origin/main"])
+        .current_dir(tmp.path())
+        .output()
+        .expect("Failed to list main");
+    let tree = String::from_utf8_lossy(&tree_output.stdout);
+    assert!(
+        tree.contains("a.txt") && tree.contains("b.txt"),
+        "got: {}",
+        tree
+    );
+}
+
+#[test]
+fn test_queue_publishes_check_of_ejected_candidate() {
+    let tmp = TempDir::new().unwrap();
+    setup_git_repo_with_remote(&tmp);
+    Command::new("git")
+        .args(["push", "origin", "main"])
+        .current_dir(tmp.path())
+        .output()
+        .expect("Failed to push main");
+
+    run_weft(&tmp, &["init"]);
+    fs::write(tmp.path().join("a.txt"), "feature").expect("Failed to write file");
+    fs::write(
+        tmp.path().join(".weft.toml"),
+        "[weave]\ncheck = \"test -f missing.txt\"\n",
+    )
+    .expect("Failed to write config");
+    run_weft(&tmp, &["save", "feature"]);
+    let candidate_id = propose_and_get_candidate_id(&tmp);
+    run_weft(&tmp, &["queue", "add", &candidate_id]);
+
+    let output = run_weft(&tmp, &["queue", "run", "--verify"]);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("0 woven, 1 ejected"), "got: {}", stdout);
+
+    let refs_output = Command::new("git")
+        .args([
+            "ls-remote",
+            "origin",
+            &format!("refs/loom-checks/{}", candidate_id),
+        ])
+        .current_dir(tmp.path())
+        .output()
+        .expect("Failed to check remote refs");
+    assert!(
+        !refs_output.stdout.is_empty(),
+        "check result should be published"
+    );
+}
+
+#[test]
+fn test_queue_add_unknown_candidate_fails() {
+    let tmp = TempDir::new().unwrap();
+    setup_git_repo_with_remote(&tmp);
+
+    run_weft(&tmp, &["init"]);
+
+    let output = run_weft(&tmp, &["queue", "add", "nobody-12345678"]);
+    assert!(!output.status.success());
+
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("not found"), "got: {}", stderr);
+}
+
+#[test]
+fn test_weave_squash_strategy_uses_title() {
+    let tmp = TempDir::new().unwrap();
+    setup_git_repo_with_remote(&tmp);
+
+    run_weft(&tmp, &["init"]);
+    fs::write(tmp.path().join("file.txt"), "content").expect("Failed to write file");
+    run_weft(&tmp, &["save", "wip"]);
+
+    let output = run_weft(&tmp, &["propose", "--title", "Squashed feature"]);
+    assert!(output.status.success(), "propose should succeed");
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let candidate_id = stdout
+        .split("weft weave ")
+        .last()
+        .and_then(|rest| rest.split_whitespace().next())
+        .unwrap()
+        .to_string();
+
+    let output = run_weft(&tmp, &["weave", &candidate_id, "--strategy", "squash"]);
+    assert!(
+        output.status.success(),
+        "weave --strategy squash failed: {}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+
+    let log_output = Command::new("git")
+        .args(["log", "--format=%s", "origin/main"])
+        .current_dir(tmp.path())
+        .output()
+        .expect("Failed to read main");
+    let log = String::from_utf8_lossy(&log_output.stdout);
+    assert_eq!(
+        log.lines().collect::<Vec<_>>(),
+        ["Squashed feature", "initial commit"]
+    );
+}
+
+#[test]
+fn test_weave_merge_strategy_from_config() {
+    let tmp = TempDir::new().unwrap();
+    setup_git_repo_with_remote(&tmp);
+
+    run_weft(&tmp, &["init"]);
+    fs::write(tmp.path().join("file.txt"), "content").expect("Failed to write file");
+    fs::write(
+        tmp.path().join(".weft.toml"),
+        "[weave]\nstrategy = \"merge\"\n",
+    )
+    .expect("Failed to write config");
+    run_weft(&tmp, &["save", "work"]);
+
+    let candidate_id = propose_and_get_candidate_id(&tmp);
+    let output = run_weft(&tmp, &["weave", &candidate_id]);
+    assert!(
+        output.status.success(),
+        "weave failed: {}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    assert!(String::from_utf8_lossy(&output.stdout).contains("(merge)"));
+
+    let parents_output = Command::new("git")
+        .args(["rev-list", "--parents", "-1", "origin/main"])
+        .current_dir(tmp.path())
+        .output()
+        .expect("Failed to read main");
+    let parents = String::from_utf8_lossy(&parents_output.stdout);
+    assert_eq!(
+        parents.split_whitespace().count(),
+        3,
+        "expected a merge commit, got: {}",
+        parents
+    );
+}
+
+#[test]
+fn test_weave_ff_refuses_when_main_moved() {
+    let tmp = TempDir::new().unwrap();
+    setup_git_repo_with_remote(&tmp);
+
+    run_weft(&tmp, &["init"]);
+    fs::write(tmp.path().join("a.txt"), "first").expect("Failed to write file");
+    run_weft(&tmp, &["save", "first"]);
+    let first = propose_and_get_candidate_id(&tmp);
+
+    run_weft(&tmp, &["new", "second"]);
+    fs::write(tmp.path().join("b.txt"), "second").expect("Failed to write file");
+    run_weft(&tmp, &["save", "second"]);
+    let second = propose_and_get_candidate_id(&tmp);
+
+    let output = run_weft(&tmp, &["weave", &first]);
+    assert!(output.status.success(), "first weave should succeed");
+
+    let output = run_weft(&tmp, &["weave", &second, "--strategy", "ff"]);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(!output.status.success(), "ff weave should be refused");
+    assert!(stderr.contains("--strategy rebase"), "got: {}", stderr);
+
+    let output = run_weft(&tmp, &["weave", &second, "--strategy", "rebase"]);
+    assert!(
+        output.status.success(),
+        "rebase weave should succeed: {}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+}
+
+#[test]
+fn test_weave_removes_candidate_refs_with_main_update() {
+    let tmp = TempDir::new().unwrap();
+    setup_git_repo_with_remote(&tmp);
+
+    run_weft(&tmp, &["init"]);
+    fs::write(tmp.path().join("file.txt"), "content").expect("Failed to write file");
+    run_weft(&tmp, &["save", "work"]);
+
+    let output = run_weft(&tmp, &["propose", "--title", "Atomic weave"]);
+    assert!(output.status.success(), "propose should succeed");
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let candidate_id = stdout
+        .split("weft weave ")
+        .last()
+        .and_then(|rest| rest.split_whitespace().next())
+        .unwrap()
+        .to_string();
+
+    let output = run_weft(&tmp, &["weave", &candidate_id]);
+    assert!(
+        output.status.success(),
+        "weave failed: {}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+
+    let refs_output = Command::new("git")
+        .args(["ls-remote", "origin"])
+        .current_dir(tmp.path())
+        .output()
+        .expect("Failed to check remote refs");
+    let refs = String::from_utf8_lossy(&refs_output.stdout);
+    assert!(refs.contains("refs/heads/main"), "got: {}", refs);
+    assert!(
+        !refs.contains("refs/loom/"),
+        "candidate ref left behind: {}",
+        refs
+    );
+    assert!(
+        !refs.contains("refs/loom-meta/"),
+        "metadata left behind: {}",
+        refs
+    );
+}
+
+#[test]
+fn test_weave_keeps_unsaved_work() {
+    let tmp = TempDir::new().unwrap();
+    setup_git_repo_with_remote(&tmp);
+
+    run_weft(&tmp, &["init"]);
+    fs::write(tmp.path().join("file.txt"), "content").expect("Failed to write file");
+    run_weft(&tmp, &["save", "work"]);
+    let candidate_id = propose_and_get_candidate_id(&tmp);
+
+    fs::write(tmp.path().join("notes.txt"), "not saved yet").expect("Failed to write file");
+
+    let output = run_weft(&tmp, &["weave", &candidate_id]);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(
+        output.status.success(),
+        "weave failed: {}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    assert!(
+        stdout.contains("Saved your unsaved changes"),
+        "got: {}",
+        stdout
+    );
+
+    let notes = fs::read_to_string(tmp.path().join("notes.txt")).expect("notes.txt was lost");
+    assert_eq!(notes, "not saved yet");
+
+    let main_output = Command::new("git")
+        .args(["rev-parse", "refs/heads/main", "refs/remotes/origin/main"])
+        .current_dir(tmp.path())
+        .output()
+        .expect("Failed to read main");
+    let mains = String::from_utf8_lossy(&main_output.stdout);
+    let mains: Vec<&str> = mains.lines().collect();
+    assert_eq!(mains[0], mains[1], "local main should match origin");
+}
+
+fn git_stdout(tmp: &TempDir, args: &[&str], stdin: &str) -> String {
+    use std::io::Write;
+    use std::process::Stdio;
+
+    let mut child = Command::new("git")
+        .args(args)
+        .current_dir(tmp.path())
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .expect("Failed to run git");
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(stdin.as_bytes())
+        .unwrap();
+    let output = child.wait_with_output().expect("Failed to wait for git");
+    assert!(output.status.success(), "git {:?} failed", args);
+    String::from_utf8_lossy(&output.stdout).trim().to_string()
+}
+
+/// Pushes a single-file commit straight to refs/loom/<candidate_id> on origin.
+fn push_raw_candidate(tmp: &TempDir, candidate_id: &str, file: &str, parent: Option<&str>) {
+    let blob = git_stdout(tmp, &["hash-object", "-w", "--stdin"], "raw candidate");
+    let tree = git_stdout(
+        tmp,
+        &["mktree"],
+        &format!("100644 blob {}\t{}\n", blob, file),
+    );
+    let mut args = vec!["commit-tree", tree.as_str(), "-m", "raw candidate"];
+    if let Some(parent) = parent {
+        args.extend(["-p", parent]);
+    }
+    let commit = git_stdout(tmp, &args, "");
+
+    let push = Command::new("git")
+        .args([
+            "push",
+            "origin",
+            &format!("{}:refs/loom/{}", commit, candidate_id),
+        ])
+        .current_dir(tmp.path())
+        .output()
+        .expect("Failed to push candidate");
+    assert!(push.status.success());
+}
+
+#[test]
+fn test_weave_refuses_tangled_candidate_commit() {
+    let tmp = TempDir::new().unwrap();
+    setup_git_repo_with_remote(&tmp);
+    run_weft(&tmp, &["init"]);
+
+    push_raw_candidate(
+        &tmp,
+        "test-user-tangled1",
+        ".jjconflict-side-0",
+        Some("main"),
+    );
+
+    let output = run_weft(&tmp, &["weave", "test-user-tangled1"]);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        !output.status.success(),
+        "tangled candidate must not be woven"
+    );
+    assert!(stderr.contains("no tangled commits"), "got: {}", stderr);
+}
+
+#[test]
+fn test_weave_refuses_unrelated_candidate_commit() {
+    let tmp = TempDir::new().unwrap();
+    setup_git_repo_with_remote(&tmp);
+    run_weft(&tmp, &["init"]);
+
+    push_raw_candidate(&tmp, "test-user-orphan1", "other.txt", None);
+
+    let output = run_weft(&tmp, &["weave", "test-user-orphan1"]);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        !output.status.success(),
+        "unrelated candidate must not be woven"
+    );
+    assert!(
+        stderr.contains("shares history with main"),
+        "got: {}",
+        stderr
+    );
+}
+
+#[test]
+fn test_candidates_lists_unrelated_candidate() {
+    let tmp = TempDir::new().unwrap();
+    setup_git_repo_with_remote(&tmp);
+    run_weft(&tmp, &["init"]);
+
+    push_raw_candidate(&tmp, "test-user-orphan1", "other.txt", None);
+
+    let output = run_weft(&tmp, &["candidates"]);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(
+        output.status.success(),
+        "candidates failed: {}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    assert!(stdout.contains("unrelated history"), "got: {}", stdout);
+}
+
+#[test]
+fn test_propose_warns_when_nothing_to_propose() {
+    let tmp = TempDir::new().unwrap();
+    setup_git_repo_with_remote(&tmp);
+
+    run_weft(&tmp, &["init"]);
+
+    let output = run_weft(&tmp, &["propose"]);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(output.status.success(), "propose should not fail");
+    assert!(stdout.contains("Nothing to propose"), "got: {}", stdout);
+
+    let refs_output = Command::new("git")
+        .args(["ls-remote", "origin", "refs/loom/*"])
+        .current_dir(tmp.path())
+        .output()
+        .expect("Failed to check remote refs");
+    assert!(
+        String::from_utf8_lossy(&refs_output.stdout).is_empty(),
+        "no candidate should be pushed"
+    );
+}
+
+#[test]
+fn test_propose_uses_weft_head_and_lists_files() {
+    let tmp = TempDir::new().unwrap();
+    setup_git_repo_with_remote(&tmp);
+
+    run_weft(&tmp, &["init"]);
+    fs::write(tmp.path().join("saved.txt"), "saved").expect("Failed to write file");
+    run_weft(&tmp, &["save", "saved work"]);
+    fs::write(tmp.path().join("unsaved.txt"), "unsaved").expect("Failed to write file");
+
+    let output = run_weft(&tmp, &["propose"]);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(output.status.success(), "propose failed: {}", stdout);
+    assert!(stdout.contains("A saved.txt"), "got: {}", stdout);
+    assert!(!stdout.contains("unsaved.txt"), "got: {}", stdout);
+
+    let weft_head = git_stdout(&tmp, &["rev-parse", "refs/weft/test-user/head"], "");
+    let refs = git_stdout(&tmp, &["ls-remote", "origin", "refs/loom/*"], "");
+    assert!(refs.contains(&weft_head), "got: {}", refs);
+}
+
+#[test]
+fn test_repropose_adds_revision_to_same_candidate() {
+    let tmp = TempDir::new().unwrap();
+    setup_git_repo_with_remote(&tmp);
+
+    run_weft(&tmp, &["init"]);
+    fs::write(tmp.path().join("file.txt"), "first try").expect("Failed to write file");
+    run_weft(&tmp, &["save", "first try"]);
+    let first = propose_and_get_candidate_id(&tmp);
+
+    let output = run_weft(&tmp, &["propose"]);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("already proposed"), "got: {}", stdout);
+
+    fs::write(tmp.path().join("file.txt"), "after review").expect("Failed to write file");
+    run_weft(&tmp, &["save", "address feedback"]);
+    let second = propose_and_get_candidate_id(&tmp);
+    assert_eq!(first, second, "re-propose should keep the candidate id");
+
+    let refs = git_stdout(&tmp, &["ls-remote", "origin", "refs/loom/*"], "");
+    assert!(
+        refs.contains(&format!("refs/loom/{}/1", first)),
+        "got: {}",
+        refs
+    );
+    assert!(
+        refs.contains(&format!("refs/loom/{}/2", first)),
+        "got: {}",
+        refs
+    );
+
+    let output = run_weft(&tmp, &["candidates"]);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("2 revisions"), "got: {}", stdout);
+}
+
+#[test]
+fn test_diff_between_candidate_revisions() {
+    let tmp = TempDir::new().unwrap();
+    setup_git_repo_with_remote(&tmp);
+
+    run_weft(&tmp, &["init"]);
+    fs::write(tmp.path().join("file.txt"), "first try\n").expect("Failed to write file");
+    run_weft(&tmp, &["save", "first try"]);
+    let candidate_id = propose_and_get_candidate_id(&tmp);
+
+    fs::write(tmp.path().join("file.txt"), "after review\n").expect("Failed to write file");
+    run_weft(&tmp, &["save", "address feedback"]);
+    propose_and_get_candidate_id(&tmp);
+
+    let output = run_weft(
+        &tmp,
+        &["diff", "--candidate", &candidate_id, "--revs", "1..2"],
+    );
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(
+        output.status.success(),
+        "diff failed: {}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    assert!(stdout.contains("-first try"), "got: {}", stdout);
+    assert!(stdout.contains("+after review"), "got: {}", stdout);
+
+    let output = run_weft(
+        &tmp,
+        &["diff", "--candidate", &candidate_id, "--revs", "1..5"],
+    );
+    assert!(!output.status.success(), "unknown revision should fail");
+}
+
+#[test]
+fn test_weave_requires_approval_from_someone_else() {
+    let tmp = TempDir::new().unwrap();
+    setup_git_repo_with_remote(&tmp);
+
+    run_weft(&tmp, &["init"]);
+    fs::write(tmp.path().join(".weft.toml"), "[review]\napprovals = 1\n")
+        .expect("Failed to write config");
+    fs::write(tmp.path().join("file.txt"), "content").expect("Failed to write file");
+    run_weft(&tmp, &["save", "work"]);
+    let candidate_id = propose_and_get_candidate_id(&tmp);
+
+    let output = run_weft(&tmp, &["weave", &candidate_id]);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(!output.status.success(), "unapproved weave should fail");
+    assert!(stderr.contains("1 approvals"), "got: {}", stderr);
+
+    let output = run_weft(&tmp, &["approve", &candidate_id]);
+    assert!(
+        !output.status.success(),
+        "authors should not approve their own candidate"
+    );
+
+    let output = run_weft_with_env(&tmp, &["approve", &candidate_id], "alice");
+    assert!(
+        output.status.success(),
+        "approve failed: {}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+
+    let output = run_weft(&tmp, &["weave", &candidate_id]);
+    assert!(
+        output.status.success(),
+        "approved weave should succeed: {}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+}
+
+#[test]
+fn test_reject_blocks_weave_and_shows_in_review() {
+    let tmp = TempDir::new().unwrap();
+    setup_git_repo_with_remote(&tmp);
+
+    run_weft(&tmp, &["init"]);
+    fs::write(tmp.path().join("file.txt"), "content").expect("Failed to write file");
+    run_weft(&tmp, &["save", "work"]);
+    let candidate_id = propose_and_get_candidate_id(&tmp);
+
+    let output = run_weft_with_env(
+        &tmp,
+        &["reject", &candidate_id, "--reason", "needs tests"],
+        "alice",
+    );
+    assert!(
+        output.status.success(),
+        "reject failed: {}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+
+    let output = run_weft(&tmp, &["review", &candidate_id]);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("rejected: needs tests"), "got: {}", stdout);
+    assert!(stdout.contains("+content"), "got: {}", stdout);
+
+    let output = run_weft(&tmp, &["weave", &candidate_id]);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(!output.status.success(), "rejected weave should fail");
+    assert!(stderr.contains("needs tests"), "got: {}", stderr);
+}
+
+#[test]
+fn test_weave_require_signed_refuses_unsigned_candidate() {
+    let tmp = TempDir::new().unwrap();
+    setup_git_repo_with_remote(&tmp);
+
+    run_weft(&tmp, &["init"]);
+    fs::write(tmp.path().join("file.txt"), "content").expect("Failed to write file");
+    run_weft(&tmp, &["save", "unsigned work"]);
+    let candidate_id = propose_and_get_candidate_id(&tmp);
+
+    let output = run_weft(&tmp, &["weave", &candidate_id, "--require-signed"]);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(!output.status.success(), "unsigned weave should be refused");
+    assert!(
+        stderr.contains("signed by a trusted key"),
+        "got: {}",
+        stderr
+    );
+    assert!(stderr.contains("not signed"), "got: {}", stderr);
+}
+
+#[test]
+fn test_weave_signs_squashed_commit_with_ssh_key() {
+    let tmp = TempDir::new().unwrap();
+    setup_git_repo_with_remote(&tmp);
+
+    let key = tmp.path().join(".git").join("test_signing_key");
+    let keygen = Command::new("ssh-keygen")
+        .args(["-q", "-t", "ed25519", "-N", "", "-f"])
+        .arg(&key)
+        .output()
+        .expect("Failed to run ssh-keygen");
+    assert!(keygen.status.success(), "ssh-keygen failed");
+
+    for (name, value) in [
+        ("gpg.format", "ssh"),
+        ("commit.gpgsign", "true"),
+        ("user.signingkey", key.to_str().unwrap()),
+    ] {
+        Command::new("git")
+            .args(["config", name, value])
+            .current_dir(tmp.path())
+            .output()
+            .expect("Failed to set git config");
+    }
+
+    run_weft(&tmp, &["init"]);
+    fs::write(tmp.path().join("file.txt"), "content").expect("Failed to write file");
+    run_weft(&tmp, &["save", "signed work"]);
+    let candidate_id = propose_and_get_candidate_id(&tmp);
+
+    let output = run_weft(&tmp, &["weave", &candidate_id, "--strategy", "squash"]);
+    assert!(
+        output.status.success(),
+        "weave failed: {}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+
+    let signed = git_stdout(&tmp, &["log", "-1", "--format=%G?", "origin/main"], "");
+    assert_ne!(signed, "N", "woven commit should carry a signature");
+}
+
+#[test]
+fn test_share_blocks_secret_in_any_save() {
+    let tmp = TempDir::new().unwrap();
+    setup_git_repo_with_remote(&tmp);
+
+    run_weft(&tmp, &["init"]);
+    let key = format!("aws_key = {}{}\n", "AKIA", "ABCDEFGHIJKLMNOP");
+    fs::write(
+        tmp.path().join("config.py"),
+        format!("debug = True\n{}", key),
+    )
+    .expect("Failed to write file");
+    run_weft(&tmp, &["save", "add config"]);
+    fs::write(tmp.path().join("config.py"), "debug = True\n").expect("Failed to write file");
+    run_weft(&tmp, &["save", "remove key"]);
+
+    let output = run_weft(&tmp, &["share"]);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(!output.status.success(), "share should be blocked");
+    assert!(stderr.contains("config.py:2"), "got: {}", stderr);
+    assert!(stderr.contains("AWS access key"), "got: {}", stderr);
+    assert!(stderr.contains("add config"), "got: {}", stderr);
+
+    let output = run_weft(&tmp, &["propose"]);
+    assert!(!output.status.success(), "propose should be blocked too");
+}
+
+#[test]
+fn test_secret_allowlist_lets_share_through() {
+    let tmp = TempDir::new().unwrap();
+    setup_git_repo_with_remote(&tmp);
+
+    run_weft(&tmp, &["init"]);
+    fs::write(
+        tmp.path().join(".weft.toml"),
+        "[[secrets.allow]]\npath = \"fixtures/\"\n\n\
+         [[secrets.patterns]]\nname = \"internal token\"\nregex = \"itk_[a-z0-9]{16}\"\n",
+    )
+    .expect("Failed to write config");
+    fs::create_dir_all(tmp.path().join("fixtures")).unwrap();
+    fs::write(
+        tmp.path().join("fixtures").join("fake.txt"),
+        format!("{}{}\n", "AKIA", "ABCDEFGHIJKLMNOP"),
+    )
+    .expect("Failed to write file");
+    run_weft(&tmp, &["save", "fixtures"]);
+
+    let output = run_weft(&tmp, &["share"]);
+    assert!(
+        output.status.success(),
+        "allowed hit should not block share: {}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+
+    fs::write(tmp.path().join("token.txt"), "itk_0123456789abcdef\n")
+        .expect("Failed to write file");
+    run_weft(&tmp, &["save", "token"]);
+
+    let output = run_weft(&tmp, &["share"]);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        !output.status.success(),
+        "custom pattern should block share"
+    );
+    assert!(stderr.contains("internal token"), "got: {}", stderr);
+}
+
+#[test]
+fn test_save_skips_oversized_files() {
+    let tmp = TempDir::new().unwrap();
+    setup_git_repo_with_remote(&tmp);
+
+    run_weft(&tmp, &["init"]);
+    fs::write(
+        tmp.path().join(".weft.toml"),
+        "[limits]\nmax_file_size = 100\n",
+    )
+    .expect("Failed to write config");
+    fs::write(tmp.path().join("small.txt"), "small").expect("Failed to write file");
+    fs::write(tmp.path().join("artifact.bin"), vec![b'x'; 1000]).expect("Failed to write file");
+
+    let output = run_weft(&tmp, &["save", "work"]);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        output.status.success(),
+        "save should skip, not fail: {}",
+        stderr
+    );
+    assert!(stderr.contains("artifact.bin (1.0 KB"), "got: {}", stderr);
+    assert!(stderr.contains(".gitignore"), "got: {}", stderr);
+    assert!(
+        tmp.path().join("artifact.bin").exists(),
+        "file must stay on disk"
+    );
+
+    let tree = git_stdout(
+        &tmp,
+        &["ls-tree", "--name-only", "refs/weft/test-user/head"],
+        "",
+    );
+    assert!(tree.contains("small.txt"), "got: {}", tree);
+    assert!(!tree.contains("artifact.bin"), "got: {}", tree);
+}
+
+#[test]
+fn test_save_refuses_and_share_blocks_over_limits() {
+    let tmp = TempDir::new().unwrap();
+    setup_git_repo_with_remote(&tmp);
+
+    run_weft(&tmp, &["init"]);
+    fs::write(tmp.path().join("image.dat"), [0u8, 159, 146, 150, 0, 1])
+        .expect("Failed to write file");
+    run_weft(&tmp, &["save", "binary"]);
+
+    fs::write(
+        tmp.path().join(".weft.toml"),
+        "[limits]\nallow_binary = false\non_save = \"refuse\"\n",
+    )
+    .expect("Failed to write config");
+
+    let output = run_weft(&tmp, &["share"]);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(!output.status.success(), "share should be blocked");
+    assert!(stderr.contains("image.dat"), "got: {}", stderr);
+    assert!(stderr.contains("binary"), "got: {}", stderr);
+
+    fs::write(tmp.path().join("other.dat"), [0u8, 1, 2, 3]).expect("Failed to write file");
+    let output = run_weft(&tmp, &["save", "more binary"]);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(!output.status.success(), "save should refuse");
+    assert!(stderr.contains("other.dat"), "got: {}", stderr);
+}
+
+#[cfg(unix)]
+fn write_hook(dir: &std::path::Path, name: &str, script: &str) {
+    use std::os::unix::fs::PermissionsExt;
+
+    fs::create_dir_all(dir).expect("Failed to create hooks dir");
+    let path = dir.join(name);
+    fs::write(&path, script).expect("Failed to write hook");
+    fs::set_permissions(&path, fs::Permissions::from_mode(0o755)).expect("Failed to chmod hook");
+}
+
+#[cfg(unix)]
+#[test]
+fn test_pre_save_hook_can_veto() {
+    let tmp = TempDir::new().unwrap();
+    setup_git_repo(&tmp);
+
+    run_weft(&tmp, &["init"]);
+    write_hook(
+        &tmp.path().join(".weft").join("hooks"),
+        "pre-save",
+        "#!/bin/sh\necho \"lint failed for $WEFT_USER\" >&2\nexit 1\n",
+    );
+    fs::write(tmp.path().join("file.txt"), "content").expect("Failed to write file");
+
+    let output = run_weft(&tmp, &["save", "blocked"]);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(!output.status.success(), "pre-save hook should veto");
+    assert!(
+        stderr.contains("lint failed for test-user"),
+        "got: {}",
+        stderr
+    );
+    assert!(stderr.contains("pre-save hook"), "got: {}", stderr);
+
+    let op_log = git_stdout(&tmp, &["cat-file", "-p", "refs/weft/op-log"], "");
+    assert!(op_log.contains("\"hook\":\"pre-save\""), "got: {}", op_log);
+    assert!(!op_log.contains("blocked"), "save should not be logged");
+}
+
+#[cfg(unix)]
+#[test]
+fn test_local_post_save_hook_gets_env_and_undo_skips_it() {
+    let tmp = TempDir::new().unwrap();
+    setup_git_repo(&tmp);
+
+    run_weft(&tmp, &["init"]);
+    let record = tmp.path().join(".git").join("hook-env");
+    write_hook(
+        &tmp.path().join(".git").join("weft").join("hooks"),
+        "post-save",
+        &format!(
+            "#!/bin/sh\necho \"$WEFT_COMMAND $WEFT_WEFT $WEFT_HEAD\" > {}\n",
+            record.display()
+        ),
+    );
+    fs::write(tmp.path().join("file.txt"), "content").expect("Failed to write file");
+
+    let output = run_weft(&tmp, &["save", "hooked"]);
+    assert!(output.status.success(), "save should succeed");
+
+    let head = git_stdout(&tmp, &["rev-parse", "refs/weft/test-user/head"], "");
+    let recorded = fs::read_to_string(&record).expect("hook should have run");
+    assert_eq!(recorded.trim(), format!("save head {}", head));
+
+    let output = run_weft(&tmp, &["undo"]);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(output.status.success(), "undo should succeed");
+    assert!(stdout.contains("Undid: weft save"), "got: {}", stdout);
+}
+
+#[cfg(unix)]
+#[test]
+fn test_library_save_returns_hook_output() {
+    let tmp = TempDir::new().unwrap();
+    setup_git_repo(&tmp);
+    run_weft(&tmp, &["init"]);
+    write_hook(
+        &tmp.path().join(".git").join("weft").join("hooks"),
+        "post-save",
+        "#!/bin/sh\necho \"formatted $WEFT_WEFT\"\n",
+    );
+
+    let weft = weft::Weft::open(tmp.path()).expect("Failed to open repo");
+    fs::write(tmp.path().join("file.txt"), "content").expect("Failed to write file");
+    let saved = weft.save("hooked").expect("save failed");
+    assert_eq!(saved.hooks, vec!["formatted head".to_string()]);
+
+    let op_log = git_stdout(&tmp, &["cat-file", "-p", "refs/weft/op-log"], "");
+    assert!(
+        op_log.contains("\"output\":\"formatted head\""),
+        "got: {}",
+        op_log
+    );
+}
+
+#[test]
+fn test_library_save_and_status_report() {
+    let tmp = TempDir::new().unwrap();
+    setup_git_repo(&tmp);
+    run_weft(&tmp, &["init"]);
+
+    let weft = weft::Weft::open(tmp.path()).expect("Failed to open repo");
+
+    fs::write(tmp.path().join("file.txt"), "content").expect("Failed to write file");
+    let saved = weft.save("from the library").expect("save failed");
+    assert_eq!(saved.weft, "head");
+    assert!(saved.skipped.is_empty());
+
+    let head = git_stdout(&tmp, &["rev-parse", "refs/weft/test-user/head"], "");
+    assert_eq!(saved.commit.to_string(), head);
+
+    let report = weft.status().expect("status failed");
+    assert_eq!(report.head, saved.commit);
+    assert!(report.tangled.is_empty());
+    assert!(!report.unsaved_changes);
+    assert_eq!(report.recent[0].summary, "save: from the library");
+}
+
+#[test]
+fn test_library_sync_outcome() {
+    let tmp = TempDir::new().unwrap();
+    setup_git_repo(&tmp);
+    run_weft(&tmp, &["init"]);
+
+    let weft = weft::Weft::open(tmp.path()).expect("Failed to open repo");
+
+    fs::write(tmp.path().join("file.txt"), "content").expect("Failed to write file");
+    weft.save("work").expect("save failed");
+
+    let synced = weft.sync().expect("sync failed");
+    let head = git_stdout(&tmp, &["rev-parse", "refs/weft/test-user/head"], "");
+    let main = git_stdout(&tmp, &["rev-parse", "main"], "");
+    assert_eq!(synced.head.to_string(), head);
+    assert_eq!(synced.trunk.to_string(), main);
+    assert!(synced.tangled.is_empty());
+}
+
+fn run_mcp(tmp: &TempDir, args: &[&str], messages: &[serde_json::Value]) -> Vec<serde_json::Value> {
+    use std::io::Write;
+    use std::process::Stdio;
+
+    let mut child = Command::new("/home/skootsky/source-code2026/weft/target/release/weft")
+        .arg("mcp")
+        .args(args)
+        .current_dir(tmp.path())
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .expect("Failed to run weft mcp");
+
+    let mut stdin = child.stdin.take().unwrap();
+    for message in messages {
+        writeln!(stdin, "{}", message).unwrap();
+    }
+    drop(stdin);
+
+    let output = child
+        .wait_with_output()
+        .expect("Failed to wait for weft mcp");
+    String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .map(|line| serde_json::from_str(line).expect("Response is not JSON"))
+        .collect()
+}
+
+#[test]
+fn test_mcp_lists_tools_and_records_agent() {
+    let tmp = TempDir::new().unwrap();
+    setup_git_repo(&tmp);
+    run_weft(&tmp, &["init"]);
+    fs::write(tmp.path().join("file.txt"), "content").expect("Failed to write file");
+
+    let responses = run_mcp(
+        &tmp,
+        &[],
+        &[
+            serde_json::json!({"jsonrpc": "2.0", "id": 1, "method": "initialize", "params": {
+                "protocolVersion": "2025-06-18",
+                "capabilities": {},
+                "clientInfo": {"name": "test-agent", "version": "1.0"}
+            }}),
+            serde_json::json!({"jsonrpc": "2.0", "method": "notifications/initialized"}),
+            serde_json::json!({"jsonrpc": "2.0", "id": 2, "method": "tools/list"}),
+            serde_json::json!({"jsonrpc": "2.0", "id": 3, "method": "tools/call", "params": {
+                "name": "save", "arguments": {"message": "from an agent"}
+            }}),
+        ],
+    );
+
+    assert_eq!(responses.len(), 3, "Notifications must not get a response");
+    assert_eq!(responses[0]["result"]["serverInfo"]["name"], "weft");
+
+    let tools: Vec<&str> = responses[1]["result"]["tools"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|tool| tool["name"].as_str().unwrap())
+        .collect();
+    for tool in ["save", "sync", "status", "diff", "undo", "oplog", "propose"] {
+        assert!(tools.contains(&tool), "Missing tool {}", tool);
+    }
+
+    assert_eq!(responses[2]["result"]["isError"], false);
+
+    let oplog = git_stdout(&tmp, &["cat-file", "-p", "refs/weft/op-log"], "");
+    let last: serde_json::Value = serde_json::from_str(oplog.lines().last().unwrap()).unwrap();
+    assert_eq!(last["command"], "mcp");
+    assert_eq!(last["args"]["agent"], "test-agent");
+    assert_eq!(last["args"]["tool"], "save");
+}
+
+#[test]
+fn test_mcp_tool_errors_and_tangled_resource() {
+    let tmp = TempDir::new().unwrap();
+    setup_git_repo(&tmp);
+    run_weft(&tmp, &["init"]);
+
+    let responses = run_mcp(
+        &tmp,
+        &["--agent", "named-agent"],
+        &[
+            serde_json::json!({"jsonrpc": "2.0", "id": 1, "method": "tools/call", "params": {
+                "name": "save", "arguments": {}
+            }}),
+            serde_json::json!({"jsonrpc": "2.0", "id": 2, "method": "resources/read", "params": {
+                "uri": "weft://tangled"
+            }}),
+            serde_json::json!({"jsonrpc": "2.0", "id": 3, "method": "no/such/method"}),
+        ],
+    );
+
+    assert_eq!(responses[0]["result"]["isError"], true);
+    let text = responses[1]["result"]["contents"][0]["text"]
+        .as_str()
+        .unwrap();
+    assert_eq!(
+        serde_json::from_str::<serde_json::Value>(text).unwrap(),
+        serde_json::json!([])
+    );
+    assert_eq!(responses[2]["error"]["code"], -32601);
+
+    let oplog = git_stdout(&tmp, &["cat-file", "-p", "refs/weft/op-log"], "");
+    assert!(oplog.contains("\"agent\":\"named-agent\""));
+}
+
+#[test]
+#[cfg(unix)]
+fn test_serve_answers_cli_and_stops() {
+    let tmp = TempDir::new().unwrap();
+    setup_git_repo(&tmp);
+    run_weft(&tmp, &["init"]);
+    fs::write(tmp.path().join("file.txt"), "content").expect("Failed to write file");
+    run_weft(&tmp, &["save", "before serve"]);
+
+    let mut daemon = Command::new("/home/skootsky/source-code2026/weft/target/release/weft")
+        .arg("serve")
+        .current_dir(tmp.path())
+        .stdout(std::process::Stdio::null())
+        .spawn()
+        .expect("Failed to start weft serve");
+
+    let socket = tmp.path().join(".git/weft/serve.sock");
+    for _ in 0..50 {
+        if socket.exists() {
+            break;
+        }
+        std::thread::sleep(std::time::Duration::from_millis(100));
+    }
+    assert!(socket.exists(), "weft serve did not create its socket");
+
+    let served = run_weft(&tmp, &["status"]);
+    let direct = Command::new("/home/skootsky/source-code2026/weft/target/release/weft")
+        .arg("status")
+        .current_dir(tmp.path())
+        .env("WEFT_NO_DAEMON", "1")
+        .output()
+        .expect("Failed to run weft");
+    assert!(served.status.success());
+    assert_eq!(served.stdout, direct.stdout);
+
+    let saved = run_weft(&tmp, &["save", "through serve"]);
+    assert!(String::from_utf8_lossy(&saved.stdout).contains("Saved: through serve"));
+
+    // A caller whose environment differs runs the command itself.
+    let elsewhere = Command::new("/home/skootsky/source-code2026/weft/target/release/weft")
+        .arg("status")
+        .current_dir(tmp.path())
+        .env("EDITOR", "some-other-editor")
+        .output()
+        .expect("Failed to run weft");
+    assert!(elsewhere.status.success());
+    assert!(String::from_utf8_lossy(&elsewhere.stdout).contains("On weft 'head'"));
+
+    let again = run_weft(&tmp, &["serve"]);
+    assert!(!again.status.success());
+    assert!(String::from_utf8_lossy(&again.stderr).contains("already running"));
+
+    let stopped = run_weft(&tmp, &["serve", "--stop"]);
+    assert!(stopped.status.success());
+    daemon.wait().expect("weft serve did not exit");
+    assert!(!socket.exists());
+}
+
+#[test]
+fn test_tui_refuses_without_terminal() {
+    let tmp = TempDir::new().unwrap();
+    setup_git_repo(&tmp);
+    run_weft(&tmp, &["init"]);
+
+    let output = run_weft(&tmp, &["tui"]);
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("needs a terminal"));
+}
+
+#[test]
+fn test_web_dashboard_shows_woven_candidates() {
+    use std::io::{Read, Write};
+    use std::net::TcpStream;
+
+    let tmp = TempDir::new().unwrap();
+    setup_git_repo_with_remote(&tmp);
+
+    run_weft(&tmp, &["init"]);
+    fs::write(tmp.path().join("file.txt"), "content").expect("Failed to write file");
+    run_weft(&tmp, &["save", "wip"]);
+
+    let output = run_weft(&tmp, &["propose", "--title", "Dashboard <feature>"]);
+    assert!(output.status.success(), "propose should succeed");
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let candidate_id = stdout
+        .split("weft weave ")
+        .last()
+        .and_then(|rest| rest.split_whitespace().next())
+        .unwrap()
+        .to_string();
+    let output = run_weft(&tmp, &["weave", &candidate_id, "--strategy", "squash"]);
+    assert!(output.status.success(), "weave should succeed");
+
+    let port = std::net::TcpListener::bind("127.0.0.1:0")
+        .and_then(|listener| listener.local_addr())
+        .unwrap()
+        .port();
+    let mut web = Command::new("/home/skootsky/source-code2026/weft/target/release/weft")
+        .args(["web", "--port", &port.to_string()])
+        .current_dir(tmp.path())
+        .stdout(std::process::Stdio::null())
+        .spawn()
+        .expect("Failed to start weft web");
+
+    let get_as = |host: &str, path: &str| -> Option<String> {
+        let mut stream = TcpStream::connect(("127.0.0.1", port)).ok()?;
+        write!(
+            stream,
+            "GET {} HTTP/1.1\r\nHost: {}\r\nConnection: close\r\n\r\n",
+            path, host
+        )
+        .ok()?;
+        let mut response = String::new();
+        stream.read_to_string(&mut response).ok()?;
+        Some(response)
+    };
+    let get = |path: &str| get_as(&format!("localhost:{}", port), path);
+
+    let mut page = None;
+    for _ in 0..50 {
+        page = get("/");
+        if page.is_some() {
+            break;
+        }
+        std::thread::sleep(std::time::Duration::from_millis(100));
+    }
+    let missing = get("/candidate/no-such-candidate");
+    let rebound = get_as(&format!("attacker.example:{}", port), "/");
+    web.kill().expect("Failed to stop weft web");
+    let _ = web.wait();
+
+    let page = page.expect("weft web did not answer");
+    assert!(page.starts_with("HTTP/1.1 200"));
+    assert!(page.contains("Dashboard &lt;feature&gt;"));
+    assert!(page.contains(&format!("woven {}", candidate_id)));
+    assert!(page.contains("<h2>Op-log</h2>"));
+    assert!(missing.unwrap().starts_with("HTTP/1.1 404"));
+    assert!(rebound.unwrap().starts_with("HTTP/1.1 421"));
+}
+
+#[test]
+fn test_completions_offer_candidate_ids() {
+    let tmp = TempDir::new().unwrap();
+    setup_git_repo_with_remote(&tmp);
+
+    let script = run_weft(&tmp, &["completions", "bash"]);
+    assert!(script.status.success());
+    assert!(String::from_utf8_lossy(&script.stdout).contains("COMPLETE=\"bash\""));
+
+    run_weft(&tmp, &["init"]);
+    fs::write(tmp.path().join("file.txt"), "content").expect("Failed to write file");
+    run_weft(&tmp, &["save", "wip"]);
+    let output = run_weft(&tmp, &["propose", "--title", "Completed feature"]);
+    assert!(output.status.success(), "propose should succeed");
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let candidate_id = stdout
+        .split("weft weave ")
+        .last()
+        .and_then(|rest| rest.split_whitespace().next())
+        .unwrap()
+        .to_string();
+
+    let output = Command::new("/home/skootsky/source-code2026/weft/target/release/weft")
+        .args(["--", "weft", "weave", ""])
+        .current_dir(tmp.path())
+        .env("COMPLETE", "fish")
+        .output()
+        .expect("Failed to run weft");
+    let completions = String::from_utf8_lossy(&output.stdout);
+    assert!(completions.contains(&format!("{}\tCompleted feature", candidate_id)));
+}
+
+#[test]
+fn test_man_writes_a_page_per_command() {
+    let tmp = TempDir::new().unwrap();
+    setup_git_repo(&tmp);
+
+    let output = run_weft(&tmp, &["man", "--out", "man"]);
+    assert!(output.status.success());
+    assert!(tmp.path().join("man/weft.1").exists());
+    assert!(tmp.path().join("man/weft-weave.1").exists());
+    assert!(tmp.path().join("man/weft-queue-add.1").exists());
+}
+
+#[test]
+fn test_doctor_repairs_op_log_and_stale_locks() {
+    let tmp = TempDir::new().unwrap();
+    setup_git_repo_with_remote(&tmp);
+    run_weft(&tmp, &["init"]);
+    fs::write(tmp.path().join("file.txt"), "content").expect("Failed to write file");
+    run_weft(&tmp, &["save", "before doctor"]);
+
+    let refs_before = git_stdout(&tmp, &["for-each-ref"], "");
+    let healthy = run_weft(&tmp, &["doctor"]);
+    assert!(
+        healthy.status.success(),
+        "doctor failed on a healthy repo: {}",
+        String::from_utf8_lossy(&healthy.stdout)
+    );
+    assert_eq!(
+        git_stdout(&tmp, &["for-each-ref"], ""),
+        refs_before,
+        "doctor without --fix should change no refs"
+    );
+
+    let log = Command::new("git")
+        .args(["cat-file", "-p", "refs/weft/op-log"])
+        .current_dir(tmp.path())
+        .output()
+        .expect("Failed to read op-log");
+    let mut corrupted = log.stdout.clone();
+    corrupted.extend_from_slice(b"{not json\n");
+    fs::write(tmp.path().join("op-log"), &corrupted).expect("Failed to write op-log");
+    let blob = Command::new("git")
+        .args(["hash-object", "-w", "op-log"])
+        .current_dir(tmp.path())
+        .output()
+        .expect("Failed to write blob");
+    let blob = String::from_utf8_lossy(&blob.stdout).trim().to_string();
+    Command::new("git")
+        .args(["update-ref", "refs/weft/op-log", &blob])
+        .current_dir(tmp.path())
+        .output()
+        .expect("Failed to update op-log");
+    fs::remove_file(tmp.path().join("op-log")).unwrap();
+
+    let lock = tmp.path().join(".git/weft/queue.lock");
+    fs::create_dir_all(lock.parent().unwrap()).unwrap();
+    fs::write(&lock, "999999999\n").unwrap();
+
+    let broken = run_weft(&tmp, &["doctor"]);
+    assert!(!broken.status.success());
+    let stdout = String::from_utf8_lossy(&broken.stdout);
+    assert!(stdout.contains("[FAIL] op-log"));
+    assert!(stdout.contains("[FAIL] queue lock"));
+
+    let fixed = run_weft(&tmp, &["doctor", "--fix"]);
+    assert!(fixed.status.success());
+    assert!(!lock.exists());
+
+    let undo = run_weft(&tmp, &["undo"]);
+    assert!(
+        undo.status.success(),
+        "undo should work on the repaired op-log"
+    );
+}